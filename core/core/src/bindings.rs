@@ -28,7 +28,7 @@ impl StaticMessageExchange for MessageExchangeFfi {
     }
 }
 
-#[cfg(not(test))]
+#[cfg(target_arch = "wasm32")]
 #[link(wasm_import_module = "sf_host_unstable")]
 extern "C" {
     #[link_name = "message_exchange"]
@@ -47,7 +47,8 @@ extern "C" {
         out_len: Size,
     ) -> AbiResultRepr;
 }
-#[cfg(test)]
+// native builds (tests, native host) never talk to the host through FFI
+#[cfg(not(target_arch = "wasm32"))]
 extern "C" fn __import_message_exchange(
     _msg_ptr: Ptr<u8>,
     _msg_len: Size,
@@ -57,7 +58,7 @@ extern "C" fn __import_message_exchange(
 ) -> Size {
     unreachable!()
 }
-#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
 extern "C" fn __import_message_exchange_retrieve(
     _handle: Handle,
     _out_ptr: Ptr<u8>,
@@ -100,7 +101,7 @@ impl StaticStreamExchange for StreamExchangeFfi {
     }
 }
 
-#[cfg(not(test))]
+#[cfg(target_arch = "wasm32")]
 #[link(wasm_import_module = "sf_host_unstable")]
 extern "C" {
     #[link_name = "stream_read"]
@@ -112,7 +113,7 @@ extern "C" {
     #[link_name = "stream_close"]
     fn __import_stream_close(handle: Handle) -> AbiResultRepr;
 }
-#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
 extern "C" fn __import_stream_read(
     _handle: Handle,
     _out_ptr: Ptr<u8>,
@@ -120,7 +121,7 @@ extern "C" fn __import_stream_read(
 ) -> AbiResultRepr {
     unreachable!()
 }
-#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
 extern "C" fn __import_stream_write(
     _handle: Handle,
     _in_ptr: Ptr<u8>,
@@ -128,7 +129,7 @@ extern "C" fn __import_stream_write(
 ) -> AbiResultRepr {
     unreachable!()
 }
-#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
 extern "C" fn __import_stream_close(_handle: Handle) -> AbiResultRepr {
     unreachable!()
 }
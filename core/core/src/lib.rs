@@ -1,25 +1,38 @@
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex, PoisonError, TryLockError};
 
-use bindings::MessageExchangeFfi;
+use bindings::{MessageExchangeFfi, StreamExchangeFfi};
 
 use sf_std::{
     abi::{Ptr, Size},
+    unstable::config::set_setup_output_exception_in,
+    unstable::describe::{set_describe_output_exception_in, set_describe_output_result_in},
+    unstable::exception::{PerformException, PerformExceptionErrorCode},
     unstable::perform::{
-        set_perform_output_error_in, set_perform_output_exception_in, set_perform_output_result_in,
+        set_perform_batch_output_in, set_perform_output_error_in, set_perform_output_exception_in,
+        set_perform_output_result_in,
     },
+    v1::handshake::{handshake_in, HandshakeError, HostCapabilities},
 };
 
 mod bindings;
 mod observability;
 mod sf_core;
-use sf_core::{CoreConfiguration, OneClientCore};
+pub use sf_core::{CoreConfiguration, CoreConfigurationOverrides, OneClientCore};
 
 #[cfg(feature = "core_mock")]
 mod mock;
 
-static GLOBAL_STATE: Mutex<Option<OneClientCore>> = Mutex::new(None);
+#[cfg(feature = "native_host")]
+pub mod native;
+
+/// Core talking to the wasm host through `sf_host_unstable` imports.
+type FfiOneClientCore = OneClientCore<MessageExchangeFfi, StreamExchangeFfi>;
+
+/// The lock is only held to clone or replace the core, so it is free while a perform is suspended in the host (e.g. for cancellation).
+static GLOBAL_STATE: Mutex<Option<Arc<FfiOneClientCore>>> = Mutex::new(None);
 
 // WASI functions which would be automatically called from `_start`, but we need to explicitly call them since we are a lib.
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     fn __wasm_call_ctors();
     fn __wasm_call_dtors();
@@ -31,40 +44,96 @@ extern "C" {
 /// Initializes persistent core state.
 ///
 /// This function must not be called twice without calling teardown in between.
+///
+/// Configuration is loaded from environment and the `core-config` message. If it is invalid, setup sends `setup-output-exception`
+/// and the core stays uninitialized, the host should instantiate the module again.
 pub extern "C" fn __export_oneclient_core_setup() {
     #[cfg(feature = "core_mock")]
     return mock::__export_oneclient_core_setup();
 
     // call ctors first
-    unsafe { __wasm_call_ctors() };
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        __wasm_call_ctors()
+    };
 
     let mut lock = GLOBAL_STATE.lock().unwrap();
     if lock.is_some() {
         panic!("Already setup");
     }
 
-    // load config, but don't display the error yet since we haven't initialize logging yet
-    let (config, config_err) = match CoreConfiguration::from_env() {
-        Ok(c) => (c, None),
-        Err(err) => (CoreConfiguration::default(), Some(err)),
-    };
+    // negotiate and load config, but don't display anything yet since we haven't initialize logging yet
+    let handshake = negotiate_with_host();
+    let config = CoreConfiguration::from_env_and_host(MessageExchangeFfi);
 
-    // initialize observability
+    // initialize observability, with defaults if the configuration is invalid so that the error can be logged
+    let default_config = CoreConfiguration::default();
     // SAFETY: setup is only allowed to be called once
-    unsafe { observability::init(&config) };
+    unsafe { observability::init(config.as_ref().unwrap_or(&default_config)) };
 
     // now that we have logging we can start printing stuff
     tracing::debug!(target: "@user", "oneclient_core_setup called");
-    if let Some(err) = config_err {
-        tracing::error!(
-            target: "@user",
-            "Failed to load core configuration from environment: {}",
-            err
-        );
+    match handshake {
+        Ok(capabilities) => {
+            tracing::debug!(target: "@user", host = ?capabilities);
+
+            let unsupported = capabilities.unsupported(sf_std::unstable::MESSAGE_KINDS);
+            if !unsupported.is_empty() {
+                tracing::warn!(
+                    target: "@user",
+                    "Host does not support messages {:?}, features using them will fail",
+                    unsupported
+                );
+            }
+        }
+        Err(err) => tracing::warn!(target: "@user", "{}", err),
     }
 
+    let config = match config {
+        Ok(config) => config,
+        Err(err) => {
+            tracing::error!(target: "@user", "Failed to load core configuration: {}", err);
+
+            let exception = PerformException {
+                error_code: PerformExceptionErrorCode::InvalidConfiguration,
+                message: err.to_string(),
+                js_exception: None,
+            };
+            // hosts which can't receive the exception get it as a panic instead
+            if let Err(send_err) = set_setup_output_exception_in(exception, MessageExchangeFfi) {
+                panic!(
+                    "Invalid core configuration: {} (host did not accept the exception: {})",
+                    err, send_err
+                );
+            }
+            return;
+        }
+    };
+
     // here we panic on error because there is nothing to teardown
-    lock.replace(OneClientCore::new(&config).unwrap());
+    lock.replace(Arc::new(OneClientCore::new(&config).unwrap()));
+}
+
+/// Exchanges versions and supported messages with the host and applies the negotiated encoding and message kinds.
+///
+/// Panics if the host is incompatible. Hosts which predate the handshake are assumed to support all unstable messages,
+/// which is returned as an error to be logged.
+fn negotiate_with_host() -> Result<HostCapabilities, HandshakeError> {
+    match handshake_in(
+        env!("CARGO_PKG_VERSION"),
+        sf_std::unstable::MESSAGE_KINDS,
+        sf_std::abi::MessageEncoding::ALL,
+        MessageExchangeFfi,
+    ) {
+        Ok(capabilities) => {
+            sf_std::abi::set_message_encoding(capabilities.encoding);
+            sf_std::abi::set_supported_message_kinds(Some(capabilities.message_kinds.clone()));
+
+            Ok(capabilities)
+        }
+        Err(err @ HandshakeError::NotSupported(_)) => Err(err),
+        Err(err) => panic!("Incompatible host: {}", err),
+    }
 }
 
 #[no_mangle]
@@ -79,17 +148,25 @@ pub extern "C" fn __export_oneclient_core_teardown() {
 
     tracing::debug!(target: "@user", "oneclient_core_teardown called");
 
-    match GLOBAL_STATE.try_lock() {
-        Err(_) => panic!("Global state lock already locked: perform most likely panicked"),
-        Ok(lock) if lock.is_none() => panic!("Not setup or already torn down"),
-        Ok(mut lock) => {
-            let state = lock.take();
-            std::mem::drop(state); // just to be explicit, would be dropped implicitly anyway
-
-            // call dtors last
-            unsafe { __wasm_call_dtors() };
-        }
+    let mut lock = match GLOBAL_STATE.try_lock() {
+        Ok(lock) => lock,
+        // the lock is only held to clone or replace the state, which leaves it consistent even after a panic
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(TryLockError::WouldBlock) => panic!("Global state lock already locked"),
+    };
+    if lock.is_none() {
+        panic!("Not setup or already torn down");
     }
+
+    // performs still in flight keep their own reference to the state
+    let state = lock.take();
+    std::mem::drop(state); // just to be explicit, would be dropped implicitly anyway
+
+    // call dtors last
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        __wasm_call_dtors()
+    };
 }
 
 #[no_mangle]
@@ -99,27 +176,111 @@ pub extern "C" fn __export_oneclient_core_teardown() {
 ///
 /// Must be called after [__export_oneclient_core_setup] and before [__export_oneclient_core_teardown].
 ///
-/// All information about map to be performed will be retrieved through messages.
+/// All information about map to be performed will be retrieved through messages. The perform id assigned
+/// by the core is sent in the first message (`perform-input`), before the perform suspends for the first time.
 pub extern "C" fn __export_oneclient_core_perform() {
     #[cfg(feature = "core_mock")]
     return mock::__export_oneclient_core_perform();
 
-    let mut lock = GLOBAL_STATE.lock().unwrap();
-    let state: &mut OneClientCore = lock
-        .as_mut()
+    let state: Arc<FfiOneClientCore> = GLOBAL_STATE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
         .expect("Global state missing: has oneclient_core_setup been called?");
 
-    match state.perform() {
-        Ok(Ok(result)) => set_perform_output_result_in(result, MessageExchangeFfi),
-        Ok(Err(error)) => set_perform_output_error_in(error, MessageExchangeFfi),
+    let perform_id = sf_core::new_perform_id();
+    match state.perform(perform_id) {
+        Ok(Ok(result)) => set_perform_output_result_in(perform_id, result, MessageExchangeFfi),
+        Ok(Err(error)) => set_perform_output_error_in(perform_id, error, MessageExchangeFfi),
         Err(exception) => {
             tracing::error!(target: "@user", "Perform failed unexpectedly: {}", exception);
 
-            set_perform_output_exception_in(exception, MessageExchangeFfi)
+            set_perform_output_exception_in(perform_id, exception, MessageExchangeFfi)
+        }
+    }
+}
+
+#[no_mangle]
+#[export_name = "oneclient_core_perform_batch"]
+#[cfg_attr(feature = "core_mock", allow(unreachable_code))]
+/// Executes a batch of performs.
+///
+/// Same as [__export_oneclient_core_perform], but takes inputs of all performs in the batch through the `perform-batch-input`
+/// message and sends their outcomes in one `perform-batch-output` message. An exception is sent as with a single perform
+/// if the batch input could not be taken.
+pub extern "C" fn __export_oneclient_core_perform_batch() {
+    #[cfg(feature = "core_mock")]
+    return mock::__export_oneclient_core_perform_batch();
+
+    let state: Arc<FfiOneClientCore> = GLOBAL_STATE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .expect("Global state missing: has oneclient_core_setup been called?");
+
+    let perform_id = sf_core::new_perform_id();
+    match state.perform_batch(perform_id) {
+        Ok(outcomes) => set_perform_batch_output_in(perform_id, &outcomes, MessageExchangeFfi),
+        Err(exception) => {
+            tracing::error!(target: "@user", "Perform batch failed unexpectedly: {}", exception);
+
+            set_perform_output_exception_in(perform_id, exception, MessageExchangeFfi)
+        }
+    }
+}
+
+#[no_mangle]
+#[export_name = "oneclient_core_describe"]
+#[cfg_attr(feature = "core_mock", allow(unreachable_code))]
+/// Describes a profile and a provider.
+///
+/// Must be called after [__export_oneclient_core_setup] and before [__export_oneclient_core_teardown].
+///
+/// Urls of the documents are retrieved through the `describe-input` message and the description is sent
+/// in `describe-output-result`. Documents are loaded through the same caches as for performs.
+pub extern "C" fn __export_oneclient_core_describe() {
+    #[cfg(feature = "core_mock")]
+    return mock::__export_oneclient_core_describe();
+
+    let state: Arc<FfiOneClientCore> = GLOBAL_STATE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+        .expect("Global state missing: has oneclient_core_setup been called?");
+
+    match state.describe() {
+        Ok(description) => set_describe_output_result_in(description, MessageExchangeFfi),
+        Err(exception) => {
+            tracing::error!(target: "@user", "Describe failed: {}", exception);
+
+            set_describe_output_exception_in(exception, MessageExchangeFfi)
         }
     }
 }
 
+#[no_mangle]
+#[export_name = "oneclient_core_cancel"]
+/// Requests cancellation of all performs in flight.
+///
+/// Does not lock the global state, so it can be called while a perform is suspended (e.g. waiting for an http response).
+/// The performs stop at the next cancellation point and output a `Cancelled` exception.
+pub extern "C" fn __export_oneclient_core_cancel() {
+    tracing::debug!(target: "@user", "oneclient_core_cancel called");
+
+    sf_core::cancellation::request_all();
+}
+
+#[no_mangle]
+#[export_name = "oneclient_core_cancel_perform"]
+/// Requests cancellation of the perform with `perform_id`, see [__export_oneclient_core_cancel].
+///
+/// Does nothing if the perform is not in flight.
+pub extern "C" fn __export_oneclient_core_cancel_perform(perform_id: u32) {
+    tracing::debug!(target: "@user", perform_id, "oneclient_core_cancel_perform called");
+
+    sf_core::cancellation::request(perform_id);
+}
+
 #[cfg(feature = "asyncify")]
 #[no_mangle]
 #[export_name = "asyncify_alloc_stack"]
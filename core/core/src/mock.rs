@@ -1,18 +1,55 @@
 //! Mocked behaviour of core to test Host applications
 //!
-//! Use usecase to request some behaviour for perform:
-//! - CORE_PERFORM_PANIC
-//! - CORE_PERFORM_TRUE
-//! - CORE_PERFORM_INPUT_VALIDATION_ERROR
-
-use sf_std::unstable::{
-    exception::{PerformException, PerformExceptionErrorCode},
-    perform::{set_perform_output_exception_in, set_perform_output_result_in, PerformInput},
-    HostValue,
+//! Each perform runs a scenario, taken from (in order):
+//! - the `$mock_scenario` field of the map input object
+//! - the file at map url, if it ends with `.mock.json`
+//! - one of the built-in scenarios named by usecase:
+//!   - CORE_PERFORM_PANIC
+//!   - CORE_PERFORM_TRUE
+//!   - CORE_PERFORM_INPUT_VALIDATION_ERROR
+//!
+//! A scenario runs its steps in order and then outputs its outcome:
+//! ```json
+//! {
+//!   "steps": [
+//!     { "kind": "http-call", "method": "GET", "url": "https://example.com", "headers": {}, "body": null, "chunk_size": 16, "chunk_delay_ms": 10 },
+//!     { "kind": "sleep", "duration_ms": 100 }
+//!   ],
+//!   "outcome": { "kind": "result", "result": true }
+//! }
+//! ```
+//! Outcome is one of `result`, `error` (with `error`), `exception` (with `exception` as in `perform-output-exception`)
+//! or `panic` (with `message`). Without outcome the result is the list of responses to the `http-call` steps.
+//!
+//! Describe outputs an empty description, unless the profile url names one of the built-in scenarios:
+//! - CORE_DESCRIBE_PANIC
+//! - CORE_DESCRIBE_EXCEPTION
+
+use std::{io::Read, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use sf_std::{
+    unstable::{
+        describe::{
+            set_describe_output_exception_in, set_describe_output_result_in, DescribeInput,
+        },
+        exception::{PerformException, PerformExceptionErrorCode},
+        fs::FsConvenience,
+        http::{FetchOptions, HttpRedirect, HttpRequest},
+        perform::{
+            set_perform_batch_output_in, set_perform_output_error_in,
+            set_perform_output_exception_in, set_perform_output_result_in, PerformInput,
+            PerformOutcome,
+        },
+        timer::TimerConvenience,
+        HostValue,
+    },
+    HeadersMultiMap,
 };
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use crate::bindings::MessageExchangeFfi;
+use crate::bindings::{MessageExchangeFfi, StreamExchangeFfi};
 
 extern "C" {
     fn __wasm_call_ctors();
@@ -36,22 +73,248 @@ pub fn __export_oneclient_core_teardown() {
 }
 
 pub fn __export_oneclient_core_perform() {
-    let perform_input = PerformInput::take_in(MessageExchangeFfi).unwrap();
+    let perform_id = crate::sf_core::new_perform_id();
+    let perform_input = PerformInput::take_in(perform_id, MessageExchangeFfi).unwrap();
 
     tracing::debug!("mocked oneclient core perform {}", perform_input.usecase);
 
-    match perform_input.usecase.as_str() {
-        "CORE_PERFORM_PANIC" => panic!("Requested panic!"),
-        "CORE_PERFORM_TRUE" => {
-            set_perform_output_result_in(HostValue::Bool(true), MessageExchangeFfi)
+    match mock_outcome(&perform_input) {
+        PerformOutcome::Result { result } => {
+            set_perform_output_result_in(perform_id, result, MessageExchangeFfi)
+        }
+        PerformOutcome::Error { error } => {
+            set_perform_output_error_in(perform_id, error, MessageExchangeFfi)
         }
-        "CORE_PERFORM_INPUT_VALIDATION_ERROR" => set_perform_output_exception_in(
+        PerformOutcome::Exception { exception } => {
+            set_perform_output_exception_in(perform_id, exception, MessageExchangeFfi)
+        }
+    }
+}
+
+/// Each perform in the batch behaves as if it was performed alone.
+pub fn __export_oneclient_core_perform_batch() {
+    let perform_id = crate::sf_core::new_perform_id();
+    let perform_inputs = PerformInput::take_batch_in(perform_id, MessageExchangeFfi).unwrap();
+
+    tracing::debug!(
+        "mocked oneclient core perform batch of {}",
+        perform_inputs.len()
+    );
+
+    let outcomes: Vec<PerformOutcome> = perform_inputs.iter().map(mock_outcome).collect();
+    set_perform_batch_output_in(perform_id, &outcomes, MessageExchangeFfi);
+}
+
+pub fn __export_oneclient_core_describe() {
+    let describe_input = DescribeInput::take_in(MessageExchangeFfi).unwrap();
+
+    tracing::debug!(
+        "mocked oneclient core describe {:?}",
+        describe_input.profile_url
+    );
+
+    match describe_input.profile_url.as_deref() {
+        Some("CORE_DESCRIBE_PANIC") => panic!("Requested panic!"),
+        Some("CORE_DESCRIBE_EXCEPTION") => set_describe_output_exception_in(
             PerformException {
-                error_code: PerformExceptionErrorCode::InputValidationError,
-                message: "Test validation error".to_string(),
+                error_code: PerformExceptionErrorCode::DocumentCacheError,
+                message: "Test describe exception".to_string(),
+                js_exception: None,
             },
             MessageExchangeFfi,
         ),
-        _ => panic!("Unknown usecase: {}", perform_input.usecase),
-    };
+        _ => set_describe_output_result_in(serde_json::json!({}), MessageExchangeFfi),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MockScenario {
+    #[serde(default)]
+    steps: Vec<MockStep>,
+    #[serde(default)]
+    outcome: Option<MockOutcome>,
+}
+impl MockScenario {
+    const INPUT_FIELD: &'static str = "$mock_scenario";
+    const FILE_SUFFIX: &'static str = ".mock.json";
+    const FILE_URL_PREFIX: &'static str = "file://";
+
+    fn from_perform_input(perform_input: &PerformInput) -> Self {
+        if let HostValue::Object(ref input) = perform_input.map_input {
+            if let Some(scenario) = input.get(Self::INPUT_FIELD) {
+                let scenario = serde_json::to_value(scenario).unwrap();
+                return serde_json::from_value(scenario).expect("Invalid mock scenario in input");
+            }
+        }
+
+        if perform_input.map_url.ends_with(Self::FILE_SUFFIX) {
+            let path = perform_input
+                .map_url
+                .strip_prefix(Self::FILE_URL_PREFIX)
+                .unwrap_or(&perform_input.map_url);
+            let scenario = FsConvenience::<MessageExchangeFfi, StreamExchangeFfi>::read(path)
+                .expect("Failed to read mock scenario file");
+            return serde_json::from_slice(&scenario).expect("Invalid mock scenario file");
+        }
+
+        Self::builtin(&perform_input.usecase)
+    }
+
+    fn builtin(usecase: &str) -> Self {
+        let outcome = match usecase {
+            "CORE_PERFORM_PANIC" => MockOutcome::Panic {
+                message: "Requested panic!".to_string(),
+            },
+            "CORE_PERFORM_TRUE" => MockOutcome::Result {
+                result: HostValue::Bool(true),
+            },
+            "CORE_PERFORM_INPUT_VALIDATION_ERROR" => MockOutcome::Exception {
+                exception: PerformException {
+                    error_code: PerformExceptionErrorCode::InputValidationError,
+                    message: "Test validation error".to_string(),
+                    js_exception: None,
+                },
+            },
+            _ => panic!("Unknown usecase: {}", usecase),
+        };
+
+        Self {
+            steps: Vec::new(),
+            outcome: Some(outcome),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+enum MockStep {
+    /// Sends `http-call` and reads the whole response body, `chunk_size` bytes at a time if set.
+    HttpCall {
+        method: String,
+        url: String,
+        #[serde(default)]
+        headers: HeadersMultiMap,
+        #[serde(default)]
+        body: Option<String>,
+        #[serde(default)]
+        chunk_size: Option<usize>,
+        /// Sleeps between reading chunks to simulate a slow consumer.
+        #[serde(default)]
+        chunk_delay_ms: u64,
+    },
+    Sleep {
+        duration_ms: u64,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+enum MockOutcome {
+    Result { result: HostValue },
+    Error { error: HostValue },
+    Exception { exception: PerformException },
+    Panic { message: String },
+}
+
+/// Response to an `http-call` step, as returned in the default outcome.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum MockResponse {
+    Ok {
+        status: u16,
+        headers: HeadersMultiMap,
+        /// Body decoded as UTF-8, lossily.
+        body: String,
+        /// Number of reads the body took.
+        chunks: usize,
+    },
+    Err {
+        error: String,
+    },
+}
+
+fn mock_outcome(perform_input: &PerformInput) -> PerformOutcome {
+    let scenario = MockScenario::from_perform_input(perform_input);
+
+    let responses: Vec<MockResponse> = scenario.steps.into_iter().filter_map(run_step).collect();
+
+    match scenario.outcome {
+        None => PerformOutcome::Result {
+            result: serde_json::to_value(responses)
+                .and_then(serde_json::from_value)
+                .unwrap(),
+        },
+        Some(MockOutcome::Result { result }) => PerformOutcome::Result { result },
+        Some(MockOutcome::Error { error }) => PerformOutcome::Error { error },
+        Some(MockOutcome::Exception { exception }) => PerformOutcome::Exception { exception },
+        Some(MockOutcome::Panic { message }) => panic!("{}", message),
+    }
+}
+
+/// Runs the step, returning the response if it was an `http-call`.
+fn run_step(step: MockStep) -> Option<MockResponse> {
+    tracing::debug!("mocked step {:?}", step);
+
+    match step {
+        MockStep::Sleep { duration_ms } => {
+            TimerConvenience::<MessageExchangeFfi>::sleep(Duration::from_millis(duration_ms))
+                .unwrap();
+            None
+        }
+        MockStep::HttpCall {
+            method,
+            url,
+            headers,
+            body,
+            chunk_size,
+            chunk_delay_ms,
+        } => {
+            let response = HttpRequest::<MessageExchangeFfi, StreamExchangeFfi>::fetch(
+                &method,
+                &url,
+                FetchOptions {
+                    headers: &headers,
+                    query: &Default::default(),
+                    body: body.as_ref().map(|body| body.as_bytes()),
+                    redirect: HttpRedirect::default(),
+                    transport: None,
+                },
+            )
+            .and_then(|request| request.into_response());
+
+            Some(match response {
+                Err(err) => MockResponse::Err {
+                    error: err.to_string(),
+                },
+                Ok(mut response) => {
+                    let mut body = Vec::new();
+                    let mut chunk = vec![0u8; chunk_size.unwrap_or(8 * 1024).max(1)];
+                    let mut chunks = 0;
+                    loop {
+                        let read = response.body().read(&mut chunk).unwrap();
+                        if read == 0 {
+                            break;
+                        }
+                        body.extend_from_slice(&chunk[..read]);
+                        chunks += 1;
+
+                        if chunk_delay_ms > 0 {
+                            TimerConvenience::<MessageExchangeFfi>::sleep(Duration::from_millis(
+                                chunk_delay_ms,
+                            ))
+                            .unwrap();
+                        }
+                    }
+
+                    MockResponse::Ok {
+                        status: response.status(),
+                        headers: response.headers().clone(),
+                        body: String::from_utf8_lossy(&body).into_owned(),
+                        chunks,
+                    }
+                }
+            })
+        }
+    }
 }
@@ -0,0 +1,437 @@
+//! In-process implementation of the host side of the messages and streams.
+//!
+//! The exchanges are static (see [StaticMessageExchange]), so the host state lives in a thread local
+//! next to them. The core is synchronous, so a perform and everything it opens stays on one thread.
+
+use std::{
+    cell::RefCell,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use map_std::handle_map::HandleMap;
+use sf_std::{
+    abi::{
+        Handle, MessageExchange, Size, StaticMessageExchange, StaticStreamExchange, StreamExchange,
+    },
+    unstable::{
+        http::{HttpRedirect, HttpRedirectMode, HttpTransport},
+        perform::PerformInput,
+        ErrorCode,
+    },
+    HeaderName, HeadersMultiMap,
+};
+
+thread_local! {
+    static HOST: RefCell<NativeHost> = RefCell::new(NativeHost::default());
+}
+
+/// Sets the input returned to the next `perform-input` message on this thread.
+pub fn set_perform_input(input: PerformInput) {
+    HOST.with(|host| host.borrow_mut().perform_input = Some(input));
+}
+
+/// Sets the inputs returned to the next `perform-batch-input` message on this thread.
+pub fn set_perform_batch_input(inputs: Vec<PerformInput>) {
+    HOST.with(|host| host.borrow_mut().perform_batch_input = Some(inputs));
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum NativeMessage {
+    /// Performs run one at a time on a thread, so the `perform_id` is not needed to find the input.
+    PerformInput,
+    PerformBatchInput,
+    HttpCall {
+        method: String,
+        url: String,
+        headers: HeadersMultiMap,
+        body: Option<Vec<u8>>,
+        redirect: HttpRedirect,
+        transport: Option<HttpTransport>,
+    },
+    HttpCallHead {
+        handle: Handle,
+    },
+    FileOpen {
+        path: String,
+        read: bool,
+        write: bool,
+        append: bool,
+        truncate: bool,
+        create: bool,
+        create_new: bool,
+    },
+    Sleep {
+        duration_ms: u64,
+    },
+}
+
+enum NativeStream {
+    File(File),
+    /// Response bodies can only be read.
+    Body(Box<dyn Read + Send + Sync>),
+}
+
+#[derive(Default)]
+struct NativeHost {
+    perform_input: Option<PerformInput>,
+    perform_batch_input: Option<Vec<PerformInput>>,
+    responses: HandleMap<ureq::Response>,
+    streams: HandleMap<NativeStream>,
+}
+impl NativeHost {
+    /// Used when the os error doesn't carry an errno.
+    const ERRNO_IO: i32 = 5;
+
+    fn handle_message(&mut self, message: &[u8]) -> Value {
+        let message: NativeMessage = match serde_json::from_slice(message) {
+            Ok(message) => message,
+            // also matches responses which only have a message
+            Err(err) => {
+                return json!({
+                    "kind": "err",
+                    "error_code": ErrorCode::NetworkError,
+                    "message": format!("Unknown message: {}", err)
+                })
+            }
+        };
+
+        match message {
+            NativeMessage::PerformInput => match self.perform_input.take() {
+                None => json!({
+                    "kind": "err",
+                    "error_code": ErrorCode::NetworkError,
+                    "message": "No perform input was set"
+                }),
+                Some(input) => json!({
+                    "kind": "ok",
+                    "profile_url": input.profile_url,
+                    "provider_url": input.provider_url,
+                    "map_url": input.map_url,
+                    "usecase": input.usecase,
+                    "map_input": input.map_input,
+                    "map_parameters": input.map_parameters,
+                    "map_security": input.map_security,
+                    "failover": input.failover,
+                    "failover_policy": input.failover_policy,
+                    "dry_run": input.dry_run
+                }),
+            },
+            NativeMessage::PerformBatchInput => match self.perform_batch_input.take() {
+                None => json!({
+                    "kind": "err",
+                    "error_code": ErrorCode::NetworkError,
+                    "message": "No perform batch input was set"
+                }),
+                Some(inputs) => json!({ "kind": "ok", "performs": inputs }),
+            },
+            NativeMessage::HttpCall {
+                method,
+                url,
+                headers,
+                body,
+                redirect,
+                transport,
+            } => match Self::fetch(
+                &method,
+                &url,
+                &headers,
+                body.as_deref(),
+                redirect,
+                transport,
+            ) {
+                Ok(response) => json!({ "kind": "ok", "handle": self.responses.insert(response) }),
+                Err((error_code, message)) => {
+                    json!({ "kind": "err", "error_code": error_code, "message": message })
+                }
+            },
+            NativeMessage::HttpCallHead { handle } => match self.responses.try_remove(handle) {
+                None => json!({
+                    "kind": "err",
+                    "error_code": ErrorCode::NetworkInvalidHandle,
+                    "message": format!("Unknown http call handle {}", handle)
+                }),
+                Some(response) => {
+                    let mut headers = HeadersMultiMap::new();
+                    for name in response.headers_names() {
+                        let values = response.all(&name).into_iter().map(String::from).collect();
+                        headers.insert(HeaderName::from(name.as_str()), values);
+                    }
+
+                    json!({
+                        "kind": "ok",
+                        "status": response.status(),
+                        "headers": headers,
+                        "body_stream": self.streams.insert(NativeStream::Body(response.into_reader()))
+                    })
+                }
+            },
+            NativeMessage::FileOpen {
+                path,
+                read,
+                write,
+                append,
+                truncate,
+                create,
+                create_new,
+            } => {
+                let file = OpenOptions::new()
+                    .read(read)
+                    .write(write)
+                    .append(append)
+                    .truncate(truncate)
+                    .create(create)
+                    .create_new(create_new)
+                    .open(path);
+
+                match file {
+                    Ok(file) => {
+                        json!({ "kind": "ok", "stream": self.streams.insert(NativeStream::File(file)) })
+                    }
+                    // the core turns errno back into an io error, natively these are the os errno values
+                    Err(err) => json!({
+                        "kind": "err",
+                        "errno": err.raw_os_error().unwrap_or(Self::ERRNO_IO)
+                    }),
+                }
+            }
+            NativeMessage::Sleep { duration_ms } => {
+                std::thread::sleep(Duration::from_millis(duration_ms));
+                json!({ "kind": "ok" })
+            }
+        }
+    }
+
+    /// Joins multiple values of a header, since ureq only sets one value per header.
+    ///
+    /// Values are joined as allowed by RFC 9110, except cookies which RFC 6265 requires to be sent in one header separated by "; ".
+    fn header_value(name: &str, values: &[String]) -> String {
+        if name.eq_ignore_ascii_case("cookie") {
+            values.join("; ")
+        } else {
+            values.join(", ")
+        }
+    }
+
+    fn fetch(
+        method: &str,
+        url: &str,
+        headers: &HeadersMultiMap,
+        body: Option<&[u8]>,
+        redirect: HttpRedirect,
+        transport: Option<HttpTransport>,
+    ) -> Result<ureq::Response, (ErrorCode, String)> {
+        let mut agent = ureq::AgentBuilder::new().redirects(match redirect.mode {
+            HttpRedirectMode::Follow => redirect.max_hops,
+            HttpRedirectMode::Manual | HttpRedirectMode::Error => 0,
+        });
+        if let Some(transport) = transport {
+            if transport.ca_pem.is_some() || transport.client_certificate.is_some() {
+                return Err((
+                    ErrorCode::NetworkError,
+                    "Custom CA and client certificates are not supported by the native host"
+                        .to_string(),
+                ));
+            }
+            if let Some(proxy_url) = transport.proxy_url {
+                let proxy = ureq::Proxy::new(proxy_url)
+                    .map_err(|err| (ErrorCode::NetworkInvalidUrl, err.to_string()))?;
+                agent = agent.proxy(proxy);
+            }
+        }
+
+        let mut request = agent.build().request(method, url);
+        for (name, values) in headers {
+            let name = name.to_string();
+            request = request.set(&name, &Self::header_value(&name, values));
+        }
+
+        let result = match body {
+            Some(body) => request.send_bytes(body),
+            None => request.call(),
+        };
+        let response = match result {
+            Ok(response) => response,
+            // error statuses are responses like any other for the map
+            Err(ureq::Error::Status(_, response)) => response,
+            Err(err) => {
+                let error_code = match err.kind() {
+                    ureq::ErrorKind::InvalidUrl | ureq::ErrorKind::UnknownScheme => {
+                        ErrorCode::NetworkInvalidUrl
+                    }
+                    ureq::ErrorKind::Dns => ErrorCode::NetworkHostNotFound,
+                    ureq::ErrorKind::ConnectionFailed => ErrorCode::NetworkConnectionRefused,
+                    _ => ErrorCode::NetworkError,
+                };
+
+                return Err((error_code, err.to_string()));
+            }
+        };
+
+        if redirect.mode == HttpRedirectMode::Error && (300..400).contains(&response.status()) {
+            return Err((
+                ErrorCode::NetworkError,
+                format!("Redirect received from {} {}", method, url),
+            ));
+        }
+
+        Ok(response)
+    }
+
+    fn stream_mut(&mut self, handle: Handle) -> io::Result<&mut NativeStream> {
+        self.streams
+            .get_mut(handle)
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+}
+
+pub struct NativeMessageExchange;
+impl MessageExchange for NativeMessageExchange {
+    fn invoke(&self, message: &[u8]) -> Vec<u8> {
+        let response = HOST.with(|host| host.borrow_mut().handle_message(message));
+
+        serde_json::to_vec(&response).unwrap()
+    }
+}
+impl StaticMessageExchange for NativeMessageExchange {
+    fn instance() -> Self {
+        Self
+    }
+}
+
+pub struct NativeStreamExchange;
+impl StreamExchange for NativeStreamExchange {
+    fn read(&self, handle: Handle, buf: &mut [u8]) -> io::Result<Size> {
+        HOST.with(|host| match host.borrow_mut().stream_mut(handle)? {
+            NativeStream::File(file) => file.read(buf),
+            NativeStream::Body(body) => body.read(buf),
+        })
+    }
+
+    fn write(&self, handle: Handle, buf: &[u8]) -> io::Result<Size> {
+        HOST.with(|host| match host.borrow_mut().stream_mut(handle)? {
+            NativeStream::File(file) => file.write(buf),
+            NativeStream::Body(_) => Err(io::ErrorKind::Unsupported.into()),
+        })
+    }
+
+    fn close(&self, handle: Handle) -> io::Result<()> {
+        HOST.with(|host| match host.borrow_mut().streams.try_remove(handle) {
+            None => Err(io::ErrorKind::NotFound.into()),
+            Some(_) => Ok(()),
+        })
+    }
+}
+impl StaticStreamExchange for NativeStreamExchange {
+    fn instance() -> Self {
+        Self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sf_std::unstable::{
+        fs::FsConvenience,
+        perform::{FailoverPolicy, PerformInput},
+        HostValue,
+    };
+
+    use super::*;
+
+    type Fs = FsConvenience<NativeMessageExchange, NativeStreamExchange>;
+
+    #[test]
+    fn test_perform_input_is_taken_once() {
+        set_perform_input(PerformInput {
+            profile_url: "file://profile.supr".to_string(),
+            provider_url: "file://provider.json".to_string(),
+            map_url: "file://profile.provider.map.js".to_string(),
+            usecase: "Example".to_string(),
+            map_input: HostValue::Bool(true),
+            map_parameters: HostValue::None,
+            map_security: HostValue::None,
+            failover: Vec::new(),
+            failover_policy: FailoverPolicy::default(),
+            dry_run: false,
+        });
+
+        let input = PerformInput::take_in(1, NativeMessageExchange).unwrap();
+        assert_eq!(input.usecase, "Example");
+        assert!(matches!(input.map_input, HostValue::Bool(true)));
+
+        assert!(PerformInput::take_in(2, NativeMessageExchange).is_err());
+    }
+
+    #[test]
+    fn test_perform_batch_input() {
+        set_perform_batch_input(vec![
+            PerformInput {
+                profile_url: "file://profile.supr".to_string(),
+                provider_url: "file://first.provider.json".to_string(),
+                map_url: "file://profile.first.map.js".to_string(),
+                usecase: "Example".to_string(),
+                map_input: HostValue::Bool(true),
+                map_parameters: HostValue::None,
+                map_security: HostValue::None,
+                failover: Vec::new(),
+                failover_policy: FailoverPolicy::default(),
+                dry_run: false,
+            },
+            PerformInput {
+                profile_url: "file://profile.supr".to_string(),
+                provider_url: "file://second.provider.json".to_string(),
+                map_url: "file://profile.second.map.js".to_string(),
+                usecase: "Example".to_string(),
+                map_input: HostValue::Bool(false),
+                map_parameters: HostValue::None,
+                map_security: HostValue::None,
+                failover: Vec::new(),
+                failover_policy: FailoverPolicy::default(),
+                dry_run: false,
+            },
+        ]);
+
+        let inputs = PerformInput::take_batch_in(1, NativeMessageExchange).unwrap();
+        assert_eq!(inputs.len(), 2);
+        assert_eq!(inputs[1].provider_url, "file://second.provider.json");
+        assert!(matches!(inputs[1].map_input, HostValue::Bool(false)));
+    }
+
+    #[test]
+    fn test_unknown_message() {
+        let response = NativeHost::default().handle_message(br#"{"kind":"unknown"}"#);
+
+        assert_eq!(response["kind"], "err");
+        assert_eq!(response["error_code"], "network:error");
+        assert!(response["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Unknown message"));
+    }
+
+    #[test]
+    fn test_header_value() {
+        let values = vec!["a=1".to_string(), "b=2".to_string()];
+
+        assert_eq!(NativeHost::header_value("Cookie", &values), "a=1; b=2");
+        assert_eq!(NativeHost::header_value("accept", &values), "a=1, b=2");
+    }
+
+    #[test]
+    fn test_file_streams() {
+        let path = std::env::temp_dir().join(format!("native_host_test_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        Fs::write(path, b"hello native").unwrap();
+        assert_eq!(Fs::read_to_string(path).unwrap(), "hello native");
+        std::fs::remove_file(path).unwrap();
+
+        let err = Fs::read(path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,198 @@
+//! Native build of the core for embedding OneSDK in Rust without a wasm runtime.
+//!
+//! The core talks to [host] through the same messages as to the wasm host, only in-process.
+//!
+//! ```ignore
+//! let client = OneClient::new(&CoreConfiguration::from_env()?)?;
+//! let result = client.perform(PerformInput {
+//!     profile_url: "file://superface/example.profile".to_string(),
+//!     provider_url: "file://superface/provider.json".to_string(),
+//!     map_url: "file://superface/example.provider.map.js".to_string(),
+//!     usecase: "Example".to_string(),
+//!     map_input: HostValue::None,
+//!     map_parameters: HostValue::None,
+//!     map_security: HostValue::None,
+//!     failover: Vec::new(),
+//!     failover_policy: FailoverPolicy::default(),
+//!     dry_run: false,
+//! });
+//! ```
+
+pub use sf_std::unstable::{
+    exception::PerformException,
+    perform::{FailoverPolicy, PerformInput, PerformOutcome, ProviderCandidate},
+    HostValue,
+};
+
+use crate::sf_core::{new_perform_id, CoreConfiguration, OneClientCore};
+
+mod host;
+pub use host::{NativeMessageExchange, NativeStreamExchange};
+
+/// OneSDK client running maps in-process.
+pub struct OneClient {
+    core: OneClientCore<NativeMessageExchange, NativeStreamExchange>,
+}
+impl OneClient {
+    pub fn new(config: &CoreConfiguration) -> anyhow::Result<Self> {
+        Ok(Self {
+            core: OneClientCore::new(config)?,
+        })
+    }
+
+    /// Performs `input.usecase` using the profile, provider and map at the given urls.
+    ///
+    /// Returns the use case result or the error defined in the profile, or an exception when the perform itself failed.
+    pub fn perform(
+        &self,
+        input: PerformInput,
+    ) -> Result<Result<HostValue, HostValue>, PerformException> {
+        host::set_perform_input(input);
+
+        self.core.perform(new_perform_id())
+    }
+
+    /// Performs all `inputs`, reusing documents and interpreters between them.
+    ///
+    /// Returns outcomes in the order of `inputs`, or an exception when the batch itself failed.
+    pub fn perform_batch(
+        &self,
+        inputs: Vec<PerformInput>,
+    ) -> Result<Vec<PerformOutcome>, PerformException> {
+        host::set_perform_batch_input(inputs);
+
+        self.core.perform_batch(new_perform_id())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const PROFILE: &str = r#"name = "test"
+version = "0.1.0"
+
+usecase Example {}
+"#;
+    /// Fails with a map error when the provider sets `FAIL` to `true`, otherwise returns `input.value`.
+    const MAP: &str = r#"
+function Example({ input, parameters }) {
+  if (parameters.FAIL === 'true') {
+    throw new std.unstable.MapError({ failed: true });
+  }
+  return input.value;
+}
+"#;
+
+    /// Writes documents of a test perform into a new temp directory.
+    struct Documents {
+        dir: PathBuf,
+    }
+    impl Documents {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "native_client_test_{}_{}",
+                name,
+                std::process::id()
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("test.profile"), PROFILE).unwrap();
+            std::fs::write(dir.join("test.provider.map.js"), MAP).unwrap();
+            for (provider, fail) in [("failing", "true"), ("working", "false")] {
+                std::fs::write(
+                    dir.join(format!("{}.provider.json", provider)),
+                    serde_json::json!({
+                        "name": provider,
+                        "services": [{ "id": "default", "baseUrl": "https://example.com" }],
+                        "defaultService": "default",
+                        "parameters": [{ "name": "FAIL", "default": fail }]
+                    })
+                    .to_string(),
+                )
+                .unwrap();
+            }
+
+            Self { dir }
+        }
+
+        fn url(&self, file_name: &str) -> String {
+            format!("file://{}", self.dir.join(file_name).display())
+        }
+
+        fn candidate(&self, provider: &str) -> ProviderCandidate {
+            ProviderCandidate {
+                provider_url: self.url(&format!("{}.provider.json", provider)),
+                map_url: self.url("test.provider.map.js"),
+            }
+        }
+
+        fn input(&self, provider: &str, value: i64) -> PerformInput {
+            let candidate = self.candidate(provider);
+
+            PerformInput {
+                profile_url: self.url("test.profile"),
+                provider_url: candidate.provider_url,
+                map_url: candidate.map_url,
+                usecase: "Example".to_string(),
+                map_input: HostValue::Object(
+                    [("value".to_string(), HostValue::Number(value.into()))].into(),
+                ),
+                map_parameters: HostValue::Object(Default::default()),
+                map_security: HostValue::Object(Default::default()),
+                failover: Vec::new(),
+                failover_policy: FailoverPolicy::default(),
+                dry_run: false,
+            }
+        }
+    }
+    impl Drop for Documents {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn client() -> OneClient {
+        OneClient::new(&CoreConfiguration::default()).unwrap()
+    }
+
+    #[test]
+    fn test_perform_batch_with_same_map() {
+        let documents = Documents::new("batch");
+
+        let outcomes = client()
+            .perform_batch(vec![
+                documents.input("working", 1),
+                documents.input("working", 2),
+            ])
+            .unwrap();
+
+        assert_eq!(outcomes.len(), 2);
+        for (outcome, expected) in outcomes.into_iter().zip([1, 2]) {
+            match outcome {
+                PerformOutcome::Result { result } => {
+                    assert_eq!(result, HostValue::Number(expected.into()))
+                }
+                outcome => panic!("Unexpected outcome: {:?}", outcome),
+            }
+        }
+    }
+
+    #[test]
+    fn test_failover_with_same_map() {
+        let documents = Documents::new("failover");
+
+        let mut input = documents.input("failing", 3);
+        input.failover = vec![documents.candidate("working")];
+        input.failover_policy.on_error = vec![HostValue::Object(
+            [("failed".to_string(), HostValue::Bool(true))].into(),
+        )];
+
+        // the working candidate reuses the interpreter of the failing one
+        assert_eq!(
+            client().perform(input).unwrap(),
+            Ok(HostValue::Number(3.into()))
+        );
+    }
+}
@@ -49,6 +49,9 @@ pub mod __private {
             provider: &'a str,
             successful_performs: usize,
             failed_performs: usize,
+            /// Url of the provider the perform failed over from, when it was served by a failover provider.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            failed_over_from: Option<&'a str>,
         },
     }
 
@@ -65,11 +68,10 @@ pub mod __private {
     }
 
     pub fn log_metric_event(event: impl Serialize) {
-        let writer = unsafe {
-            crate::observability::METRICS_BUFFER
-                .as_ref()
-                .unwrap()
-                .make_writer()
+        // metrics are only collected when observability was initialized, which the native host does not do
+        let writer = match unsafe { crate::observability::METRICS_BUFFER.as_ref() } {
+            None => return,
+            Some(buffer) => buffer.make_writer(),
         };
 
         serde_json::to_writer(writer, &event).unwrap();
@@ -107,7 +109,8 @@ macro_rules! log_metric {
         provider_url = $provider_url: expr,
         provider_content_hash = $provider_content_hash: expr,
         map_url = $map_url: expr,
-        map_content_hash = $map_content_hash: expr
+        map_content_hash = $map_content_hash: expr,
+        failed_over_from = $failed_over_from: expr
         $(,)?
     ) => {
         {
@@ -127,7 +130,8 @@ macro_rules! log_metric {
                             profile: $profile,
                             provider: $provider,
                             successful_performs,
-                            failed_performs
+                            failed_performs,
+                            failed_over_from: $failed_over_from
                         }]
                     }
                 }
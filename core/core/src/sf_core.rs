@@ -1,60 +1,101 @@
-use std::{collections::BTreeMap, str::FromStr};
+use std::{
+    collections::BTreeMap,
+    marker::PhantomData,
+    panic::AssertUnwindSafe,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+};
 
-use sf_std::unstable::{
-    exception::{PerformException, PerformExceptionErrorCode},
-    perform::PerformInput,
-    HostValue,
+use sf_std::{
+    abi::{StaticMessageExchange, StaticStreamExchange},
+    unstable::{
+        describe::DescribeInput,
+        exception::{PerformException, PerformExceptionErrorCode},
+        fs::FsConvenience,
+        perform::{PerformId, PerformInput, PerformOutcome, ProviderCandidate},
+        HostValue,
+    },
 };
 
-use interpreter_js::JsInterpreter;
-use map_std::unstable::{
-    security::{prepare_provider_parameters, prepare_security_map},
-    services::prepare_services_map,
-    MapValue, MapValueObject,
+use interpreter_js::{JsInterpreter, JsInterpreterError, JsInterpreterLimits};
+use map_std::{
+    interpreter::Interpreter,
+    unstable::{
+        security::{prepare_provider_parameters, prepare_security_map, prepare_transport},
+        services::prepare_services_map,
+        MapValue, MapValueObject,
+    },
 };
 use comlink::json_schema_validator::JsonSchemaValidator;
 
-use crate::{
-    bindings::{MessageExchangeFfi, StreamExchangeFfi},
-    sf_core::{
-        exception::FromJsonSchemaValidationError,
-        metrics::PerformMetricsData,
-    },
+use crate::sf_core::{
+    exception::{FromJsonSchemaValidationError, FromPanicPayload},
+    metrics::PerformMetricsData,
 };
 
 mod cache;
+pub mod cancellation;
 mod config;
+mod describe;
 mod digest;
 mod exception;
+mod map_language;
 mod map_std_impl;
 mod metrics;
 mod profile_validator;
+mod vcr;
 
 // use crate::profile_validator::ProfileValidator;
 use cache::DocumentCache;
-pub use config::CoreConfiguration;
+pub use config::{CoreConfiguration, CoreConfigurationOverrides};
+use map_language::MapLanguage;
 use map_std_impl::MapStdImpl;
 
 use self::{
     cache::{MapCacheEntry, ProfileCacheEntry, ProviderJsonCacheEntry},
+    describe::{Description, ProfileDescription},
     map_std_impl::MapStdImplConfig,
+    vcr::Vcr,
 };
 
-type Fs = sf_std::unstable::fs::FsConvenience<MessageExchangeFfi, StreamExchangeFfi>;
-type HttpRequest = sf_std::unstable::http::HttpRequest<MessageExchangeFfi, StreamExchangeFfi>;
-type HttpResponse = sf_std::unstable::http::HttpResponse<StreamExchangeFfi>;
-type IoStream = sf_std::unstable::IoStream<StreamExchangeFfi>;
+type HttpRequest<Me, Se> = sf_std::unstable::http::HttpRequest<Me, Se>;
+type HttpResponse<Se> = sf_std::unstable::http::HttpResponse<Se>;
+type IoStream<Se> = sf_std::unstable::IoStream<Se>;
+type Timer<Me> = sf_std::unstable::timer::TimerConvenience<Me>;
+type MapInterpreter<Me, Se> = Box<dyn Interpreter<MapStdImpl<Me, Se>>>;
+
+static NEXT_PERFORM_ID: AtomicU32 = AtomicU32::new(1);
 
+/// Allocates an id for a new perform, identifying it in messages, cancellation and logs.
+pub fn new_perform_id() -> PerformId {
+    NEXT_PERFORM_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Core state generic over how it talks to the host.
+///
+/// The wasm core uses FFI exchanges, a native host can implement the same messages in-process.
+///
+/// Performs only need a shared reference and share the document caches of the instance.
 #[derive(Debug)]
-pub struct OneClientCore {
+pub struct OneClientCore<Me, Se> {
     profile_cache: DocumentCache<ProfileCacheEntry>,
     provider_cache: DocumentCache<ProviderJsonCacheEntry>,
     map_cache: DocumentCache<MapCacheEntry>,
     security_validator: JsonSchemaValidator,
     parameters_validator: JsonSchemaValidator,
     mapstd_config: MapStdImplConfig,
+    vcr: Option<Arc<Mutex<Vcr>>>,
+    interpreter_limits: JsInterpreterLimits,
+    exchanges: PhantomData<fn() -> (Me, Se)>,
 }
-impl OneClientCore {
+impl<Me, Se> OneClientCore<Me, Se>
+where
+    Me: StaticMessageExchange + 'static,
+    Se: StaticStreamExchange + 'static,
+{
     const MAP_STDLIB_JS: &'static str = include_str!("../assets/js/map_std.js");
     const SECURITY_VALUES_JSON_SCHEMA: &'static str =
         include_str!("../assets/schemas/security_values.json");
@@ -72,24 +113,27 @@ impl OneClientCore {
                 config.cache_duration,
                 config.registry_url.clone(),
                 Some(config.user_agent.clone()),
+                config.document_max_size,
             ),
             provider_cache: DocumentCache::new(
                 config.cache_duration,
                 config.registry_url.clone(),
                 Some(config.user_agent.clone()),
+                config.document_max_size,
             ),
             map_cache: DocumentCache::new(
                 config.cache_duration,
                 config.registry_url.clone(),
                 Some(config.user_agent.clone()),
+                config.document_max_size,
             ),
             security_validator: JsonSchemaValidator::new(
-                &serde_json::Value::from_str(&OneClientCore::SECURITY_VALUES_JSON_SCHEMA)
+                &serde_json::Value::from_str(&Self::SECURITY_VALUES_JSON_SCHEMA)
                     .expect("Valid JSON"),
             )
             .expect("Valid JSON Schema for security values exists"),
             parameters_validator: JsonSchemaValidator::new(
-                &serde_json::Value::from_str(&OneClientCore::PARAMETERS_VALUES_JSON_SCHEMA)
+                &serde_json::Value::from_str(&Self::PARAMETERS_VALUES_JSON_SCHEMA)
                     .expect("Valid JSON"),
             )
             .expect("Valid JSON Schema for parameters values exists"),
@@ -97,29 +141,36 @@ impl OneClientCore {
                 log_http_transactions: config.user_log,
                 log_http_transactions_body_max_size: config.user_log_http_body_max_size,
                 user_agent: config.user_agent.clone(),
+                http_body_max_size: config.map_http_body_max_size,
+                sleep_max_duration: Some(config.map_sleep_max_duration).filter(|v| !v.is_zero()),
+            },
+            vcr: Vcr::new::<Me, Se>(config.vcr_mode, &config.vcr_cassette_path)?
+                .map(|vcr| Arc::new(Mutex::new(vcr))),
+            interpreter_limits: JsInterpreterLimits {
+                execution_budget: Some(config.map_execution_budget).filter(|&v| v > 0),
+                max_heap_size: Some(config.map_heap_max_size).filter(|&v| v > 0),
             },
+            exchanges: PhantomData,
         })
     }
 
     /// Converts HostValue into MapValue.
     ///
-    /// For primitive types this is a simple move. For custom types with drop code this might include adding
-    /// reference counting and registering handles.
-    fn host_value_to_map_value(&mut self, value: HostValue) -> MapValue {
+    /// Values are copied, so that the input can be used again when failing over to another provider.
+    /// For custom types with drop code this might include adding reference counting and registering handles.
+    fn host_value_to_map_value(&self, value: &HostValue) -> MapValue {
         match value {
             HostValue::Stream(_) => todo!(),
             HostValue::None => MapValue::None,
-            HostValue::Bool(b) => MapValue::Bool(b),
-            HostValue::Number(n) => MapValue::Number(n),
-            HostValue::String(s) => MapValue::String(s),
-            HostValue::Array(a) => MapValue::Array(
-                a.into_iter()
-                    .map(|v| self.host_value_to_map_value(v))
-                    .collect(),
-            ),
+            HostValue::Bool(b) => MapValue::Bool(*b),
+            HostValue::Number(n) => MapValue::Number(n.clone()),
+            HostValue::String(s) => MapValue::String(s.clone()),
+            HostValue::Array(a) => {
+                MapValue::Array(a.iter().map(|v| self.host_value_to_map_value(v)).collect())
+            }
             HostValue::Object(o) => MapValue::Object(BTreeMap::from_iter(
-                o.into_iter()
-                    .map(|(k, v)| (k, self.host_value_to_map_value(v))),
+                o.iter()
+                    .map(|(k, v)| (k.clone(), self.host_value_to_map_value(v))),
             )),
         }
     }
@@ -127,7 +178,7 @@ impl OneClientCore {
     /// Converts MapValue into HostValue.
     ///
     /// This is the opposite action to [host_value_to_map_value].
-    fn map_value_to_host_value(&mut self, value: MapValue) -> HostValue {
+    fn map_value_to_host_value(&self, value: MapValue) -> HostValue {
         match value {
             MapValue::None => HostValue::None,
             MapValue::Bool(b) => HostValue::Bool(b),
@@ -145,15 +196,254 @@ impl OneClientCore {
         }
     }
 
-    pub fn perform(&mut self) -> Result<Result<HostValue, HostValue>, PerformException> {
-        // we can't send metrics if we don't even know the profile and provider urls
-        let perform_input = PerformInput::take_in(MessageExchangeFfi)?;
+    /// Creates the interpreter for maps written in `language` with the stdlib loaded.
+    fn create_interpreter(
+        &self,
+        language: MapLanguage,
+        state: MapStdImpl<Me, Se>,
+        perform_id: PerformId,
+    ) -> Result<MapInterpreter<Me, Se>, PerformException> {
+        match language {
+            MapLanguage::JavaScript => {
+                let mut interpreter = JsInterpreter::new(state)?;
+                interpreter.set_interrupt_handler(move || cancellation::is_cancelled(perform_id));
+                interpreter.set_limits(self.interpreter_limits.clone());
+                // here we allow runtime stdlib replacement for development purposes
+                // this might be removed in the future
+                match std::env::var("ONESDK_REPLACE_MAP_STDLIB").ok() {
+                    None => interpreter.eval_code("map_std.js", Self::MAP_STDLIB_JS)?,
+                    Some(path) => {
+                        let replacement =
+                            FsConvenience::<Me, Se>::read_to_string(&path).map_err(|err| {
+                                PerformException {
+                                    error_code: PerformExceptionErrorCode::ReplacementStdlibError,
+                                    message: format!("Failed to load replacement map_std: {}", err),
+                                    js_exception: None,
+                                }
+                            })?;
+
+                        interpreter.eval_code(&path, &replacement)?
+                    }
+                }
+
+                Ok(Box::new(interpreter))
+            }
+            language => Err(language.unsupported_exception()),
+        }
+    }
+
+    /// Performs the use case identified by `perform_id`, taking its input from the host.
+    ///
+    /// A panic during the perform is returned as a `Panic` exception, see [Self::catch_panic].
+    pub fn perform(
+        &self,
+        perform_id: PerformId,
+    ) -> Result<Result<HostValue, HostValue>, PerformException> {
+        let outcome = self.catch_panic(|| {
+            let _in_flight = cancellation::register(perform_id);
+
+            // we can't send metrics if we don't even know the profile and provider urls
+            let perform_input = PerformInput::take_in(perform_id, Me::instance())?;
+
+            self.perform_one(perform_id, perform_input, &mut None)
+        });
+        let saved = self.save_vcr();
+
+        outcome.and_then(|outcome| saved.map(|_| outcome))
+    }
+
+    /// Performs all use cases of the batch identified by `perform_id`, taking their inputs from the host.
+    ///
+    /// Documents are cached once for the whole batch and the interpreter is reused by consecutive performs of the same map.
+    /// Cancelling the batch cancels all of its remaining performs. A perform which panics outputs a `Panic` exception
+    /// and the rest of the batch continues with a new interpreter.
+    pub fn perform_batch(
+        &self,
+        perform_id: PerformId,
+    ) -> Result<Vec<PerformOutcome>, PerformException> {
+        let outcomes = self.catch_panic(|| {
+            let _in_flight = cancellation::register(perform_id);
+
+            let perform_inputs = PerformInput::take_batch_in(perform_id, Me::instance())?;
+
+            let mut interpreter = None;
+            Ok(perform_inputs
+                .into_iter()
+                .map(|perform_input| {
+                    let outcome = self.catch_panic(|| {
+                        self.perform_one(perform_id, perform_input, &mut interpreter)
+                    });
+                    if let Err(PerformException {
+                        error_code: PerformExceptionErrorCode::Panic,
+                        ..
+                    }) = outcome
+                    {
+                        interpreter = None;
+                    }
+
+                    outcome.into()
+                })
+                .collect())
+        });
+        let saved = self.save_vcr();
+
+        outcomes.and_then(|outcomes| saved.map(|_| outcomes))
+    }
+
+    /// Saves interactions recorded by the finished perform, so the cassette is written once per perform or batch.
+    fn save_vcr(&self) -> Result<(), PerformException> {
+        match self.vcr {
+            None => Ok(()),
+            Some(ref vcr) => vcr
+                .lock()
+                .unwrap()
+                .save::<Me, Se>()
+                .map_err(|err| PerformException {
+                    error_code: PerformExceptionErrorCode::InvalidConfiguration,
+                    message: err.to_string(),
+                    js_exception: None,
+                }),
+        }
+    }
+
+    /// Runs `f`, turning a panic into a `Panic` exception.
+    ///
+    /// Only the state of the panicked perform is lost. Locks poisoned by the panic are cleared, so the document caches
+    /// are kept for subsequent performs. Panics can only be caught where they unwind, in the wasm build they still abort the instance.
+    fn catch_panic<T>(
+        &self,
+        f: impl FnOnce() -> Result<T, PerformException>,
+    ) -> Result<T, PerformException> {
+        match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(result) => result,
+            Err(payload) => {
+                // the panic itself was already logged by the panic hook
+                let exception = PerformException::from_panic_payload(payload.as_ref());
+
+                self.profile_cache.clear_poison();
+                self.provider_cache.clear_poison();
+                self.map_cache.clear_poison();
+                if let Some(ref vcr) = self.vcr {
+                    vcr.clear_poison();
+                }
+                cancellation::clear_poison();
+
+                Err(exception)
+            }
+        }
+    }
+
+    /// Describes the profile and provider requested by the host, loading them through the document caches.
+    pub fn describe(&self) -> Result<serde_json::Value, PerformException> {
+        let describe_input = DescribeInput::take_in(Me::instance())?;
+
+        let profile = match describe_input.profile_url {
+            None => None,
+            Some(ref profile_url) => {
+                self.profile_cache
+                    .cache::<Me, Se, _>(profile_url, ProfileCacheEntry::from_data)?;
+                let profile_entry = self.profile_cache.get(profile_url).unwrap();
+                let file_name = profile_url.rsplit('/').next().unwrap();
+
+                Some(ProfileDescription::parse(
+                    file_name,
+                    &profile_entry.profile,
+                )?)
+            }
+        };
+
+        let provider_entry = match describe_input.provider_url {
+            None => None,
+            Some(ref provider_url) => {
+                self.provider_cache
+                    .cache::<Me, Se, _>(provider_url, ProviderJsonCacheEntry::from_data)?;
+
+                self.provider_cache.get(provider_url)
+            }
+        };
+
+        let description = Description {
+            profile,
+            provider: provider_entry
+                .as_deref()
+                .map(|entry| (&entry.provider_json).into()),
+        };
+        Ok(serde_json::to_value(description).expect("Description is serializable"))
+    }
+
+    /// Performs one use case described by `perform_input`.
+    ///
+    /// The provider and map from the input are tried first, then the failover candidates in order
+    /// for as long as the failover policy decides the perform failed.
+    fn perform_one(
+        &self,
+        perform_id: PerformId,
+        perform_input: PerformInput,
+        reusable_interpreter: &mut Option<(String, MapInterpreter<Me, Se>)>,
+    ) -> Result<Result<HostValue, HostValue>, PerformException> {
+        let primary = ProviderCandidate {
+            provider_url: perform_input.provider_url.clone(),
+            map_url: perform_input.map_url.clone(),
+        };
+
+        let mut candidates = std::iter::once(&primary)
+            .chain(perform_input.failover.iter())
+            .peekable();
+        let mut failed_over_from = None;
+        loop {
+            let candidate = candidates.next().unwrap();
+            let outcome = self.perform_candidate(
+                perform_id,
+                &perform_input,
+                candidate,
+                failed_over_from,
+                reusable_interpreter,
+            );
+
+            match candidates.peek() {
+                Some(next) if perform_input.failover_policy.should_fail_over(&outcome) => {
+                    tracing::warn!(
+                        target: "@user",
+                        "Perform with provider {} failed, failing over to {}",
+                        candidate.provider_url,
+                        next.provider_url
+                    );
+                    failed_over_from = Some(primary.provider_url.as_str());
+                }
+                _ => return outcome,
+            }
+        }
+    }
+
+    /// Performs the use case with one provider `candidate`.
+    ///
+    /// The interpreter is taken from `reusable_interpreter` if it was used for the same map and put back
+    /// there if the map didn't fail, so that it can be reused by the next perform. Interpreters are not shared between maps
+    /// because they keep state of the loaded map (e.g. evaluated ES modules).
+    fn perform_candidate(
+        &self,
+        perform_id: PerformId,
+        perform_input: &PerformInput,
+        candidate: &ProviderCandidate,
+        failed_over_from: Option<&str>,
+        reusable_interpreter: &mut Option<(String, MapInterpreter<Me, Se>)>,
+    ) -> Result<Result<HostValue, HostValue>, PerformException> {
+        // map logs and http transactions are emitted within this span so they can be correlated
+        let _span = tracing::info_span!(
+            target: "@user",
+            "perform",
+            perform_id,
+            map = %candidate.map_url,
+            usecase = %perform_input.usecase
+        )
+        .entered();
 
         // information we have so far parsed from the available data, might be partial if an exception happens
         let mut metrics_data = PerformMetricsData {
             profile_url: &perform_input.profile_url,
-            provider_url: &perform_input.provider_url,
-            map_url: &perform_input.map_url,
+            provider_url: &candidate.provider_url,
+            map_url: &candidate.map_url,
+            failed_over_from,
             ..Default::default()
         };
 
@@ -173,7 +463,8 @@ impl OneClientCore {
                     provider_url = metrics_data.provider_url,
                     provider_content_hash = metrics_data.provider_content_hash,
                     map_url = metrics_data.map_url,
-                    map_content_hash = metrics_data.map_content_hash
+                    map_content_hash = metrics_data.map_content_hash,
+                    failed_over_from = metrics_data.failed_over_from
                 );
             };
 
@@ -192,20 +483,22 @@ impl OneClientCore {
         // first cache documents
         try_metrics!(self
             .profile_cache
-            .cache(&perform_input.profile_url, ProfileCacheEntry::from_data));
-        try_metrics!(self.provider_cache.cache(
-            &perform_input.provider_url,
-            ProviderJsonCacheEntry::from_data
-        ));
-        try_metrics!(self.map_cache.cache(&perform_input.map_url, |data| {
-            // TODO: this is temporary, should be extracted from the map manifest
-            let file_name = perform_input.map_url.split('/').last().unwrap().to_string();
+            .cache::<Me, Se, _>(&perform_input.profile_url, ProfileCacheEntry::from_data));
+        try_metrics!(self
+            .provider_cache
+            .cache::<Me, Se, _>(&candidate.provider_url, ProviderJsonCacheEntry::from_data));
+        try_metrics!(self
+            .map_cache
+            .cache::<Me, Se, _>(&candidate.map_url, |data| {
+                // TODO: this is temporary, should be extracted from the map manifest
+                let file_name = candidate.map_url.split('/').last().unwrap().to_string();
 
-            MapCacheEntry::new(data, file_name)
-        }));
+                MapCacheEntry::new(data, file_name)
+            }));
+        try_metrics!(cancellation::check(perform_id));
 
         // process map input and parameters
-        let map_input = self.host_value_to_map_value(perform_input.map_input);
+        let map_input = self.host_value_to_map_value(&perform_input.map_input);
         // TODO: Validate Input
         // let mut profile_validator = ProfileValidator::new(
         //     std::str::from_utf8(
@@ -235,9 +528,9 @@ impl OneClientCore {
             })?;
 
         let mut map_parameters = match perform_input.map_parameters {
-            HostValue::Object(o) => MapValueObject::from_iter(
-                o.into_iter()
-                    .map(|(k, v)| (k, self.host_value_to_map_value(v))),
+            HostValue::Object(ref o) => MapValueObject::from_iter(
+                o.iter()
+                    .map(|(k, v)| (k.clone(), self.host_value_to_map_value(v))),
             ),
             HostValue::None => MapValueObject::new(),
             _ => unreachable!("Object or None ensured with JSON Schema validation"),
@@ -254,13 +547,12 @@ impl OneClientCore {
             })?;
 
         // parse provider json
+        // entries are shared with other performs, a later refresh doesn't affect this one
+        let provider_entry = self.provider_cache.get(&candidate.provider_url).unwrap();
         let ProviderJsonCacheEntry {
             provider_json,
             content_hash: provider_json_content_hash,
-        } = self
-            .provider_cache
-            .get(&perform_input.provider_url)
-            .unwrap();
+        } = provider_entry.as_ref();
         // TODO: validate provider json with json schema, to verify OneClient will understand it?
 
         metrics_data.provider_content_hash = Some(provider_json_content_hash);
@@ -277,40 +569,41 @@ impl OneClientCore {
         let map_services = try_metrics!(
             prepare_services_map(provider_json, &map_parameters)
         );
+        let map_transport = try_metrics!(prepare_transport(
+            provider_json,
+            &perform_input.map_security
+        ));
 
+        let profile_entry = self.profile_cache.get(&perform_input.profile_url).unwrap();
         let ProfileCacheEntry {
             profile: _,
             content_hash: profile_content_hash,
-        } = self.profile_cache.get(&perform_input.profile_url).unwrap();
+        } = profile_entry.as_ref();
         metrics_data.profile_content_hash = Some(profile_content_hash);
 
-        // start interpreting stdlib and then map code
-        // TODO: should this be here or should we hold an instance of the interpreter in global state
-        // and clear per-perform data each time it is called?
-        let mut interpreter = try_metrics!(JsInterpreter::new(MapStdImpl::new(
-            self.mapstd_config.to_owned()
-        )));
-        // here we allow runtime stdlib replacement for development purposes
-        // this might be removed in the future
-        try_metrics!(match std::env::var("ONESDK_REPLACE_MAP_STDLIB").ok() {
-            None => interpreter.eval_code("map_std.js", Self::MAP_STDLIB_JS),
-            Some(path) => {
-                let replacement =
-                    try_metrics!(Fs::read_to_string(&path).map_err(|err| PerformException {
-                        error_code: PerformExceptionErrorCode::ReplacementStdlibError,
-                        message: format!("Failed to load replacement map_std: {}", err),
-                    }));
-
-                interpreter.eval_code(&path, &replacement)
-            }
-        });
-
+        let map_entry = self.map_cache.get(&candidate.map_url).unwrap();
         let MapCacheEntry {
             map,
             content_hash: map_content_hash,
             file_name: map_file_name,
-        } = self.map_cache.get(&perform_input.map_url).unwrap();
+            ..
+        } = map_entry.as_ref();
         metrics_data.map_content_hash = Some(map_content_hash);
+
+        // start interpreting stdlib and then map code
+        // TODO: should we hold an instance of the interpreter in global state and clear per-perform data
+        // each time it is called, not just within a batch?
+        let mut interpreter = match reusable_interpreter.take() {
+            Some((map_url, mut interpreter)) if map_url == candidate.map_url => {
+                interpreter.state_mut().reset_perform_state();
+                interpreter
+            }
+            _ => try_metrics!(self.create_interpreter(
+                MapLanguage::from_file_name(map_file_name),
+                MapStdImpl::new(self.mapstd_config.to_owned(), perform_id),
+                perform_id
+            )),
+        };
         let map_result = {
             interpreter.state_mut().set_context(
                 map_std::map_value!({
@@ -320,10 +613,52 @@ impl OneClientCore {
                 }),
                 Some(map_security),
             );
-            try_metrics!(interpreter.run(map_file_name, map, &perform_input.usecase));
+            interpreter.state_mut().set_transport(map_transport);
+            interpreter.state_mut().set_vcr(self.vcr.clone());
+            interpreter.state_mut().set_dry_run(perform_input.dry_run);
+            let mut run_result = interpreter.run(map_file_name, map, &perform_input.usecase);
+            if let Err(ref mut exception) = run_result {
+                // source map is shipped next to the map and only loaded once an exception needs it
+                let source_map = exception.js_exception.as_ref().and_then(|_| {
+                    map_entry.source_map(|| {
+                        self.map_cache.load_companion::<Me, Se>(
+                            &candidate.map_url,
+                            MapCacheEntry::SOURCE_MAP_SUFFIX,
+                        )
+                    })
+                });
+                if let Some(source_map) = source_map {
+                    let mut js_exception = exception.js_exception.take().unwrap();
+                    source_map.apply(map_file_name, &mut js_exception);
+                    *exception = JsInterpreterError::UncaughtException(js_exception).into();
+                }
+            }
+            // cancellation interrupts the map with an error, so check it first to report the actual reason
+            if cancellation::is_cancelled(perform_id) {
+                interpreter.state_mut().drop_handles();
+            }
+            try_metrics!(cancellation::check(perform_id));
+
+            if perform_input.dry_run {
+                // maps commonly fail on the synthetic responses, the requests made up to that point are still of interest
+                if let Err(err) = run_result {
+                    tracing::warn!(target: "@user", "Map failed in dry run: {}", err);
+                }
+                interpreter.take_output();
 
-            interpreter.state_mut().take_output().unwrap()
+                let requests = interpreter.state_mut().take_dry_run_requests();
+                let requests = serde_json::to_value(requests)
+                    .and_then(serde_json::from_value)
+                    .expect("Dry run requests are representable as a map value");
+                Ok(requests)
+            } else {
+                try_metrics!(run_result);
+
+                interpreter.take_output().unwrap()
+            }
         };
+        interpreter.state_mut().drop_handles();
+        reusable_interpreter.replace((candidate.map_url.clone(), interpreter));
 
         try_metrics!(Send map_result.is_ok());
 
@@ -1,17 +1,25 @@
 use std::{
     collections::HashMap,
     io::Read,
+    sync::{Arc, Mutex, OnceLock},
     time::{Duration, Instant},
 };
 
 use url::Url;
 
+use interpreter_js::SourceMap;
+
 use sf_std::{
-    unstable::{http::HttpCallError, provider::ProviderJson},
+    abi::{StaticMessageExchange, StaticStreamExchange},
+    unstable::{
+        fs::OpenOptions,
+        http::{FetchOptions, HttpCallError, HttpRedirect},
+        provider::ProviderJson,
+    },
     HeaderName, HeadersMultiMap,
 };
 
-use super::{digest, Fs, HttpRequest};
+use super::{digest, HttpRequest};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ProfileCacheEntryError {
@@ -20,7 +28,6 @@ pub enum ProfileCacheEntryError {
 }
 #[derive(Debug)]
 pub struct ProfileCacheEntry {
-    #[allow(dead_code)]
     pub profile: String, // TODO: parsed so we can extract the version
     pub content_hash: String,
 }
@@ -63,8 +70,12 @@ pub struct MapCacheEntry {
     pub content_hash: String,
     /// This is for the purposes of stacktraces in JsInterpreter
     pub file_name: String,
+    /// Source map used to map stacktraces back to the original sources, loaded on first use.
+    source_map: OnceLock<Option<SourceMap>>,
 }
 impl MapCacheEntry {
+    pub const SOURCE_MAP_SUFFIX: &'static str = ".map";
+
     // TODO: name should be taken from the manifest
     pub fn new(data: Vec<u8>, file_name: String) -> Result<Self, MapCacheEntryError> {
         let content_hash = digest::content_hash(&data);
@@ -74,8 +85,27 @@ impl MapCacheEntry {
             content_hash,
             map,
             file_name,
+            source_map: OnceLock::new(),
         })
     }
+
+    /// Returns the source map of this map, loading it with `load` the first time it is needed.
+    ///
+    /// The source map is only needed to report uncaught exceptions, so it is not loaded together with the map.
+    pub fn source_map(&self, load: impl FnOnce() -> Option<Vec<u8>>) -> Option<&SourceMap> {
+        self.source_map
+            .get_or_init(|| {
+                // source maps are only a debugging aid, so a broken one doesn't fail the perform
+                load().and_then(|data| match SourceMap::parse(&data) {
+                    Ok(source_map) => Some(source_map),
+                    Err(err) => {
+                        tracing::warn!(target: "@user", "Ignoring source map of {}: {}", self.file_name, err);
+                        None
+                    }
+                })
+            })
+            .as_ref()
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -84,15 +114,19 @@ pub enum DocumentCacheError<PostProcessError: std::error::Error> {
     FileLoadFailed(String, std::io::Error),
     #[error("Failed to load document \"{0}\" over http: {1}")]
     HttpLoadFailed(String, HttpCallError),
+    #[error("Failed to load document \"{0}\" over http: server responded with status {1}")]
+    HttpStatus(String, u16),
     #[error("Failed to read http body: {0}")]
     HttpBodyReadFailed(std::io::Error),
+    #[error("Document \"{0}\" exceeds the maximum allowed size of {1} bytes")]
+    DocumentTooLarge(String, usize),
     #[error("Failed to post process data: {0}")]
     PostProcessError(PostProcessError),
 }
 
 struct DocumentCacheEntry<E> {
     store_time: Instant,
-    data: E,
+    data: Arc<E>,
 }
 impl<E: std::fmt::Debug> std::fmt::Debug for DocumentCacheEntry<E> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -105,11 +139,16 @@ impl<E: std::fmt::Debug> std::fmt::Debug for DocumentCacheEntry<E> {
     }
 }
 
+/// Cache of documents shared by all performs of a core instance.
+///
+/// The map is only locked for lookups and inserts, never while a document is being loaded,
+/// since loading suspends the perform and other performs may use the cache in the meantime.
 pub struct DocumentCache<E> {
-    map: HashMap<String, DocumentCacheEntry<E>>,
+    map: Mutex<HashMap<String, DocumentCacheEntry<E>>>,
     cache_duration: Duration,
     registry_url: Url,
     user_agent: Option<String>,
+    max_size: usize,
 }
 impl<E> DocumentCache<E> {
     const FILE_URL_PREFIX: &'static str = "file://";
@@ -117,21 +156,48 @@ impl<E> DocumentCache<E> {
     const HTTPS_URL_PREFIX: &'static str = "https://";
     const BASE64_URL_PREFIX: &'static str = "data:;base64,";
 
-    pub fn new(cache_duration: Duration, registry_url: Url, user_agent: Option<String>) -> Self {
+    pub fn new(
+        cache_duration: Duration,
+        registry_url: Url,
+        user_agent: Option<String>,
+        max_size: usize,
+    ) -> Self {
         Self {
-            map: HashMap::new(),
+            map: Mutex::new(HashMap::new()),
             cache_duration,
             registry_url,
             user_agent,
+            max_size,
         }
     }
 
-    pub fn get(&self, url: &str) -> Option<&E> {
-        self.map.get(url).map(|e| &e.data)
+    /// Clears poisoning of the map after a perform panicked.
+    ///
+    /// Entries are only ever inserted whole, so the cached documents are still consistent.
+    pub fn clear_poison(&self) {
+        self.map.clear_poison();
+    }
+
+    pub fn get(&self, url: &str) -> Option<Arc<E>> {
+        self.map.lock().unwrap().get(url).map(|e| e.data.clone())
+    }
+
+    /// Returns whether the document at `url` is cached and not expired.
+    pub fn is_cached(&self, url: &str) -> bool {
+        match self.map.lock().unwrap().get(url) {
+            Some(DocumentCacheEntry { store_time, .. }) => {
+                store_time.elapsed() <= self.cache_duration
+            }
+            None => false,
+        }
     }
 
-    pub fn cache<PostProcessError: std::error::Error>(
-        &mut self,
+    pub fn cache<
+        Me: StaticMessageExchange,
+        Se: StaticStreamExchange,
+        PostProcessError: std::error::Error,
+    >(
+        &self,
         url: &str,
         post_process_fn: impl FnOnce(Vec<u8>) -> Result<E, PostProcessError>,
     ) -> Result<(), DocumentCacheError<PostProcessError>> {
@@ -139,25 +205,85 @@ impl<E> DocumentCache<E> {
 
         tracing::debug!(url);
 
-        match self.map.get(url) {
-            Some(DocumentCacheEntry { store_time, .. })
-                if store_time.elapsed() <= self.cache_duration =>
-            {
-                tracing::debug!("already cached");
-                return Ok(());
+        if self.is_cached(url) {
+            tracing::debug!("already cached");
+            return Ok(());
+        }
+
+        let data = self.load::<Me, Se, _>(url, "")?;
+
+        tracing::trace!(bytes = ?data);
+        if tracing::enabled!(tracing::Level::DEBUG) {
+            if let Ok(utf8) = std::str::from_utf8(&data) {
+                tracing::debug!(%utf8);
+            }
+        }
+
+        let data = post_process_fn(data).map_err(|e| DocumentCacheError::PostProcessError(e))?;
+        // another perform may have cached the same document in the meantime, the newer one wins
+        self.map.lock().unwrap().insert(
+            url.to_string(),
+            DocumentCacheEntry {
+                store_time: Instant::now(),
+                data: Arc::new(data),
+            },
+        );
+        Ok(())
+    }
+
+    /// Loads a document stored next to the document at `url` under the same name with `suffix` appended.
+    ///
+    /// Returns `None` if it cannot be loaded since such documents (e.g. source maps) are optional.
+    pub fn load_companion<Me: StaticMessageExchange, Se: StaticStreamExchange>(
+        &self,
+        url: &str,
+        suffix: &str,
+    ) -> Option<Vec<u8>> {
+        let _span = tracing::debug_span!("load_companion_document").entered();
+
+        match self.load::<Me, Se, std::convert::Infallible>(url, suffix) {
+            Ok(data) => Some(data),
+            Err(err) => {
+                tracing::debug!(url, suffix, "companion document not loaded: {}", err);
+                None
             }
-            _ => (),
         }
+    }
 
+    fn load<
+        Me: StaticMessageExchange,
+        Se: StaticStreamExchange,
+        PostProcessError: std::error::Error,
+    >(
+        &self,
+        url: &str,
+        suffix: &str,
+    ) -> Result<Vec<u8>, DocumentCacheError<PostProcessError>> {
         let data = match url {
-            url if url.starts_with(Self::FILE_URL_PREFIX) => Self::cache_file(url),
-            url if url.starts_with("data:;base64,") => Self::cache_base64(url),
+            url if url.starts_with(Self::FILE_URL_PREFIX) => {
+                Self::cache_file::<Me, Se, _>(&format!("{}{}", url, suffix), self.max_size)
+            }
+            url if url.starts_with(Self::BASE64_URL_PREFIX) => {
+                if !suffix.is_empty() {
+                    // inline documents have nothing stored next to them
+                    return Err(DocumentCacheError::FileLoadFailed(
+                        url.to_string(),
+                        std::io::ErrorKind::NotFound.into(),
+                    ));
+                }
+                Self::cache_base64(url)
+            }
             url => {
                 if url.starts_with(Self::HTTP_URL_PREFIX) || url.starts_with(Self::HTTPS_URL_PREFIX)
                 {
-                    Self::cache_http(url, self.user_agent.as_deref())
+                    Self::cache_http::<Me, Se, _>(
+                        &format!("{}{}", url, suffix),
+                        self.user_agent.as_deref(),
+                        self.max_size,
+                        !suffix.is_empty(),
+                    )
                 } else {
-                    let file = format!("{}.js", url);
+                    let file = format!("{}.js{}", url, suffix);
                     let full_url = self.registry_url.join(&file).map_err(|_e| {
                         DocumentCacheError::HttpLoadFailed(
                             url.to_string(),
@@ -165,57 +291,97 @@ impl<E> DocumentCache<E> {
                         )
                     })?;
 
-                    Self::cache_http(full_url.as_str(), self.user_agent.as_deref())
+                    Self::cache_http::<Me, Se, _>(
+                        full_url.as_str(),
+                        self.user_agent.as_deref(),
+                        self.max_size,
+                        !suffix.is_empty(),
+                    )
                 }
             }
         }?;
-
-        tracing::trace!(bytes = ?data);
-        if tracing::enabled!(tracing::Level::DEBUG) {
-            if let Ok(utf8) = std::str::from_utf8(&data) {
-                tracing::debug!(%utf8);
-            }
+        if data.len() > self.max_size {
+            return Err(DocumentCacheError::DocumentTooLarge(
+                url.to_string(),
+                self.max_size,
+            ));
         }
 
-        self.map.insert(
-            url.to_string(),
-            DocumentCacheEntry {
-                store_time: Instant::now(),
-                data: post_process_fn(data).map_err(|e| DocumentCacheError::PostProcessError(e))?,
-            },
-        );
-        Ok(())
+        Ok(data)
     }
 
-    fn cache_file<PostProcessError: std::error::Error>(
+    fn cache_file<
+        Me: StaticMessageExchange,
+        Se: StaticStreamExchange,
+        PostProcessError: std::error::Error,
+    >(
         url: &str,
+        max_size: usize,
     ) -> Result<Vec<u8>, DocumentCacheError<PostProcessError>> {
-        match url.strip_prefix(Self::FILE_URL_PREFIX) {
-            None => Err(DocumentCacheError::FileLoadFailed(
-                url.to_string(),
-                std::io::ErrorKind::NotFound.into(),
-            )),
-            Some(path) => Fs::read(path)
-                .map_err(|err| DocumentCacheError::FileLoadFailed(path.to_string(), err)),
-        }
+        let path = match url.strip_prefix(Self::FILE_URL_PREFIX) {
+            None => {
+                return Err(DocumentCacheError::FileLoadFailed(
+                    url.to_string(),
+                    std::io::ErrorKind::NotFound.into(),
+                ))
+            }
+            Some(path) => path,
+        };
+
+        // same as for http, oversized files are detected without reading them whole
+        let mut data = Vec::new();
+        OpenOptions::new()
+            .read(true)
+            .open_in(path, Me::instance(), Se::instance())
+            .and_then(|file| file.take(max_size as u64 + 1).read_to_end(&mut data))
+            .map_err(|err| DocumentCacheError::FileLoadFailed(path.to_string(), err))?;
+
+        Ok(data)
     }
 
-    fn cache_http<PostProcessError: std::error::Error>(
+    /// Loads the document at `url`.
+    ///
+    /// With `require_success` a non-2xx response is an error, so that a missing companion document isn't mistaken for one.
+    fn cache_http<
+        Me: StaticMessageExchange,
+        Se: StaticStreamExchange,
+        PostProcessError: std::error::Error,
+    >(
         url: &str,
         user_agent: Option<&str>,
+        max_size: usize,
+        require_success: bool,
     ) -> Result<Vec<u8>, DocumentCacheError<PostProcessError>> {
         let mut headers = HeadersMultiMap::new();
         if let Some(user_agent) = user_agent {
             headers.insert(HeaderName::from("user-agent"), vec![user_agent.to_string()]);
         }
 
-        let mut response = HttpRequest::fetch("GET", url, &headers, &Default::default(), None)
-            .and_then(|v| v.into_response())
-            .map_err(|err| DocumentCacheError::HttpLoadFailed(url.to_string(), err))?;
+        let mut response = HttpRequest::<Me, Se>::fetch(
+            "GET",
+            url,
+            FetchOptions {
+                headers: &headers,
+                query: &Default::default(),
+                body: None,
+                redirect: HttpRedirect::default(),
+                transport: None,
+            },
+        )
+        .and_then(|v| v.into_response())
+        .map_err(|err| DocumentCacheError::HttpLoadFailed(url.to_string(), err))?;
+        if require_success && !(200..300).contains(&response.status()) {
+            return Err(DocumentCacheError::HttpStatus(
+                url.to_string(),
+                response.status(),
+            ));
+        }
 
+        // read at most one byte over the limit so that oversized documents are detected without reading them whole
         let mut data = Vec::new();
         response
             .body()
+            .take(max_size as u64 + 1)
             .read_to_end(&mut data)
             .map_err(|err| DocumentCacheError::HttpBodyReadFailed(err))?;
 
@@ -244,3 +410,55 @@ impl<E: std::fmt::Debug> std::fmt::Debug for DocumentCache<E> {
             .finish()
     }
 }
+
+#[cfg(all(test, feature = "native_host"))]
+mod test {
+    use crate::native::{NativeMessageExchange, NativeStreamExchange};
+
+    use super::*;
+
+    #[test]
+    fn test_oversized_file_is_rejected() {
+        let path = std::env::temp_dir().join(format!("cache_test_{}.json", std::process::id()));
+        std::fs::write(&path, vec![b'a'; 64]).unwrap();
+        let url = format!("file://{}", path.display());
+
+        let cache = DocumentCache::<Vec<u8>>::new(
+            Duration::from_secs(60),
+            Url::parse("https://registry.example.com/").unwrap(),
+            None,
+            16,
+        );
+        let result = cache.cache::<NativeMessageExchange, NativeStreamExchange, _>(&url, |data| {
+            Ok::<_, std::convert::Infallible>(data)
+        });
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            result,
+            Err(DocumentCacheError::DocumentTooLarge(_, 16))
+        ));
+        assert!(cache.get(&url).is_none());
+    }
+
+    #[test]
+    fn test_map_source_map_is_loaded_once() {
+        let entry =
+            MapCacheEntry::new(b"function Test() {}".to_vec(), "test.js".to_string()).unwrap();
+
+        let mut loads = 0;
+        assert!(entry
+            .source_map(|| {
+                loads += 1;
+                Some(b"not a source map".to_vec())
+            })
+            .is_none());
+        assert!(entry
+            .source_map(|| {
+                loads += 1;
+                None
+            })
+            .is_none());
+        assert_eq!(loads, 1);
+    }
+}
@@ -0,0 +1,98 @@
+//! Cancellation of in-flight performs.
+//!
+//! Requests are static because the host sets them through an export while the perform is suspended (asyncify),
+//! without access to the core state.
+
+use std::{collections::BTreeMap, sync::Mutex};
+
+use sf_std::unstable::{
+    exception::{PerformException, PerformExceptionErrorCode},
+    perform::PerformId,
+};
+
+/// Performs in flight and whether their cancellation was requested.
+static IN_FLIGHT: Mutex<BTreeMap<PerformId, bool>> = Mutex::new(BTreeMap::new());
+
+/// Registers the perform as in flight until dropped.
+pub struct InFlightGuard(PerformId);
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        IN_FLIGHT.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Marks `perform_id` as in flight, cancellation can only be requested for performs in flight.
+pub fn register(perform_id: PerformId) -> InFlightGuard {
+    IN_FLIGHT.lock().unwrap().insert(perform_id, false);
+
+    InFlightGuard(perform_id)
+}
+
+/// Clears poisoning of the in-flight registry after a perform panicked.
+///
+/// Guards of the panicked perform are dropped while unwinding, so the registry doesn't keep its id.
+pub fn clear_poison() {
+    IN_FLIGHT.clear_poison();
+}
+
+/// Requests cancellation of the perform, does nothing if it is not in flight.
+pub fn request(perform_id: PerformId) {
+    if let Some(cancelled) = IN_FLIGHT.lock().unwrap().get_mut(&perform_id) {
+        *cancelled = true;
+    }
+}
+
+/// Requests cancellation of all performs in flight.
+pub fn request_all() {
+    for cancelled in IN_FLIGHT.lock().unwrap().values_mut() {
+        *cancelled = true;
+    }
+}
+
+pub fn is_cancelled(perform_id: PerformId) -> bool {
+    IN_FLIGHT
+        .lock()
+        .unwrap()
+        .get(&perform_id)
+        .copied()
+        .unwrap_or(false)
+}
+
+/// Returns the `Cancelled` exception if cancellation was requested.
+pub fn check(perform_id: PerformId) -> Result<(), PerformException> {
+    if is_cancelled(perform_id) {
+        tracing::debug!(target: "@user", "Perform cancelled");
+
+        return Err(PerformException {
+            error_code: PerformExceptionErrorCode::Cancelled,
+            message: "Perform was cancelled by the host".to_string(),
+            js_exception: None,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_is_per_perform() {
+        let first = register(1001);
+        let _second = register(1002);
+
+        request(1001);
+        assert!(is_cancelled(1001));
+        assert!(!is_cancelled(1002));
+        assert!(check(1001).is_err());
+
+        // ids are forgotten once the perform finishes, so a reused id doesn't start cancelled
+        std::mem::drop(first);
+        request(1001);
+        assert!(!is_cancelled(1001));
+
+        request_all();
+        assert!(is_cancelled(1002));
+    }
+}
@@ -1,13 +1,55 @@
 use std::{fmt::Debug, time::Duration};
 
+use serde::Deserialize;
 use url::Url;
 
+use sf_std::{
+    abi::MessageExchange,
+    unstable::config::{take_core_config_in, TakeCoreConfigError},
+};
+
+use super::vcr::VcrMode;
+
 #[derive(Debug, thiserror::Error)]
 pub enum CoreConfigurationEnvError {
     #[error("Variable {0} could not be parsed as {1}")]
     InvalidVariableFormat(String, String),
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum CoreConfigurationError {
+    #[error(transparent)]
+    Env(#[from] CoreConfigurationEnvError),
+    #[error(transparent)]
+    TakeFailed(TakeCoreConfigError),
+    #[error("Invalid core configuration from host: {0}")]
+    InvalidFormat(serde_json::Error),
+    #[error("Field {0} could not be parsed as {1}")]
+    InvalidFieldFormat(String, String),
+}
+
+/// Configuration sent by the host in the `core-config` message.
+///
+/// Fields have the same meaning as in [CoreConfiguration], durations are in seconds.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CoreConfigurationOverrides {
+    pub cache_duration: Option<u64>,
+    pub developer_dump_buffer_size: Option<usize>,
+    pub registry_url: Option<String>,
+    pub user_agent: Option<String>,
+    pub user_log: Option<bool>,
+    pub user_log_http_body_max_size: Option<usize>,
+    pub developer_log: Option<String>,
+    pub document_max_size: Option<usize>,
+    pub map_http_body_max_size: Option<usize>,
+    pub vcr_mode: Option<String>,
+    pub vcr_cassette_path: Option<String>,
+    pub map_execution_budget: Option<u64>,
+    pub map_heap_max_size: Option<usize>,
+    pub map_sleep_max_duration: Option<u64>,
+}
+
 pub struct CoreConfiguration {
     /// Duration to cache documents for.
     pub cache_duration: Duration,
@@ -19,6 +61,22 @@ pub struct CoreConfiguration {
     pub user_log: bool,
     pub user_log_http_body_max_size: usize,
     pub developer_log: String,
+    /// Maximum size of a document (profile, provider or map) in bytes.
+    pub document_max_size: usize,
+    /// Maximum number of bytes a map can read from a single http response body.
+    pub map_http_body_max_size: usize,
+    /// Whether to record or replay map http transactions.
+    pub vcr_mode: VcrMode,
+    /// Path to the VCR cassette file.
+    pub vcr_cassette_path: String,
+    /// Number of interpreter interrupt checks a single map evaluation may take, 0 means unlimited.
+    pub map_execution_budget: u64,
+    /// Maximum size of the map interpreter heap in bytes, 0 means unlimited.
+    pub map_heap_max_size: usize,
+    /// Longest sleep a map may request, 0 means unlimited.
+    ///
+    /// Hosts don't interrupt a sleep when the perform is cancelled.
+    pub map_sleep_max_duration: Duration,
 }
 impl CoreConfiguration {
     pub fn from_env() -> Result<Self, CoreConfigurationEnvError> {
@@ -42,6 +100,7 @@ impl CoreConfiguration {
             (__internal parse Url) => { |v| Url::parse(&v) };
             (__internal parse u64) => { |v| v.parse::<u64>() };
             (__internal parse usize) => { |v| v.parse::<usize>() };
+            (__internal parse VcrMode) => { |v| v.parse::<VcrMode>() };
             (__internal parse bool) => { |v| match v.as_str() {
                 "on" | "yes" | "true" | "1" => Ok::<bool, CoreConfigurationEnvError>(true),
                 _ => Ok(false)
@@ -66,9 +125,112 @@ impl CoreConfiguration {
         if let Some(v) = get_env!("ONESDK_DEFAULT_USERAGENT", String "string")? {
             base.user_agent = v;
         }
+        if let Some(v) = get_env!("ONESDK_CONFIG_DOCUMENT_MAX_SIZE", usize "size in bytes")? {
+            base.document_max_size = v;
+        }
+        if let Some(v) = get_env!("ONESDK_CONFIG_MAP_HTTP_BODY_MAX_SIZE", usize "size in bytes")? {
+            base.map_http_body_max_size = v;
+        }
+        if let Some(v) = get_env!("ONESDK_CONFIG_VCR_MODE", VcrMode "off, record or replay")? {
+            base.vcr_mode = v;
+        }
+        if let Some(v) = get_env!("ONESDK_CONFIG_VCR_CASSETTE", String "path")? {
+            base.vcr_cassette_path = v;
+        }
+        if let Some(v) = get_env!("ONESDK_CONFIG_MAP_EXECUTION_BUDGET", u64 "interrupt checks")? {
+            base.map_execution_budget = v;
+        }
+        if let Some(v) = get_env!("ONESDK_CONFIG_MAP_HEAP_MAX_SIZE", usize "size in bytes")? {
+            base.map_heap_max_size = v;
+        }
+        if let Some(v) = get_env!("ONESDK_CONFIG_MAP_SLEEP_MAX_DURATION", u64 "milliseconds")? {
+            base.map_sleep_max_duration = Duration::from_millis(v);
+        }
+
+        Ok(base)
+    }
+
+    /// Loads configuration from environment and merges configuration from the `core-config` message over it.
+    ///
+    /// Hosts which don't send configuration are configured through environment only. Invalid values from either source are an error.
+    pub fn from_env_and_host<E: MessageExchange>(
+        message_exchange: E,
+    ) -> Result<Self, CoreConfigurationError> {
+        let mut base = Self::from_env()?;
+
+        match take_core_config_in(message_exchange) {
+            Err(TakeCoreConfigError::NotProvided(err)) => {
+                tracing::debug!("Host does not provide core configuration: {}", err);
+            }
+            Err(err) => return Err(CoreConfigurationError::TakeFailed(err)),
+            Ok(serde_json::Value::Null) => (),
+            Ok(config) => {
+                let overrides = serde_json::from_value::<CoreConfigurationOverrides>(config)
+                    .map_err(CoreConfigurationError::InvalidFormat)?;
+                base.merge(overrides)?;
+            }
+        }
 
         Ok(base)
     }
+
+    /// Replaces values with those set in `overrides`.
+    pub fn merge(
+        &mut self,
+        overrides: CoreConfigurationOverrides,
+    ) -> Result<(), CoreConfigurationError> {
+        if let Some(v) = overrides.cache_duration {
+            self.cache_duration = Duration::from_secs(v);
+        }
+        if let Some(v) = overrides.developer_dump_buffer_size {
+            self.developer_dump_buffer_size = v;
+        }
+        if let Some(v) = overrides.registry_url {
+            self.registry_url = Url::parse(&v).map_err(|_| {
+                CoreConfigurationError::InvalidFieldFormat("registry_url".into(), "url".into())
+            })?;
+        }
+        if let Some(v) = overrides.user_agent {
+            self.user_agent = v;
+        }
+        if let Some(v) = overrides.user_log {
+            self.user_log = v;
+        }
+        if let Some(v) = overrides.user_log_http_body_max_size {
+            self.user_log_http_body_max_size = v;
+        }
+        if let Some(v) = overrides.developer_log {
+            self.developer_log = v;
+        }
+        if let Some(v) = overrides.document_max_size {
+            self.document_max_size = v;
+        }
+        if let Some(v) = overrides.map_http_body_max_size {
+            self.map_http_body_max_size = v;
+        }
+        if let Some(v) = overrides.vcr_mode {
+            self.vcr_mode = v.parse().map_err(|_| {
+                CoreConfigurationError::InvalidFieldFormat(
+                    "vcr_mode".into(),
+                    "off, record or replay".into(),
+                )
+            })?;
+        }
+        if let Some(v) = overrides.vcr_cassette_path {
+            self.vcr_cassette_path = v;
+        }
+        if let Some(v) = overrides.map_execution_budget {
+            self.map_execution_budget = v;
+        }
+        if let Some(v) = overrides.map_heap_max_size {
+            self.map_heap_max_size = v;
+        }
+        if let Some(v) = overrides.map_sleep_max_duration {
+            self.map_sleep_max_duration = Duration::from_millis(v);
+        }
+
+        Ok(())
+    }
 }
 impl Default for CoreConfiguration {
     fn default() -> Self {
@@ -80,6 +242,13 @@ impl Default for CoreConfiguration {
             user_log: false,
             user_log_http_body_max_size: 1024 * 1024, // 1 MiB
             developer_log: "off".to_string(),
+            document_max_size: 10 * 1024 * 1024,       // 10 MiB
+            map_http_body_max_size: 100 * 1024 * 1024, // 100 MiB
+            vcr_mode: VcrMode::Off,
+            vcr_cassette_path: "onesdk_cassette.json".to_string(),
+            map_execution_budget: 100_000,
+            map_heap_max_size: 256 * 1024 * 1024, // 256 MiB
+            map_sleep_max_duration: Duration::from_secs(5 * 60),
         }
     }
 }
@@ -99,6 +268,60 @@ impl Debug for CoreConfiguration {
                 &self.user_log_http_body_max_size,
             )
             .field("developer_log", &self.developer_log)
+            .field("document_max_size", &self.document_max_size)
+            .field("map_http_body_max_size", &self.map_http_body_max_size)
+            .field("vcr_mode", &self.vcr_mode)
+            .field("vcr_cassette_path", &self.vcr_cassette_path)
+            .field("map_execution_budget", &self.map_execution_budget)
+            .field("map_heap_max_size", &self.map_heap_max_size)
+            .field("map_sleep_max_duration", &self.map_sleep_max_duration)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use sf_std::abi::testing::TestMessageExchangeFn;
+
+    use super::*;
+
+    #[test]
+    fn test_config_from_host_is_merged() {
+        let exchange = TestMessageExchangeFn::new(|_| {
+            json!({
+                "kind": "ok",
+                "config": { "cache_duration": 10, "user_log": true, "vcr_mode": "replay" }
+            })
+        });
+
+        let config = CoreConfiguration::from_env_and_host(&exchange).unwrap();
+        assert_eq!(config.cache_duration, Duration::from_secs(10));
+        assert!(config.user_log);
+        assert_eq!(config.vcr_mode, VcrMode::Replay);
+        assert_eq!(
+            config.document_max_size,
+            CoreConfiguration::default().document_max_size
+        );
+    }
+
+    #[test]
+    fn test_invalid_config_from_host() {
+        let exchange = TestMessageExchangeFn::new(
+            |_| json!({ "kind": "ok", "config": { "user_log": "yes" } }),
+        );
+        assert!(matches!(
+            CoreConfiguration::from_env_and_host(&exchange),
+            Err(CoreConfigurationError::InvalidFormat(_))
+        ));
+
+        let exchange = TestMessageExchangeFn::new(
+            |_| json!({ "kind": "ok", "config": { "vcr_mode": "rewind" } }),
+        );
+        assert!(matches!(
+            CoreConfiguration::from_env_and_host(&exchange),
+            Err(CoreConfigurationError::InvalidFieldFormat(_, _))
+        ));
+    }
+}
@@ -0,0 +1,147 @@
+//! Descriptions of profiles and providers, so that hosts don't have to parse the documents themselves.
+
+use serde::Serialize;
+
+use comlink::{
+    comlink_parser::{self, ProfileDocumentNode},
+    typescript_parser::{self, DiagnosticSeverity, Profile},
+};
+use sf_std::unstable::{
+    exception::{PerformException, PerformExceptionErrorCode},
+    provider::{IntegrationParameter, ProviderJson, ProviderService, SecurityScheme},
+};
+
+#[derive(Serialize)]
+pub struct Description<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<ProfileDescription>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<ProviderDescription<'a>>,
+}
+
+/// Profile parsed by the parser of its format.
+#[derive(Serialize)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum ProfileDescription {
+    /// Use cases with input, result and error described by Comlink types in the profile AST.
+    Comlink { ast: ProfileDocumentNode },
+    /// Use cases with input, result and error described by JSON schemas.
+    Typescript(Profile),
+}
+impl ProfileDescription {
+    const TYPESCRIPT_SUFFIX: &'static str = ".profile.ts";
+
+    pub fn parse(file_name: &str, source: &str) -> Result<Self, PerformException> {
+        if file_name.ends_with(Self::TYPESCRIPT_SUFFIX) {
+            let (mut profile, _spans, diagnostics) = typescript_parser::parse_profile(source);
+            if let Some(diagnostic) = diagnostics
+                .iter()
+                .find(|d| matches!(d.severity, DiagnosticSeverity::Error))
+            {
+                return Err(Self::parse_exception(file_name, &diagnostic.message));
+            }
+            if let Some(profile_id) = typescript_parser::parse_profile_id_from_path(file_name) {
+                profile.id = profile_id;
+            }
+
+            Ok(Self::Typescript(profile))
+        } else {
+            let (ast, errors) = comlink_parser::parse_profile(source);
+            if let Some(error) = errors.first() {
+                return Err(Self::parse_exception(
+                    file_name,
+                    &format!("{} at offset {}", error.message, error.offset),
+                ));
+            }
+
+            Ok(Self::Comlink { ast })
+        }
+    }
+
+    fn parse_exception(file_name: &str, message: &str) -> PerformException {
+        PerformException {
+            error_code: PerformExceptionErrorCode::ProfileParseError,
+            message: format!("Failed to parse profile {}: {}", file_name, message),
+            js_exception: None,
+        }
+    }
+}
+
+/// What a provider needs to be configured, transport settings are left out as they may contain secrets.
+#[derive(Serialize)]
+pub struct ProviderDescription<'a> {
+    pub name: &'a str,
+    pub services: &'a [ProviderService],
+    pub default_service: Option<&'a str>,
+    pub security_schemes: &'a [SecurityScheme],
+    /// Integration parameters with their defaults.
+    pub parameters: &'a [IntegrationParameter],
+}
+impl<'a> From<&'a ProviderJson> for ProviderDescription<'a> {
+    fn from(value: &'a ProviderJson) -> Self {
+        Self {
+            name: &value.name,
+            services: &value.services,
+            default_service: value.default_service.as_deref(),
+            security_schemes: value.security_schemes.as_deref().unwrap_or_default(),
+            parameters: value.parameters.as_deref().unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_describe_comlink_profile() {
+        let profile = ProfileDescription::parse(
+            "scope.example.profile",
+            "name = \"scope/example\"\nversion = \"1.0.0\"\n\nusecase Example {\n  input {\n    id! string!\n  }\n}\n",
+        )
+        .unwrap();
+
+        let actual = serde_json::to_value(profile).unwrap();
+        assert_eq!(actual["format"], "comlink");
+        assert_eq!(actual["ast"]["kind"], "ProfileDocument");
+    }
+
+    #[test]
+    fn test_describe_invalid_profile() {
+        let err = ProfileDescription::parse(
+            "scope.example.profile",
+            "name = \"scope/example\"\nversion = \"1.0.0\"\nfoo bar\n",
+        )
+        .err()
+        .unwrap();
+        assert!(matches!(
+            err.error_code,
+            PerformExceptionErrorCode::ProfileParseError
+        ));
+    }
+
+    #[test]
+    fn test_describe_provider() {
+        let provider_json: ProviderJson = serde_json::from_value(json!({
+            "name": "example",
+            "services": [{ "id": "default", "baseUrl": "https://example.com/{REGION}" }],
+            "parameters": [{ "name": "REGION", "default": "eu" }],
+            "transport": { "proxyUrl": "http://proxy.local" }
+        }))
+        .unwrap();
+
+        let actual = serde_json::to_value(ProviderDescription::from(&provider_json)).unwrap();
+        assert_eq!(
+            actual,
+            json!({
+                "name": "example",
+                "services": [{ "id": "default", "baseUrl": "https://example.com/{REGION}" }],
+                "default_service": null,
+                "security_schemes": [],
+                "parameters": [{ "name": "REGION", "description": null, "default": "eu" }]
+            })
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::any::Any;
+
 use sf_std::unstable::exception::{PerformException, PerformExceptionErrorCode};
 
 use comlink::json_schema_validator::JsonSchemaValidatorError;
@@ -10,6 +12,7 @@ impl<PostProcessError: std::error::Error> From<DocumentCacheError<PostProcessErr
         PerformException {
             error_code: PerformExceptionErrorCode::DocumentCacheError,
             message: value.to_string(),
+            js_exception: None,
         }
     }
 }
@@ -34,6 +37,46 @@ impl FromJsonSchemaValidationError for PerformException {
         PerformException {
             error_code: PerformExceptionErrorCode::InputValidationError,
             message,
+            js_exception: None,
         }
     }
 }
+
+pub trait FromPanicPayload {
+    fn from_panic_payload(payload: &(dyn Any + Send)) -> Self;
+}
+
+impl FromPanicPayload for PerformException {
+    fn from_panic_payload(payload: &(dyn Any + Send)) -> Self {
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            (*message).to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown panic payload".to_string()
+        };
+
+        PerformException {
+            error_code: PerformExceptionErrorCode::Panic,
+            message: format!("Core panicked: {}", message),
+            js_exception: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_exception_from_panic_payload() {
+        let payload = std::panic::catch_unwind(|| panic!("Invalid state {}", 1)).unwrap_err();
+        let exception = PerformException::from_panic_payload(payload.as_ref());
+
+        assert!(matches!(
+            exception.error_code,
+            PerformExceptionErrorCode::Panic
+        ));
+        assert_eq!(exception.message, "Core panicked: Invalid state 1");
+    }
+}
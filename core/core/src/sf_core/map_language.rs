@@ -0,0 +1,55 @@
+//! Languages maps can be written in, each is run by its own [Interpreter](map_std::interpreter::Interpreter).
+
+use sf_std::unstable::exception::{PerformException, PerformExceptionErrorCode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapLanguage {
+    JavaScript,
+    /// Maps compiled to WebAssembly components, no interpreter is available yet.
+    Wasm,
+}
+impl MapLanguage {
+    /// Detects the language from the map file extension.
+    ///
+    /// Registry and inline maps have no extension and anything not recognized is treated as JavaScript.
+    // TODO: prefer the language declared in the map manifest once maps are shipped with one
+    pub fn from_file_name(file_name: &str) -> Self {
+        let extension = file_name
+            .rsplit_once('.')
+            .map(|(_, extension)| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("wasm") => Self::Wasm,
+            _ => Self::JavaScript,
+        }
+    }
+
+    pub fn unsupported_exception(&self) -> PerformException {
+        PerformException {
+            error_code: PerformExceptionErrorCode::UnsupportedMapLanguage,
+            message: format!("No interpreter is available for {:?} maps", self),
+            js_exception: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_map_language_from_file_name() {
+        assert_eq!(
+            MapLanguage::from_file_name("profile.provider.map.js"),
+            MapLanguage::JavaScript
+        );
+        assert_eq!(
+            MapLanguage::from_file_name("profile.provider"),
+            MapLanguage::JavaScript
+        );
+        assert_eq!(
+            MapLanguage::from_file_name("profile.provider.map.WASM"),
+            MapLanguage::Wasm
+        );
+    }
+}
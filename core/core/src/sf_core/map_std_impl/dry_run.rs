@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+
+use base64::Engine;
+use serde::Serialize;
+use url::Url;
+
+use map_std::unstable::HttpRequest as MapHttpRequest;
+
+/// Request as it would have been sent to the host, after security and user-agent were resolved.
+#[derive(Debug, Serialize)]
+pub struct DryRunRequest {
+    pub method: String,
+    /// Url including the query.
+    pub url: String,
+    /// Headers with lowercased names.
+    pub headers: BTreeMap<String, Vec<String>>,
+    /// Body if it is valid UTF-8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Base64 encoded body if it is not valid UTF-8.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_base64: Option<String>,
+}
+impl From<MapHttpRequest> for DryRunRequest {
+    fn from(params: MapHttpRequest) -> Self {
+        let mut url = params.url;
+        if let Ok(mut full_url) = Url::parse(&url) {
+            if !params.query.is_empty() {
+                // sort for stable output, the host would send them in map order
+                let query: BTreeMap<_, _> = params.query.iter().collect();
+                full_url.query_pairs_mut().extend_pairs(
+                    query
+                        .into_iter()
+                        .flat_map(|(key, values)| values.iter().map(move |value| (key, value))),
+                );
+            }
+            url = full_url.into();
+        }
+
+        let headers = params
+            .headers
+            .into_iter()
+            .map(|(name, values)| (name.lowercase_chars().collect(), values))
+            .collect();
+
+        let (body, body_base64) = match params.body.map(String::from_utf8) {
+            None => (None, None),
+            Some(Ok(body)) => (Some(body), None),
+            Some(Err(err)) => (
+                None,
+                Some(base64::engine::general_purpose::STANDARD.encode(err.into_bytes())),
+            ),
+        };
+
+        Self {
+            method: params.method.to_uppercase(),
+            url,
+            headers,
+            body,
+            body_base64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use sf_std::{unstable::http::HttpRedirect, HeaderName};
+
+    use super::*;
+
+    #[test]
+    fn test_dry_run_request_from_map_request() {
+        let request = DryRunRequest::from(MapHttpRequest {
+            method: "post".to_string(),
+            url: "https://example.com/users".to_string(),
+            headers: HashMap::from([(
+                HeaderName::from("Authorization"),
+                vec!["Bearer secret".to_string()],
+            )]),
+            query: HashMap::from([
+                ("page".to_string(), vec!["2".to_string()]),
+                ("fields".to_string(), vec!["a".to_string(), "b".to_string()]),
+            ]),
+            body: Some(b"{}".to_vec()),
+            redirect: HttpRedirect::default(),
+        });
+
+        assert_eq!(
+            serde_json::to_value(request).unwrap(),
+            serde_json::json!({
+                "method": "POST",
+                "url": "https://example.com/users?fields=a&fields=b&page=2",
+                "headers": { "authorization": ["Bearer secret"] },
+                "body": "{}"
+            })
+        );
+    }
+}
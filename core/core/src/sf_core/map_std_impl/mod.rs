@@ -1,25 +1,41 @@
-use std::io::{Read, Write};
+use std::{
+    io::{Read, Write},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use map_std::{
     handle_map::HandleMap,
     unstable::{
+        crypto::{self, CryptoError},
         security::{resolve_security, SecurityMap},
         HttpCallError as MapHttpCallError, HttpCallHeadError as MapHttpCallHeadError,
-        HttpRequest as MapHttpRequest, HttpRequestSecurity as MapHttpRequestSecurity, HttpResponse as MapHttpResponse, MapStdUnstable, MapValue,
-        SetOutputError, TakeContextError,
+        HttpRequest as MapHttpRequest, HttpRequestSecurity as MapHttpRequestSecurity, HttpResponse as MapHttpResponse, LogLevel, MapStdUnstable, MapValue, MapValueObject,
+        SetOutputError, SleepError, TakeContextError,
     },
     MapStdFull,
 };
 use sf_std::{
-    abi::Handle,
+    abi::{Handle, StaticMessageExchange, StaticStreamExchange},
     fmt::{HttpRequestFmt, HttpResponseFmt},
+    unstable::{http::HttpTransport, perform::PerformId},
     HeaderName,
 };
 
-use self::stream::PeekableStream;
+use self::{
+    dry_run::DryRunRequest,
+    redirect::PendingHttpRequest,
+    stream::{LimitedStream, PeekableStream, StreamEntry},
+};
 
-use super::HttpRequest;
+use super::{
+    cancellation,
+    vcr::{Vcr, VcrMode, VcrRequest, VcrResponse},
+    Timer,
+};
 
+mod dry_run;
+mod redirect;
 mod stream;
 
 #[derive(Debug, Clone)]
@@ -30,25 +46,51 @@ pub struct MapStdImplConfig {
     pub log_http_transactions_body_max_size: usize,
     /// Default OneSDK user-agent
     pub user_agent: String,
+    /// Maximum number of bytes the map can read from an http response body.
+    pub http_body_max_size: usize,
+    /// Longest sleep the map can request, unlimited if `None`.
+    pub sleep_max_duration: Option<Duration>,
 }
 
-pub struct MapStdImpl {
-    http_requests: HandleMap<HttpRequest>,
-    streams: HandleMap<stream::StreamEntry>,
+enum HttpRequestEntry<Me: StaticMessageExchange, Se: StaticStreamExchange> {
+    /// Request sent to the host, recorded when `vcr_request` is set.
+    Live {
+        request: PendingHttpRequest<Me, Se>,
+        vcr_request: Option<VcrRequest>,
+    },
+    /// Response served from a VCR cassette.
+    Replayed(VcrResponse),
+    /// Request recorded in dry run, answered with an empty `200` response.
+    DryRun,
+}
+
+pub struct MapStdImpl<Me: StaticMessageExchange, Se: StaticStreamExchange> {
+    http_requests: HandleMap<HttpRequestEntry<Me, Se>>,
+    streams: HandleMap<StreamEntry<Se>>,
     security: Option<SecurityMap>,
+    transport: Option<HttpTransport>,
+    vcr: Option<Arc<Mutex<Vcr>>>,
+    /// Requests the map would have sent, http calls are only recorded when this is set.
+    dry_run_requests: Option<Vec<DryRunRequest>>,
     map_context: Option<MapValue>,
     map_output: Option<Result<MapValue, MapValue>>,
     config: MapStdImplConfig,
+    /// Perform this map std serves, used to check for its cancellation.
+    perform_id: PerformId,
 }
-impl MapStdImpl {
-    pub fn new(config: MapStdImplConfig) -> Self {
+impl<Me: StaticMessageExchange, Se: StaticStreamExchange> MapStdImpl<Me, Se> {
+    pub fn new(config: MapStdImplConfig, perform_id: PerformId) -> Self {
         Self {
             http_requests: HandleMap::new(),
             streams: HandleMap::new(),
             security: None,
+            transport: None,
+            vcr: None,
+            dry_run_requests: None,
             map_context: None,
             map_output: None,
             config,
+            perform_id,
         }
     }
 
@@ -60,16 +102,75 @@ impl MapStdImpl {
         self.security = security;
     }
 
-    pub fn take_output(&mut self) -> Option<Result<MapValue, MapValue>> {
-        self.map_output.take()
+    /// Sets transport settings used for all http calls made by the map.
+    pub fn set_transport(&mut self, transport: Option<HttpTransport>) {
+        self.transport = transport;
+    }
+
+    /// Sets the VCR used to record or replay http calls.
+    pub fn set_vcr(&mut self, vcr: Option<Arc<Mutex<Vcr>>>) {
+        self.vcr = vcr;
+    }
+
+    /// Sets whether http calls are only recorded instead of being sent to the host.
+    pub fn set_dry_run(&mut self, dry_run: bool) {
+        self.dry_run_requests = if dry_run { Some(Vec::new()) } else { None };
+    }
+
+    /// Takes requests recorded in dry run since it was set.
+    pub fn take_dry_run_requests(&mut self) -> Vec<DryRunRequest> {
+        self.dry_run_requests.take().unwrap_or_default()
+    }
+
+    /// Clears everything set for the previous perform, so the interpreter can be reused by another perform.
+    ///
+    /// Also drops outstanding handles, see [Self::drop_handles].
+    pub fn reset_perform_state(&mut self) {
+        self.map_context = None;
+        self.security = None;
+        self.transport = None;
+        self.vcr = None;
+        self.dry_run_requests = None;
+        self.map_output = None;
+        self.drop_handles();
+    }
+
+    /// Drops all outstanding http requests and streams.
+    ///
+    /// Dropping streams closes them on the host.
+    pub fn drop_handles(&mut self) {
+        self.http_requests = HandleMap::new();
+        self.streams = HandleMap::new();
     }
 }
-impl MapStdUnstable for MapStdImpl {
+impl<Me: StaticMessageExchange, Se: StaticStreamExchange> MapStdUnstable for MapStdImpl<Me, Se> {
     fn print(&mut self, message: &str) {
         tracing::info!(target: "@user", map = %message);
     }
 
+    fn log(&mut self, level: LogLevel, message: &str, fields: &MapValueObject) {
+        // tracing field names are static, so map fields are recorded as one JSON field
+        let fields = serde_json::to_string(fields).unwrap_or_default();
+
+        // target must be static as well, so each level has its own call
+        match level {
+            LogLevel::Trace => tracing::trace!(target: "@user::map", %fields, "{}", message),
+            LogLevel::Debug => tracing::debug!(target: "@user::map", %fields, "{}", message),
+            LogLevel::Info => tracing::info!(target: "@user::map", %fields, "{}", message),
+            LogLevel::Warn => tracing::warn!(target: "@user::map", %fields, "{}", message),
+            LogLevel::Error => tracing::error!(target: "@user::map", %fields, "{}", message),
+        }
+    }
+
     fn stream_read(&mut self, handle: Handle, buf: &mut [u8]) -> std::io::Result<usize> {
+        if cancellation::is_cancelled(self.perform_id) {
+            self.drop_handles();
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                MapHttpCallError::Cancelled,
+            ));
+        }
+
         match self.streams.get_mut(handle) {
             None => Err(std::io::ErrorKind::NotFound.into()),
             Some(stream) => stream.read(buf),
@@ -90,7 +191,72 @@ impl MapStdUnstable for MapStdImpl {
         }
     }
 
+    fn crypto_digest(&mut self, algorithm: &str, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let algorithm = crypto::DigestAlgorithm::parse(algorithm)?;
+
+        Ok(crypto::digest(algorithm, data))
+    }
+
+    fn crypto_hmac(
+        &mut self,
+        algorithm: &str,
+        key: &[u8],
+        data: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        let algorithm = crypto::DigestAlgorithm::parse(algorithm)?;
+
+        Ok(crypto::hmac(algorithm, key, data))
+    }
+
+    fn crypto_random_fill(&mut self, buf: &mut [u8]) -> Result<(), CryptoError> {
+        crypto::random_fill(buf)
+    }
+
+    fn sleep(&mut self, duration: Duration) -> Result<(), SleepError> {
+        if let Some(max_duration) = self.config.sleep_max_duration {
+            if duration > max_duration {
+                return Err(SleepError::Failed(format!(
+                    "{}ms exceeds the maximum sleep duration of {}ms",
+                    duration.as_millis(),
+                    max_duration.as_millis()
+                )));
+            }
+        }
+
+        // replayed and dry run transactions don't need to wait for the provider
+        if self.dry_run_requests.is_some() {
+            return Ok(());
+        }
+        if let Some(ref vcr) = self.vcr {
+            if vcr.lock().unwrap().mode() == VcrMode::Replay {
+                return Ok(());
+            }
+        }
+
+        cancellation::check(self.perform_id).map_err(|_| SleepError::Cancelled)?;
+        Timer::<Me>::sleep(duration).map_err(|err| SleepError::Failed(err.to_string()))?;
+        // the host may cancel the perform while it is suspended in sleep
+        cancellation::check(self.perform_id).map_err(|_| SleepError::Cancelled)
+    }
+
     fn http_call(&mut self, mut params: MapHttpRequest, security: Option<MapHttpRequestSecurity>) -> Result<Handle, MapHttpCallError> {
+        if cancellation::is_cancelled(self.perform_id) {
+            self.drop_handles();
+            return Err(MapHttpCallError::Cancelled);
+        }
+
+        let original_headers = params.headers.clone();
+        let original_body = params.body.clone();
+        // interactions are matched on the request before security is resolved so that cassettes contain no secrets
+        let vcr_request = self.vcr.as_ref().map(|_| {
+            VcrRequest::new(
+                &params.method,
+                &params.url,
+                &params.query,
+                params.body.as_deref(),
+            )
+        });
+
         let security_map = self.security.as_ref().unwrap();
         if let Some(ref security) = security {
             resolve_security(security_map, &mut params, security)?;
@@ -106,72 +272,174 @@ impl MapStdUnstable for MapStdImpl {
             )]);
 
         // We want to log the transaction below together with the handle, but we want to log it even if it fails
-        // in which case it doesn't get a handle, so we play around with a result here
-        let handle_result = HttpRequest::fetch(
-            &params.method,
-            &params.url,
-            &params.headers,
-            &params.query,
-            params.body.as_deref(),
-        )
-        .map(|request| self.http_requests.insert(request))
-        .map_err(MapHttpCallError::from);
-
+        // in which case it doesn't get a handle, so we format it before the request is moved and play around with a result here
         // IDEA: mark this branch as unlikely?
-        if self.config.log_http_transactions {
-            let _span =
-                tracing::debug_span!(target: "@user", "HTTP Request", id = handle_result.as_ref().copied().unwrap_or(0)).entered();
-            tracing::debug!(
-                target: "@user",
-                "\n{:?}", HttpRequestFmt {
+        let request_log = if self.config.log_http_transactions {
+            Some(format!(
+                "\n{:?}",
+                HttpRequestFmt {
                     method: &params.method,
                     url: &params.url,
                     headers: &params.headers,
                     body: params.body.as_deref().unwrap_or(&[])
                 }
-            );
+            ))
+        } else {
+            None
+        };
+
+        let handle_result = match (&self.vcr, vcr_request) {
+            _ if self.dry_run_requests.is_some() => {
+                self.dry_run_requests.as_mut().unwrap().push(params.into());
+                Ok(self.http_requests.insert(HttpRequestEntry::DryRun))
+            }
+            (Some(vcr), Some(vcr_request)) if vcr.lock().unwrap().mode() == VcrMode::Replay => vcr
+                .lock()
+                .unwrap()
+                .replay(&vcr_request)
+                .map(|response| {
+                    self.http_requests
+                        .insert(HttpRequestEntry::Replayed(response))
+                })
+                .map_err(|err| MapHttpCallError::Failed(err.to_string())),
+            (_, vcr_request) => PendingHttpRequest::fetch(
+                params,
+                &original_headers,
+                original_body.as_deref(),
+                self.transport.clone(),
+            )
+            .map(|request| {
+                self.http_requests.insert(HttpRequestEntry::Live {
+                    request,
+                    vcr_request,
+                })
+            })
+            .map_err(MapHttpCallError::from),
+        };
+
+        if let Some(request_log) = request_log {
+            let _span =
+                tracing::debug_span!(target: "@user", "HTTP Request", id = handle_result.as_ref().copied().unwrap_or(0)).entered();
+            tracing::debug!(target: "@user", "{}", request_log);
         }
 
         handle_result
     }
 
     fn http_call_head(&mut self, handle: Handle) -> Result<MapHttpResponse, MapHttpCallHeadError> {
-        match self.http_requests.try_remove(handle) {
-            None => Err(MapHttpCallHeadError::InvalidHandle),
-            Some(request) => {
-                let response = request.into_response()?;
-                let status = response.status();
-                let headers = response.headers().clone();
-                let body = response.into_body();
-
-                // IDEA: mark this branch as unlikely?
-                let body_stream = if self.config.log_http_transactions {
-                    let _span = tracing::debug_span!(target: "@user", "HTTP Response", id = handle)
-                        .entered();
+        if cancellation::is_cancelled(self.perform_id) {
+            self.drop_handles();
+            return Err(MapHttpCallHeadError::Cancelled);
+        }
 
-                    let mut stream = PeekableStream::from(body);
+        let (request, vcr_request) = match self.http_requests.try_remove(handle) {
+            None => return Err(MapHttpCallHeadError::InvalidHandle),
+            Some(HttpRequestEntry::Replayed(response)) => {
+                let body = response
+                    .decode_body()
+                    .map_err(|err| MapHttpCallHeadError::Failed(err.to_string()))?;
 
+                if self.config.log_http_transactions {
+                    let _span = tracing::debug_span!(target: "@user", "HTTP Response", id = handle)
+                        .entered();
                     tracing::debug!(
                         target: "@user",
                         "\n{:?}", HttpResponseFmt {
-                            status,
-                            headers: &headers,
-                            body: stream.peek(self.config.log_http_transactions_body_max_size).unwrap_or(b"<error>")
+                            status: response.status,
+                            headers: &response.headers,
+                            body: &body[..body.len().min(self.config.log_http_transactions_body_max_size)]
                         }
                     );
+                }
+
+                return Ok(MapHttpResponse {
+                    status: response.status,
+                    headers: response.headers,
+                    body_stream: self.streams.insert(body.into()),
+                });
+            }
+            Some(HttpRequestEntry::DryRun) => {
+                return Ok(MapHttpResponse {
+                    status: 200,
+                    headers: Default::default(),
+                    body_stream: self.streams.insert(Vec::new().into()),
+                });
+            }
+            Some(HttpRequestEntry::Live {
+                request,
+                vcr_request,
+            }) => (request, vcr_request),
+        };
 
-                    stream.into()
-                } else {
-                    body.into()
-                };
+        let log_http_transactions = self.config.log_http_transactions;
+        let perform_id = self.perform_id;
+        let response = request.into_response(|method, url| {
+            if cancellation::is_cancelled(perform_id) {
+                return Err(MapHttpCallHeadError::Cancelled);
+            }
 
-                Ok(MapHttpResponse {
-                    status,
-                    headers,
-                    body_stream: self.streams.insert(body_stream),
-                })
+            if log_http_transactions {
+                let _span =
+                    tracing::debug_span!(target: "@user", "HTTP Response", id = handle).entered();
+                tracing::debug!(target: "@user", "Following redirect: {} {}", method, url);
             }
+
+            Ok(())
+        });
+        if let Err(MapHttpCallHeadError::Cancelled) = response {
+            self.drop_handles();
         }
+        let response = response?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.into_body();
+
+        // IDEA: mark this branch as unlikely?
+        let body_stream: StreamEntry<Se> = if self.config.log_http_transactions {
+            let _span =
+                tracing::debug_span!(target: "@user", "HTTP Response", id = handle).entered();
+
+            let mut stream = PeekableStream::from(body);
+
+            tracing::debug!(
+                target: "@user",
+                "\n{:?}", HttpResponseFmt {
+                    status,
+                    headers: &headers,
+                    body: stream.peek(self.config.log_http_transactions_body_max_size).unwrap_or(b"<error>")
+                }
+            );
+
+            stream.into()
+        } else {
+            body.into()
+        };
+        let mut body_stream =
+            LimitedStream::new(Box::new(body_stream), self.config.http_body_max_size);
+
+        let body_stream: StreamEntry<Se> = match (&self.vcr, vcr_request) {
+            (Some(vcr), Some(vcr_request)) => {
+                // the body has to be read whole to be recorded, the map then reads it from memory
+                let mut body = Vec::new();
+                body_stream
+                    .read_to_end(&mut body)
+                    .map_err(|err| MapHttpCallHeadError::Failed(err.to_string()))?;
+
+                vcr.lock().unwrap().record(
+                    vcr_request,
+                    VcrResponse::new(status, headers.clone(), &body),
+                );
+
+                body.into()
+            }
+            _ => body_stream.into(),
+        };
+
+        Ok(MapHttpResponse {
+            status,
+            headers,
+            body_stream: self.streams.insert(body_stream),
+        })
     }
 
     fn take_context(&mut self) -> Result<MapValue, TakeContextError> {
@@ -198,4 +466,61 @@ impl MapStdUnstable for MapStdImpl {
         Ok(())
     }
 }
-impl MapStdFull for MapStdImpl {}
+impl<Me: StaticMessageExchange, Se: StaticStreamExchange> MapStdFull for MapStdImpl<Me, Se> {
+    fn take_output(&mut self) -> Option<Result<MapValue, MapValue>> {
+        self.map_output.take()
+    }
+}
+
+#[cfg(all(test, feature = "native_host"))]
+mod test {
+    use crate::native::{NativeMessageExchange, NativeStreamExchange};
+
+    use super::*;
+
+    fn state(
+        sleep_max_duration: Option<Duration>,
+    ) -> MapStdImpl<NativeMessageExchange, NativeStreamExchange> {
+        MapStdImpl::new(
+            MapStdImplConfig {
+                log_http_transactions: false,
+                log_http_transactions_body_max_size: 0,
+                user_agent: "test".to_string(),
+                http_body_max_size: 1024,
+                sleep_max_duration,
+            },
+            1,
+        )
+    }
+
+    #[test]
+    fn test_reset_perform_state_allows_new_context() {
+        let mut state = state(None);
+
+        state.set_context(MapValue::Bool(true), Some(SecurityMap::new()));
+        state.set_transport(Some(HttpTransport::default()));
+        state.set_dry_run(true);
+        state.set_output_success(MapValue::Bool(true)).unwrap();
+
+        state.reset_perform_state();
+        assert!(state.transport.is_none());
+        assert!(state.dry_run_requests.is_none());
+        assert!(state.take_output().is_none());
+
+        state.set_context(MapValue::Bool(false), None);
+        assert!(matches!(state.take_context(), Ok(MapValue::Bool(false))));
+    }
+
+    #[test]
+    fn test_sleep_over_max_duration_fails() {
+        let mut state = state(Some(Duration::from_secs(1)));
+        // dry run doesn't wait, but still enforces the limit
+        state.set_dry_run(true);
+
+        assert!(state.sleep(Duration::from_millis(500)).is_ok());
+        assert!(matches!(
+            state.sleep(Duration::from_secs(2)),
+            Err(SleepError::Failed(_))
+        ));
+    }
+}
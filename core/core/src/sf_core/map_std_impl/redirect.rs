@@ -0,0 +1,499 @@
+use url::Url;
+
+use map_std::unstable::{HttpCallHeadError as MapHttpCallHeadError, HttpRequest as MapHttpRequest};
+use sf_std::{
+    abi::{StaticMessageExchange, StaticStreamExchange},
+    unstable::http::{FetchOptions, HttpCallError, HttpRedirect, HttpRedirectMode, HttpTransport},
+    HeaderName, HeadersMultiMap,
+};
+
+use crate::sf_core::{HttpRequest, HttpResponse};
+
+/// Headers which are never sent to a different origin, regardless of whether they were set by security.
+const CROSS_ORIGIN_STRIPPED_HEADERS: [&str; 3] = ["authorization", "cookie", "proxy-authorization"];
+
+/// Http request in flight together with everything needed to follow its redirects.
+///
+/// Redirects are followed by the core rather than the host so that the behavior is the same across hosts
+/// and so that headers injected by security can be stripped when the redirect leaves the original origin.
+pub struct PendingHttpRequest<Me: StaticMessageExchange, Se: StaticStreamExchange> {
+    request: HttpRequest<Me, Se>,
+    method: String,
+    url: String,
+    headers: HeadersMultiMap,
+    body: Option<Vec<u8>>,
+    redirect: HttpRedirect,
+    transport: Option<HttpTransport>,
+    /// Headers set by security resolution.
+    security_headers: Vec<HeaderName>,
+    /// Whether security resolution modified the body.
+    security_in_body: bool,
+    hops: u32,
+}
+impl<Me: StaticMessageExchange, Se: StaticStreamExchange> PendingHttpRequest<Me, Se> {
+    /// Sends the request to the host.
+    ///
+    /// `original_headers` and `original_body` are the request parts before security was resolved,
+    /// they are used to determine what must not leak to other origins.
+    pub fn fetch(
+        params: MapHttpRequest,
+        original_headers: &HeadersMultiMap,
+        original_body: Option<&[u8]>,
+        transport: Option<HttpTransport>,
+    ) -> Result<Self, HttpCallError> {
+        let request = HttpRequest::fetch(
+            &params.method,
+            &params.url,
+            FetchOptions {
+                headers: &params.headers,
+                query: &params.query,
+                body: params.body.as_deref(),
+                // redirects are handled in `into_response`
+                redirect: HttpRedirect::manual(),
+                transport: transport.as_ref(),
+            },
+        )?;
+
+        let security_headers = params
+            .headers
+            .iter()
+            .filter(|(name, values)| original_headers.get(name) != Some(values))
+            .map(|(name, _)| name.clone())
+            .collect();
+        let security_in_body = params.body.as_deref() != original_body;
+
+        // query parameters are only relevant for the first hop, `Location` replaces them
+        let mut url = params.url;
+        if let Ok(mut full_url) = Url::parse(&url) {
+            full_url.query_pairs_mut().extend_pairs(
+                params
+                    .query
+                    .iter()
+                    .flat_map(|(key, values)| values.iter().map(move |value| (key, value))),
+            );
+            url = full_url.into();
+        }
+
+        Ok(Self {
+            request,
+            method: params.method,
+            url,
+            headers: params.headers,
+            body: params.body,
+            redirect: params.redirect,
+            transport,
+            security_headers,
+            security_in_body,
+            hops: 0,
+        })
+    }
+
+    /// Waits for the response head, following redirects according to the redirect policy.
+    ///
+    /// `before_hop` is called before each redirect is followed and can abort it.
+    pub fn into_response(
+        mut self,
+        mut before_hop: impl FnMut(&str, &str) -> Result<(), MapHttpCallHeadError>,
+    ) -> Result<HttpResponse<Se>, MapHttpCallHeadError> {
+        loop {
+            let response = self.request.into_response()?;
+
+            let location = match Self::redirect_location(&response) {
+                Some(location) if self.redirect.mode != HttpRedirectMode::Manual => location,
+                _ => return Ok(response),
+            };
+            let next_url = Url::parse(&self.url)
+                .and_then(|url| url.join(&location))
+                .map_err(|err| {
+                    MapHttpCallHeadError::Failed(format!(
+                        "Invalid redirect location \"{}\": {}",
+                        location, err
+                    ))
+                })?;
+
+            if self.redirect.mode == HttpRedirectMode::Error {
+                return Err(MapHttpCallHeadError::RedirectNotAllowed(next_url.into()));
+            }
+            if self.hops >= self.redirect.max_hops {
+                return Err(MapHttpCallHeadError::TooManyRedirects(
+                    self.redirect.max_hops,
+                ));
+            }
+
+            // see <https://fetch.spec.whatwg.org/#http-redirect-fetch>
+            let status = response.status();
+            if (status == 303 && !self.method.eq_ignore_ascii_case("HEAD"))
+                || ((status == 301 || status == 302) && self.method.eq_ignore_ascii_case("POST"))
+            {
+                self.method = "GET".to_string();
+                self.body = None;
+                self.security_in_body = false;
+            }
+
+            let same_origin = Url::parse(&self.url)
+                .map(|url| url.origin() == next_url.origin())
+                .unwrap_or(false);
+            if !same_origin {
+                if self.body.is_some() && self.security_in_body {
+                    return Err(MapHttpCallHeadError::RedirectNotAllowed(format!(
+                        "{} (request body contains secrets and the redirect leaves the original origin)",
+                        next_url
+                    )));
+                }
+
+                // the client certificate identifies us to the provider, don't present it to anyone else
+                if let Some(ref mut transport) = self.transport {
+                    transport.client_certificate = None;
+                }
+
+                let security_headers = std::mem::take(&mut self.security_headers);
+                self.headers.retain(|name, _| {
+                    !security_headers.contains(name)
+                        && !CROSS_ORIGIN_STRIPPED_HEADERS
+                            .iter()
+                            .any(|stripped| name == &HeaderName::from(*stripped))
+                });
+            }
+
+            // dropping the response closes its body stream
+            std::mem::drop(response);
+
+            before_hop(&self.method, next_url.as_str())?;
+
+            self.hops += 1;
+            self.url = next_url.into();
+            self.request = HttpRequest::fetch(
+                &self.method,
+                &self.url,
+                FetchOptions {
+                    headers: &self.headers,
+                    query: &Default::default(),
+                    body: self.body.as_deref(),
+                    redirect: HttpRedirect::manual(),
+                    transport: self.transport.as_ref(),
+                },
+            )
+            .map_err(|err| match err {
+                // the url was already parsed above, so the host rejected it
+                HttpCallError::InvalidUrl(_) => MapHttpCallHeadError::Failed(err.to_string()),
+                err => err.into(),
+            })?;
+        }
+    }
+
+    fn redirect_location(response: &HttpResponse<Se>) -> Option<String> {
+        if !matches!(response.status(), 301 | 302 | 303 | 307 | 308) {
+            return None;
+        }
+
+        // headers are lowercased by `HttpRequest::into_response`
+        response
+            .headers()
+            .get(&HeaderName::from("location"))
+            .and_then(|values| values.first())
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, collections::VecDeque, io};
+
+    use serde_json::{json, Value};
+
+    use sf_std::{
+        abi::{Handle, MessageExchange, Size, StreamExchange},
+        unstable::http::HttpClientCertificate,
+    };
+
+    use super::*;
+
+    /// Reply of the test host to one `http-call`.
+    #[derive(Clone)]
+    enum Reply {
+        Status(u16, Option<&'static str>),
+        ConnectionRefused,
+    }
+
+    thread_local! {
+        static REPLIES: RefCell<VecDeque<Reply>> = const { RefCell::new(VecDeque::new()) };
+        static CALLS: RefCell<Vec<Value>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Answers `http-call` messages with the scripted replies and records them.
+    struct TestExchange;
+    impl MessageExchange for TestExchange {
+        fn invoke(&self, message: &[u8]) -> Vec<u8> {
+            let message: Value = serde_json::from_slice(message).unwrap();
+
+            let response = match message["kind"].as_str().unwrap() {
+                "http-call" => {
+                    CALLS.with(|calls| calls.borrow_mut().push(message));
+                    match REPLIES.with(|replies| replies.borrow()[0].clone()) {
+                        Reply::ConnectionRefused => json!({
+                            "kind": "err",
+                            "error_code": "network:ECONNREFUSED",
+                            "message": "refused"
+                        }),
+                        Reply::Status(..) => json!({ "kind": "ok", "handle": 1 }),
+                    }
+                }
+                "http-call-head" => {
+                    match REPLIES.with(|replies| replies.borrow_mut().pop_front()) {
+                        Some(Reply::Status(status, location)) => json!({
+                            "kind": "ok",
+                            "status": status,
+                            "headers": { "Location": location.as_slice() },
+                            "body_stream": 1
+                        }),
+                        _ => panic!("No response to read"),
+                    }
+                }
+                kind => panic!("Unexpected message {}", kind),
+            };
+
+            serde_json::to_vec(&response).unwrap()
+        }
+    }
+    impl StaticMessageExchange for TestExchange {
+        fn instance() -> Self {
+            Self
+        }
+    }
+    impl StreamExchange for TestExchange {
+        fn read(&self, _handle: Handle, _buf: &mut [u8]) -> io::Result<Size> {
+            Ok(0)
+        }
+
+        fn write(&self, _handle: Handle, _buf: &[u8]) -> io::Result<Size> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+
+        fn close(&self, _handle: Handle) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    impl StaticStreamExchange for TestExchange {
+        fn instance() -> Self {
+            Self
+        }
+    }
+
+    fn request(method: &str, headers: &[(&str, &str)], body: Option<&str>) -> MapHttpRequest {
+        MapHttpRequest {
+            method: method.to_string(),
+            url: "https://example.com/start".to_string(),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (HeaderName::from(*name), vec![value.to_string()]))
+                .collect(),
+            query: Default::default(),
+            body: body.map(|body| body.as_bytes().to_vec()),
+            redirect: HttpRedirect::default(),
+        }
+    }
+
+    /// Sends `params` with `replies` scripted and returns the result together with the `http-call` messages.
+    fn run(
+        params: MapHttpRequest,
+        original_headers: &HeadersMultiMap,
+        transport: Option<HttpTransport>,
+        replies: Vec<Reply>,
+    ) -> (Result<u16, MapHttpCallHeadError>, Vec<Value>) {
+        REPLIES.with(|r| *r.borrow_mut() = replies.into());
+        CALLS.with(|calls| calls.borrow_mut().clear());
+
+        let original_body = params.body.clone();
+        let result = PendingHttpRequest::<TestExchange, TestExchange>::fetch(
+            params,
+            original_headers,
+            original_body.as_deref(),
+            transport,
+        )
+        .unwrap()
+        .into_response(|_, _| Ok(()))
+        .map(|response| response.status());
+
+        (result, CALLS.with(|calls| calls.take()))
+    }
+
+    /// Url of an `http-call` message, without the empty query `HttpRequest::fetch` leaves in it.
+    fn url(call: &Value) -> &str {
+        call["url"].as_str().unwrap().trim_end_matches('?')
+    }
+
+    #[test]
+    fn test_cross_origin_redirect_strips_headers() {
+        let original_headers =
+            HeadersMultiMap::from([(HeaderName::from("x-custom"), vec!["keep".to_string()])]);
+        let params = request(
+            "GET",
+            &[
+                ("x-custom", "keep"),
+                ("x-api-key", "secret"),
+                ("authorization", "Bearer token"),
+                ("cookie", "session=1"),
+                ("proxy-authorization", "Basic abc"),
+            ],
+            None,
+        );
+
+        let (result, calls) = run(
+            params,
+            &original_headers,
+            None,
+            vec![
+                Reply::Status(302, Some("https://other.example.com/next")),
+                Reply::Status(200, None),
+            ],
+        );
+
+        assert_eq!(result.unwrap(), 200);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0]["headers"]["x-api-key"], json!(["secret"]));
+        assert_eq!(url(&calls[1]), "https://other.example.com/next");
+        assert_eq!(calls[1]["headers"], json!({ "x-custom": ["keep"] }));
+    }
+
+    #[test]
+    fn test_same_origin_redirect_keeps_headers() {
+        let params = request("GET", &[("authorization", "Bearer token")], None);
+
+        let (result, calls) = run(
+            params,
+            &HeadersMultiMap::new(),
+            None,
+            vec![Reply::Status(307, Some("/next")), Reply::Status(200, None)],
+        );
+
+        assert_eq!(result.unwrap(), 200);
+        assert_eq!(url(&calls[1]), "https://example.com/next");
+        assert_eq!(
+            calls[1]["headers"]["authorization"],
+            json!(["Bearer token"])
+        );
+    }
+
+    #[test]
+    fn test_cross_origin_redirect_drops_client_certificate() {
+        let transport = HttpTransport {
+            proxy_url: Some("http://proxy.example.com".to_string()),
+            ca_pem: None,
+            client_certificate: Some(HttpClientCertificate {
+                certificate: "cert".to_string(),
+                key: "key".to_string(),
+            }),
+        };
+
+        let (result, calls) = run(
+            request("GET", &[], None),
+            &HeadersMultiMap::new(),
+            Some(transport),
+            vec![
+                Reply::Status(301, Some("https://other.example.com/")),
+                Reply::Status(200, None),
+            ],
+        );
+
+        assert_eq!(result.unwrap(), 200);
+        assert_eq!(
+            calls[0]["transport"]["client_certificate"]["certificate"],
+            "cert"
+        );
+        assert_eq!(calls[1]["transport"]["client_certificate"], Value::Null);
+        assert_eq!(
+            calls[1]["transport"]["proxy_url"],
+            "http://proxy.example.com"
+        );
+    }
+
+    #[test]
+    fn test_redirect_max_hops() {
+        let mut params = request("GET", &[], None);
+        params.redirect.max_hops = 2;
+
+        let (result, calls) = run(
+            params,
+            &HeadersMultiMap::new(),
+            None,
+            vec![
+                Reply::Status(302, Some("/1")),
+                Reply::Status(302, Some("/2")),
+                Reply::Status(302, Some("/3")),
+            ],
+        );
+
+        assert!(matches!(
+            result,
+            Err(MapHttpCallHeadError::TooManyRedirects(2))
+        ));
+        assert_eq!(calls.len(), 3);
+    }
+
+    #[test]
+    fn test_303_changes_method_to_get() {
+        for method in ["PUT", "DELETE", "post"] {
+            let (result, calls) = run(
+                request(method, &[], Some("data")),
+                &HeadersMultiMap::new(),
+                None,
+                vec![Reply::Status(303, Some("/next")), Reply::Status(200, None)],
+            );
+
+            assert_eq!(result.unwrap(), 200);
+            assert_eq!(calls[1]["method"], "GET");
+            assert_eq!(calls[1]["body"], Value::Null);
+        }
+
+        let (_, calls) = run(
+            request("head", &[], None),
+            &HeadersMultiMap::new(),
+            None,
+            vec![Reply::Status(303, Some("/next")), Reply::Status(200, None)],
+        );
+        assert_eq!(calls[1]["method"], "head");
+    }
+
+    #[test]
+    fn test_301_302_change_post_to_get() {
+        for (status, method) in [(301, "POST"), (302, "POST"), (302, "post")] {
+            let (result, calls) = run(
+                request(method, &[], Some("data")),
+                &HeadersMultiMap::new(),
+                None,
+                vec![
+                    Reply::Status(status, Some("/next")),
+                    Reply::Status(200, None),
+                ],
+            );
+
+            assert_eq!(result.unwrap(), 200);
+            assert_eq!(calls[1]["method"], "GET");
+            assert_eq!(calls[1]["body"], Value::Null);
+        }
+
+        let (_, calls) = run(
+            request("PUT", &[], Some("data")),
+            &HeadersMultiMap::new(),
+            None,
+            vec![Reply::Status(302, Some("/next")), Reply::Status(200, None)],
+        );
+        assert_eq!(calls[1]["method"], "PUT");
+        assert_ne!(calls[1]["body"], Value::Null);
+    }
+
+    #[test]
+    fn test_redirect_hop_keeps_error_category() {
+        let (result, _) = run(
+            request("GET", &[], None),
+            &HeadersMultiMap::new(),
+            None,
+            vec![Reply::Status(302, Some("/next")), Reply::ConnectionRefused],
+        );
+
+        assert!(matches!(
+            result,
+            Err(MapHttpCallHeadError::ConnectionRefused(_))
+        ));
+    }
+}
@@ -1,40 +1,60 @@
-use std::io::{Read, Write};
+use std::io::{Cursor, Read, Write};
+
+use sf_std::abi::StreamExchange;
 
 use crate::sf_core::{HttpResponse, IoStream};
 
-pub enum StreamEntry {
-    Io(IoStream),
+pub enum StreamEntry<Se: StreamExchange> {
+    Io(IoStream<Se>),
     /// Buffered streams are currently implemented for body logging
-    Peekable(PeekableStream),
+    Peekable(PeekableStream<Se>),
+    /// Streams with a cap on the number of bytes that can be read from them
+    Limited(LimitedStream<Box<StreamEntry<Se>>>),
+    /// In-memory streams, used for recorded and replayed bodies
+    Buffer(Cursor<Vec<u8>>),
 }
-impl From<IoStream> for StreamEntry {
-    fn from(value: IoStream) -> Self {
+impl<Se: StreamExchange> From<IoStream<Se>> for StreamEntry<Se> {
+    fn from(value: IoStream<Se>) -> Self {
         Self::Io(value)
     }
 }
-impl From<PeekableStream> for StreamEntry {
-    fn from(value: PeekableStream) -> Self {
+impl<Se: StreamExchange> From<PeekableStream<Se>> for StreamEntry<Se> {
+    fn from(value: PeekableStream<Se>) -> Self {
         Self::Peekable(value)
     }
 }
-impl From<HttpResponse> for StreamEntry {
-    fn from(value: HttpResponse) -> Self {
+impl<Se: StreamExchange> From<LimitedStream<Box<StreamEntry<Se>>>> for StreamEntry<Se> {
+    fn from(value: LimitedStream<Box<StreamEntry<Se>>>) -> Self {
+        Self::Limited(value)
+    }
+}
+impl<Se: StreamExchange> From<Vec<u8>> for StreamEntry<Se> {
+    fn from(value: Vec<u8>) -> Self {
+        Self::Buffer(Cursor::new(value))
+    }
+}
+impl<Se: StreamExchange> From<HttpResponse<Se>> for StreamEntry<Se> {
+    fn from(value: HttpResponse<Se>) -> Self {
         Self::Io(value.into_body())
     }
 }
-impl Read for StreamEntry {
+impl<Se: StreamExchange> Read for StreamEntry<Se> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
             Self::Io(i) => i.read(buf),
             Self::Peekable(i) => i.read(buf),
+            Self::Limited(i) => i.read(buf),
+            Self::Buffer(i) => i.read(buf),
         }
     }
 }
-impl Write for StreamEntry {
+impl<Se: StreamExchange> Write for StreamEntry<Se> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         match self {
             Self::Io(i) => i.write(buf),
             Self::Peekable(i) => i.write(buf),
+            Self::Limited(i) => i.write(buf),
+            Self::Buffer(i) => i.write(buf),
         }
     }
 
@@ -42,15 +62,17 @@ impl Write for StreamEntry {
         match self {
             Self::Io(i) => i.flush(),
             Self::Peekable(i) => i.flush(),
+            Self::Limited(i) => i.flush(),
+            Self::Buffer(i) => i.flush(),
         }
     }
 }
 
-pub struct PeekableStream {
+pub struct PeekableStream<Se: StreamExchange> {
     buffer: Vec<u8>,
-    inner: IoStream,
+    inner: IoStream<Se>,
 }
-impl PeekableStream {
+impl<Se: StreamExchange> PeekableStream<Se> {
     pub fn peek(&mut self, count: usize) -> std::io::Result<&[u8]> {
         let count = if self.buffer.len() < count {
             // calculate how many bytes are needed to fill buffer up to `count`
@@ -81,15 +103,15 @@ impl PeekableStream {
         Ok(&self.buffer[..count])
     }
 }
-impl From<IoStream> for PeekableStream {
-    fn from(value: IoStream) -> Self {
+impl<Se: StreamExchange> From<IoStream<Se>> for PeekableStream<Se> {
+    fn from(value: IoStream<Se>) -> Self {
         PeekableStream {
             buffer: Vec::new(),
             inner: value,
         }
     }
 }
-impl Read for PeekableStream {
+impl<Se: StreamExchange> Read for PeekableStream<Se> {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         if self.buffer.len() > 0 {
             // calculate how much to read from the buffer
@@ -105,7 +127,7 @@ impl Read for PeekableStream {
         self.inner.read(buf)
     }
 }
-impl Write for PeekableStream {
+impl<Se: StreamExchange> Write for PeekableStream<Se> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         // TODO: we probably don't care about this
         self.inner.write(buf)
@@ -115,3 +137,84 @@ impl Write for PeekableStream {
         self.inner.flush()
     }
 }
+
+/// Stream which fails reading once more than `limit` bytes would be read from the inner stream.
+///
+/// Unlike [Read::take] this does not silently truncate, so the map cannot mistake a cut-off body for a complete one.
+pub struct LimitedStream<S> {
+    inner: S,
+    limit: usize,
+    remaining: usize,
+}
+impl<S> LimitedStream<S> {
+    pub fn new(inner: S, limit: usize) -> Self {
+        Self {
+            inner,
+            limit,
+            remaining: limit,
+        }
+    }
+}
+impl<S: Read> Read for LimitedStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.remaining == 0 {
+            // limit reached, succeed only if the inner stream is exhausted as well
+            let mut probe = [0u8; 1];
+            return match self.inner.read(&mut probe)? {
+                0 => Ok(0),
+                _ => Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!(
+                        "Response body exceeds the maximum allowed size of {} bytes",
+                        self.limit
+                    ),
+                )),
+            };
+        }
+
+        let count = buf.len().min(self.remaining);
+        let read = self.inner.read(&mut buf[..count])?;
+        self.remaining -= read;
+
+        Ok(read)
+    }
+}
+impl<S: Write> Write for LimitedStream<S> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Read;
+
+    use super::LimitedStream;
+
+    #[test]
+    fn test_limited_stream_within_limit() {
+        let mut stream = LimitedStream::new(&b"hello"[..], 5);
+
+        let mut data = Vec::new();
+        stream.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"hello");
+    }
+
+    #[test]
+    fn test_limited_stream_over_limit() {
+        let mut stream = LimitedStream::new(&b"hello world"[..], 5);
+
+        let mut data = Vec::new();
+        let err = stream.read_to_end(&mut data).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::Other);
+        assert_eq!(data, b"hello");
+    }
+}
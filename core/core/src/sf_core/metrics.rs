@@ -23,6 +23,8 @@ pub struct PerformMetricsData<'a> {
     /// Map version as parsed from map metadata
     pub map_version: Option<String>,
     pub map_content_hash: Option<&'a str>,
+    /// Url of the first provider candidate when this perform is a failover
+    pub failed_over_from: Option<&'a str>,
 }
 impl<'a> PerformMetricsData<'a> {
     pub fn get_profile(&self) -> Cow<'_, str> {
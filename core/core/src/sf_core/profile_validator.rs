@@ -1,15 +1,18 @@
 #![allow(dead_code)] // TODO: validator broken? always has been
 
+use std::time::Duration;
+
 use thiserror::Error;
 
 use interpreter_js::{JsInterpreter, JsInterpreterError};
-use map_std::unstable::MapValue;
-
-use super::{
-    map_std_impl::{MapStdImpl, MapStdImplConfig},
-    Fs,
+use map_std::{unstable::MapValue, MapStdFull};
+use sf_std::{
+    abi::{StaticMessageExchange, StaticStreamExchange},
+    unstable::fs::FsConvenience,
 };
 
+use super::map_std_impl::{MapStdImpl, MapStdImplConfig};
+
 #[derive(Debug, Error)]
 pub enum ProfileValidatorError {
     #[error("Error interpreting validator code: {0}")]
@@ -26,25 +29,38 @@ pub enum ProfileValidatorError {
     ErrorValidationFailed(String),
 }
 
-pub struct ProfileValidator {
-    interpreter: JsInterpreter<MapStdImpl>,
+pub struct ProfileValidator<Me, Se>
+where
+    Me: StaticMessageExchange + 'static,
+    Se: StaticStreamExchange + 'static,
+{
+    interpreter: JsInterpreter<MapStdImpl<Me, Se>>,
     validator_bytecode: Vec<u8>,
     usecase: String,
 }
-impl ProfileValidator {
+impl<Me, Se> ProfileValidator<Me, Se>
+where
+    Me: StaticMessageExchange + 'static,
+    Se: StaticStreamExchange + 'static,
+{
     const PROFILE_VALIDATOR_JS: &'static str = include_str!("../../assets/js/profile_validator.js");
 
     pub fn new(profile: String, usecase: String) -> Result<Self, ProfileValidatorError> {
-        let mut interpreter = JsInterpreter::new(MapStdImpl::new(MapStdImplConfig {
-            log_http_transactions: false,
-            log_http_transactions_body_max_size: 0,
-            user_agent: "".to_string(),
-        }))?;
+        let mut interpreter = JsInterpreter::new(MapStdImpl::new(
+            MapStdImplConfig {
+                log_http_transactions: false,
+                log_http_transactions_body_max_size: 0,
+                user_agent: "".to_string(),
+                http_body_max_size: 0,
+                sleep_max_duration: Some(Duration::ZERO),
+            },
+            0,
+        ))?;
 
         let validator_bytecode = match std::env::var("ONESDK_REPLACE_PROFILE_VALIDATOR").ok() {
             None => interpreter.compile_code("profile_validator.js", Self::PROFILE_VALIDATOR_JS),
             Some(path) => {
-                let replacement = Fs::read_to_string(&path)
+                let replacement = FsConvenience::<Me, Se>::read_to_string(&path)
                     .expect("Failed to load replacement profile_validator");
                 interpreter.compile_code(&path, &replacement)
             }
@@ -0,0 +1,305 @@
+//! Recording and replaying of map http transactions (VCR mode).
+//!
+//! Transactions are matched on the request as issued by the map, before security is resolved,
+//! so cassettes don't contain secrets from security values and can be replayed with dummy ones.
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use sf_std::{
+    abi::{StaticMessageExchange, StaticStreamExchange},
+    unstable::fs::FsConvenience,
+    HeadersMultiMap, MultiMap,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VcrMode {
+    Off,
+    /// Transactions are sent to the host and recorded into the cassette.
+    Record,
+    /// Transactions are served from the cassette, the host is never called.
+    Replay,
+}
+impl FromStr for VcrMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "record" => Ok(Self::Record),
+            "replay" => Ok(Self::Replay),
+            _ => Err(format!("Unknown VCR mode: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VcrError {
+    #[error("Failed to read cassette \"{0}\": {1}")]
+    ReadFailed(String, std::io::Error),
+    #[error("Failed to parse cassette \"{0}\": {1}")]
+    ParseFailed(String, serde_json::Error),
+    #[error("Failed to serialize cassette \"{0}\": {1}")]
+    SerializeFailed(String, serde_json::Error),
+    #[error("Failed to write cassette \"{0}\": {1}")]
+    WriteFailed(String, std::io::Error),
+    #[error("No recorded interaction matches {0} {1}")]
+    NoMatch(String, String),
+    #[error("Recorded body is not valid base64: {0}")]
+    InvalidBody(#[from] base64::DecodeError),
+}
+
+/// Request as issued by the map, used to match interactions.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VcrRequest {
+    pub method: String,
+    pub url: String,
+    pub query: BTreeMap<String, Vec<String>>,
+    /// Base64 encoded body.
+    pub body: Option<String>,
+}
+impl VcrRequest {
+    pub fn new(method: &str, url: &str, query: &MultiMap, body: Option<&[u8]>) -> Self {
+        Self {
+            method: method.to_uppercase(),
+            url: url.to_string(),
+            query: query
+                .iter()
+                .map(|(key, values)| (key.clone(), values.clone()))
+                .collect(),
+            body: body.map(|body| base64::engine::general_purpose::STANDARD.encode(body)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VcrResponse {
+    pub status: u16,
+    pub headers: HeadersMultiMap,
+    /// Base64 encoded body.
+    pub body: String,
+}
+impl VcrResponse {
+    pub fn new(status: u16, headers: HeadersMultiMap, body: &[u8]) -> Self {
+        Self {
+            status,
+            headers,
+            body: base64::engine::general_purpose::STANDARD.encode(body),
+        }
+    }
+
+    pub fn decode_body(&self) -> Result<Vec<u8>, VcrError> {
+        Ok(base64::engine::general_purpose::STANDARD.decode(&self.body)?)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct VcrInteraction {
+    request: VcrRequest,
+    response: VcrResponse,
+    #[serde(skip)]
+    replayed: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cassette {
+    interactions: Vec<VcrInteraction>,
+}
+
+#[derive(Debug)]
+pub struct Vcr {
+    mode: VcrMode,
+    path: String,
+    cassette: Cassette,
+    /// Whether interactions were recorded since the cassette was last saved.
+    unsaved: bool,
+}
+impl Vcr {
+    /// Creates a VCR for given mode, returns `None` when the mode is off.
+    ///
+    /// In replay mode the cassette is loaded immediately, in record mode it is overwritten on the first [Self::save] after an interaction is recorded.
+    pub fn new<Me: StaticMessageExchange, Se: StaticStreamExchange>(
+        mode: VcrMode,
+        path: &str,
+    ) -> Result<Option<Self>, VcrError> {
+        let cassette = match mode {
+            VcrMode::Off => return Ok(None),
+            VcrMode::Record => Cassette::default(),
+            VcrMode::Replay => {
+                let data = FsConvenience::<Me, Se>::read(path)
+                    .map_err(|err| VcrError::ReadFailed(path.to_string(), err))?;
+                serde_json::from_slice(&data)
+                    .map_err(|err| VcrError::ParseFailed(path.to_string(), err))?
+            }
+        };
+
+        Ok(Some(Self {
+            mode,
+            path: path.to_string(),
+            cassette,
+            unsaved: false,
+        }))
+    }
+
+    pub fn mode(&self) -> VcrMode {
+        self.mode
+    }
+
+    /// Returns the recorded response for `request`.
+    ///
+    /// Identical requests are served in the order they were recorded, the last one is repeated once all have been replayed.
+    pub fn replay(&mut self, request: &VcrRequest) -> Result<VcrResponse, VcrError> {
+        let interactions = &mut self.cassette.interactions;
+        let position = interactions
+            .iter()
+            .position(|interaction| &interaction.request == request && !interaction.replayed)
+            .or_else(|| {
+                interactions
+                    .iter()
+                    .rposition(|interaction| &interaction.request == request)
+            });
+
+        match position {
+            Some(position) => {
+                interactions[position].replayed = true;
+                Ok(interactions[position].response.clone())
+            }
+            None => Err(VcrError::NoMatch(
+                request.method.clone(),
+                request.url.clone(),
+            )),
+        }
+    }
+
+    /// Records an interaction, it is kept in memory until [Self::save] is called.
+    pub fn record(&mut self, request: VcrRequest, response: VcrResponse) {
+        self.cassette.interactions.push(VcrInteraction {
+            request,
+            response,
+            replayed: false,
+        });
+        self.unsaved = true;
+    }
+
+    /// Writes the cassette if any interactions were recorded since it was last saved.
+    pub fn save<Me: StaticMessageExchange, Se: StaticStreamExchange>(
+        &mut self,
+    ) -> Result<(), VcrError> {
+        if !self.unsaved {
+            return Ok(());
+        }
+
+        let data = serde_json::to_vec_pretty(&self.cassette)
+            .map_err(|err| VcrError::SerializeFailed(self.path.clone(), err))?;
+        FsConvenience::<Me, Se>::write(&self.path, &data)
+            .map_err(|err| VcrError::WriteFailed(self.path.clone(), err))?;
+        self.unsaved = false;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn interaction(request: &VcrRequest, status: u16) -> VcrInteraction {
+        VcrInteraction {
+            request: request.clone(),
+            response: VcrResponse::new(status, HashMap::new(), b""),
+            replayed: false,
+        }
+    }
+
+    #[test]
+    fn test_replay_in_recorded_order() {
+        let request = VcrRequest::new("get", "https://example.com/", &HashMap::new(), None);
+        let mut vcr = Vcr {
+            mode: VcrMode::Replay,
+            path: "cassette.json".to_string(),
+            cassette: Cassette {
+                interactions: vec![interaction(&request, 500), interaction(&request, 200)],
+            },
+            unsaved: false,
+        };
+
+        assert_eq!(vcr.replay(&request).unwrap().status, 500);
+        assert_eq!(vcr.replay(&request).unwrap().status, 200);
+        // last one is repeated
+        assert_eq!(vcr.replay(&request).unwrap().status, 200);
+    }
+
+    #[test]
+    fn test_replay_matches_query_and_body() {
+        let request = VcrRequest::new(
+            "POST",
+            "https://example.com/",
+            &HashMap::from([("foo".to_string(), vec!["bar".to_string()])]),
+            Some(b"body"),
+        );
+        let mut vcr = Vcr {
+            mode: VcrMode::Replay,
+            path: "cassette.json".to_string(),
+            cassette: Cassette {
+                interactions: vec![interaction(&request, 201)],
+            },
+            unsaved: false,
+        };
+
+        assert_eq!(vcr.replay(&request).unwrap().status, 201);
+        assert!(matches!(
+            vcr.replay(&VcrRequest::new(
+                "POST",
+                "https://example.com/",
+                &HashMap::new(),
+                Some(b"body")
+            )),
+            Err(VcrError::NoMatch(_, _))
+        ));
+    }
+}
+
+#[cfg(all(test, feature = "native_host"))]
+mod native_test {
+    use std::collections::HashMap;
+
+    use crate::native::{NativeMessageExchange, NativeStreamExchange};
+
+    use super::*;
+
+    #[test]
+    fn test_record_is_written_on_save() {
+        let path = std::env::temp_dir().join(format!("vcr_test_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let request = VcrRequest::new("GET", "https://example.com/", &HashMap::new(), None);
+
+        let mut vcr =
+            Vcr::new::<NativeMessageExchange, NativeStreamExchange>(VcrMode::Record, path)
+                .unwrap()
+                .unwrap();
+        vcr.record(
+            request.clone(),
+            VcrResponse::new(200, HashMap::new(), b"first"),
+        );
+        vcr.record(
+            request.clone(),
+            VcrResponse::new(201, HashMap::new(), b"second"),
+        );
+        assert!(!std::path::Path::new(path).exists());
+
+        vcr.save::<NativeMessageExchange, NativeStreamExchange>()
+            .unwrap();
+        let mut replay =
+            Vcr::new::<NativeMessageExchange, NativeStreamExchange>(VcrMode::Replay, path)
+                .unwrap()
+                .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(replay.replay(&request).unwrap().status, 200);
+        assert_eq!(replay.replay(&request).unwrap().status, 201);
+    }
+}
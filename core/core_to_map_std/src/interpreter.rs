@@ -0,0 +1,28 @@
+//! Interface between the core and interpreters running maps.
+//!
+//! The core only talks to maps through this trait and [MapStdFull], so maps can be written in any language with an interpreter implementing it.
+
+use std::ops::DerefMut;
+
+use sf_std::unstable::exception::PerformException;
+
+use crate::{unstable::MapValue, MapStdFull};
+
+/// Runs maps on top of map std implementation `S`.
+pub trait Interpreter<S: MapStdFull> {
+    /// Evaluates `code` named `name` in the global scope, i.e. the map stdlib.
+    fn eval(&mut self, name: &str, code: &str) -> Result<(), PerformException>;
+
+    /// Loads map `code` named `name` and runs its `usecase`.
+    ///
+    /// The map sets its output through the map std.
+    fn run(&mut self, name: &str, code: &str, usecase: &str) -> Result<(), PerformException>;
+
+    /// Returns the map std state shared with the running map.
+    fn state_mut(&mut self) -> Box<dyn DerefMut<Target = S> + '_>;
+
+    /// Takes output set by the last run map.
+    fn take_output(&mut self) -> Option<Result<MapValue, MapValue>> {
+        self.state_mut().take_output()
+    }
+}
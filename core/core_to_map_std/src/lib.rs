@@ -72,14 +72,22 @@ macro_rules! define_exchange_map_to_core {
             ),+
         }
         impl $(<$life>)? $receiver_enum $(<$life>)? {
+            /// Handles a JSON message and returns a JSON response.
             pub fn handle($state_name: &mut impl $state_trait, message: & $($life)? [u8]) -> String {
-                match serde_json::from_slice::<$receiver_enum>(message) {
+                let response = Self::handle_encoded($state_name, message, sf_std::abi::MessageEncoding::Json);
+
+                String::from_utf8(response).expect("JSON is valid UTF-8")
+            }
+
+            /// Handles a message in `encoding` and returns a response in the same encoding.
+            pub fn handle_encoded($state_name: &mut impl $state_trait, message: & $($life)? [u8], encoding: sf_std::abi::MessageEncoding) -> Vec<u8> {
+                match $crate::decode_message::<$receiver_enum>(message, encoding) {
                     Err(err) => {
                         let error = serde_json::json!({
                             "kind": "err",
                             "error": format!("Failed to deserialize {} message: {}", stringify!($receiver_enum), err)
                         });
-                        serde_json::to_string(&error)
+                        $crate::encode_message(&error, encoding)
                     }
                     $(
                         Ok(Self::$in_name $({ $($in_field_name),+ })?) => {
@@ -97,16 +105,44 @@ macro_rules! define_exchange_map_to_core {
 
                             let response = $handler;
 
-                            serde_json::to_string(&response)
+                            $crate::encode_message(&response, encoding)
                         }
                     )+
-                }.unwrap()
+                }
             }
         }
     };
 }
 
+fn decode_message<'a, T: serde::Deserialize<'a>>(
+    message: &'a [u8],
+    encoding: sf_std::abi::MessageEncoding,
+) -> Result<T, String> {
+    match encoding {
+        sf_std::abi::MessageEncoding::Json => {
+            serde_json::from_slice(message).map_err(|err| err.to_string())
+        }
+        sf_std::abi::MessageEncoding::MessagePack => {
+            rmp_serde::from_slice(message).map_err(|err| err.to_string())
+        }
+    }
+}
+
+fn encode_message<T: serde::Serialize>(
+    message: &T,
+    encoding: sf_std::abi::MessageEncoding,
+) -> Vec<u8> {
+    match encoding {
+        sf_std::abi::MessageEncoding::Json => serde_json::to_vec(message).unwrap(),
+        sf_std::abi::MessageEncoding::MessagePack => rmp_serde::to_vec_named(message).unwrap(),
+    }
+}
+
 pub mod handle_map;
+pub mod interpreter;
 pub mod unstable;
 
-pub trait MapStdFull: unstable::MapStdUnstable {}
+pub trait MapStdFull: unstable::MapStdUnstable {
+    /// Takes output set by the map through [unstable::MapStdUnstable::set_output_success] or [unstable::MapStdUnstable::set_output_failure].
+    fn take_output(&mut self) -> Option<Result<unstable::MapValue, unstable::MapValue>>;
+}
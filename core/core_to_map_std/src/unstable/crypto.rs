@@ -0,0 +1,110 @@
+//! Cryptographic primitives backing the map `crypto` global.
+
+use hmac::{Mac, SimpleHmac};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use thiserror::Error;
+
+/// Maximum number of bytes which can be requested from the random source at once, as specified by `crypto.getRandomValues`.
+pub const RANDOM_MAX_LENGTH: usize = 65536;
+
+#[derive(Debug, Error)]
+pub enum CryptoError {
+    #[error("Unsupported algorithm \"{0}\"")]
+    UnsupportedAlgorithm(String),
+    #[error("Requested {0} random bytes, maximum is {RANDOM_MAX_LENGTH}")]
+    RandomQuotaExceeded(usize),
+    #[error("Failed to get random bytes: {0}")]
+    RandomFailed(String),
+}
+
+/// Digest algorithms named as in Web Crypto.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestAlgorithm {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+impl DigestAlgorithm {
+    /// Parses Web Crypto algorithm name, case-insensitively.
+    pub fn parse(name: &str) -> Result<Self, CryptoError> {
+        match name.to_ascii_uppercase().as_str() {
+            "SHA-256" => Ok(Self::Sha256),
+            "SHA-384" => Ok(Self::Sha384),
+            "SHA-512" => Ok(Self::Sha512),
+            _ => Err(CryptoError::UnsupportedAlgorithm(name.to_string())),
+        }
+    }
+}
+
+pub fn digest(algorithm: DigestAlgorithm, data: &[u8]) -> Vec<u8> {
+    match algorithm {
+        DigestAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        DigestAlgorithm::Sha384 => Sha384::digest(data).to_vec(),
+        DigestAlgorithm::Sha512 => Sha512::digest(data).to_vec(),
+    }
+}
+
+pub fn hmac(algorithm: DigestAlgorithm, key: &[u8], data: &[u8]) -> Vec<u8> {
+    fn sign<D: Digest + sha2::digest::core_api::BlockSizeUser>(key: &[u8], data: &[u8]) -> Vec<u8> {
+        // HMAC accepts keys of any length
+        let mut mac = SimpleHmac::<D>::new_from_slice(key).unwrap();
+        mac.update(data);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    match algorithm {
+        DigestAlgorithm::Sha256 => sign::<Sha256>(key, data),
+        DigestAlgorithm::Sha384 => sign::<Sha384>(key, data),
+        DigestAlgorithm::Sha512 => sign::<Sha512>(key, data),
+    }
+}
+
+/// Fills `buf` from the system random source, which is `random_get` under WASI.
+pub fn random_fill(buf: &mut [u8]) -> Result<(), CryptoError> {
+    if buf.len() > RANDOM_MAX_LENGTH {
+        return Err(CryptoError::RandomQuotaExceeded(buf.len()));
+    }
+
+    getrandom::getrandom(buf).map_err(|err| CryptoError::RandomFailed(err.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_digest() {
+        assert_eq!(
+            hex::encode(digest(DigestAlgorithm::parse("sha-256").unwrap(), b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(digest(DigestAlgorithm::Sha384, b"").len(), 48);
+        assert!(DigestAlgorithm::parse("MD5").is_err());
+    }
+
+    #[test]
+    fn test_hmac() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            hex::encode(hmac(
+                DigestAlgorithm::Sha256,
+                b"Jefe",
+                b"what do ya want for nothing?"
+            )),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_random_fill() {
+        let mut buf = [0u8; 32];
+        random_fill(&mut buf).unwrap();
+        assert_ne!(buf, [0u8; 32]);
+
+        let mut buf = vec![0u8; RANDOM_MAX_LENGTH + 1];
+        assert!(matches!(
+            random_fill(&mut buf),
+            Err(CryptoError::RandomQuotaExceeded(_))
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+//! Date parsing and formatting backing the map `std.unstable` date helpers.
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DateError {
+    #[error("Unsupported date format \"{0}\", expected \"iso8601\" or \"rfc2822\"")]
+    UnsupportedFormat(String),
+    #[error("Invalid {0} date \"{1}\": {2}")]
+    InvalidDate(&'static str, String, String),
+    #[error("Timestamp {0} is out of range")]
+    TimestampOutOfRange(f64),
+    #[error("Offset of {0} minutes is out of range")]
+    OffsetOutOfRange(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateFormat {
+    Iso8601,
+    Rfc2822,
+}
+impl DateFormat {
+    pub fn parse(name: &str) -> Result<Self, DateError> {
+        match name.to_ascii_lowercase().as_str() {
+            "iso8601" => Ok(Self::Iso8601),
+            "rfc2822" => Ok(Self::Rfc2822),
+            _ => Err(DateError::UnsupportedFormat(name.to_string())),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Iso8601 => "ISO 8601",
+            Self::Rfc2822 => "RFC 2822",
+        }
+    }
+}
+
+/// Parses `input` into milliseconds since the Unix epoch.
+///
+/// ISO 8601 dates and date-times without an offset are treated as UTC.
+pub fn parse_date(input: &str, format: DateFormat) -> Result<f64, DateError> {
+    let invalid = |err: chrono::ParseError| {
+        DateError::InvalidDate(format.name(), input.to_string(), err.to_string())
+    };
+
+    let date_time = match format {
+        DateFormat::Rfc2822 => DateTime::parse_from_rfc2822(input)
+            .map_err(invalid)?
+            .with_timezone(&Utc),
+        DateFormat::Iso8601 => match DateTime::parse_from_rfc3339(input) {
+            Ok(date_time) => date_time.with_timezone(&Utc),
+            Err(err) => NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%M:%S%.f")
+                .or_else(|_| {
+                    NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                        .map(|date| date.and_hms_opt(0, 0, 0).unwrap())
+                })
+                .map(|date_time| Utc.from_utc_datetime(&date_time))
+                .map_err(|_| invalid(err))?,
+        },
+    };
+
+    Ok(date_time.timestamp_millis() as f64)
+}
+
+/// Formats `timestamp` in milliseconds since the Unix epoch in the timezone given by `offset_minutes` east of UTC.
+pub fn format_date(
+    timestamp: f64,
+    format: DateFormat,
+    offset_minutes: i32,
+) -> Result<String, DateError> {
+    let offset = offset_minutes
+        .checked_mul(60)
+        .and_then(FixedOffset::east_opt)
+        .ok_or(DateError::OffsetOutOfRange(offset_minutes))?;
+    if !timestamp.is_finite() {
+        return Err(DateError::TimestampOutOfRange(timestamp));
+    }
+    let date_time = Utc
+        .timestamp_millis_opt(timestamp.trunc() as i64)
+        .single()
+        .ok_or(DateError::TimestampOutOfRange(timestamp))?
+        .with_timezone(&offset);
+
+    Ok(match format {
+        DateFormat::Iso8601 => date_time.to_rfc3339_opts(SecondsFormat::Millis, true),
+        DateFormat::Rfc2822 => date_time.to_rfc2822(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(
+            parse_date("2023-03-01T12:30:00.250+01:00", DateFormat::Iso8601).unwrap(),
+            1677670200250.0
+        );
+        assert_eq!(
+            parse_date("2023-03-01T11:30:00", DateFormat::Iso8601).unwrap(),
+            1677670200000.0
+        );
+        assert_eq!(
+            parse_date("2023-03-01", DateFormat::Iso8601).unwrap(),
+            1677628800000.0
+        );
+        assert_eq!(
+            parse_date("Wed, 01 Mar 2023 12:30:00 +0100", DateFormat::Rfc2822).unwrap(),
+            1677670200000.0
+        );
+        assert!(parse_date("yesterday", DateFormat::Iso8601).is_err());
+    }
+
+    #[test]
+    fn test_format_date() {
+        assert_eq!(
+            format_date(1677670200250.0, DateFormat::Iso8601, 0).unwrap(),
+            "2023-03-01T11:30:00.250Z"
+        );
+        assert_eq!(
+            format_date(1677670200250.0, DateFormat::Iso8601, 60).unwrap(),
+            "2023-03-01T12:30:00.250+01:00"
+        );
+        assert_eq!(
+            format_date(1677670200000.0, DateFormat::Rfc2822, -300).unwrap(),
+            "Wed, 1 Mar 2023 06:30:00 -0500"
+        );
+        assert!(format_date(f64::NAN, DateFormat::Iso8601, 0).is_err());
+        assert!(format_date(0.0, DateFormat::Iso8601, 24 * 60).is_err());
+        assert!(DateFormat::parse("rfc3339").is_err());
+    }
+}
@@ -1,16 +1,22 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, time::Duration};
 
 use thiserror::Error;
 
 use serde::{Deserialize, Serialize};
 
 use sf_std::{
-    abi::Handle, unstable::http::HttpCallError as HostHttpCallError, HeadersMultiMap, MultiMap,
+    abi::{Handle, MessageEncoding},
+    unstable::http::{HttpCallError as HostHttpCallError, HttpRedirect},
+    HeadersMultiMap, MultiMap,
 };
 
+pub mod crypto;
+pub mod date;
 pub mod security;
 pub mod services;
 
+pub use crypto::CryptoError;
+
 #[allow(dead_code)]
 pub const MODULE_NAME: &str = "sf_core_unstable";
 
@@ -182,6 +188,28 @@ macro_rules! map_value {
     };
 }
 
+/// Level of a log emitted by the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+impl LogLevel {
+    pub fn parse(level: &str) -> Option<Self> {
+        match level.to_ascii_lowercase().as_str() {
+            "trace" => Some(Self::Trace),
+            "debug" => Some(Self::Debug),
+            "info" => Some(Self::Info),
+            "warn" => Some(Self::Warn),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(tag = "kind", content = "ids")]
 #[serde(rename_all = "kebab-case")]
@@ -200,7 +228,9 @@ pub struct HttpRequest {
     /// Multiple values with the same key will be repeated in the query string, no joining will be performed.
     pub query: MultiMap,
     /// Body as bytes.
-    pub body: Option<Vec<u8>>
+    pub body: Option<Vec<u8>>,
+    /// Redirect handling requested by the map.
+    pub redirect: HttpRedirect,
 }
 pub struct HttpResponse {
     /// Status code of the response.
@@ -222,6 +252,8 @@ pub enum ErrorCode {
     NetworkInvalidHandle,
     #[serde(rename = "network:invalid_url")]
     NetworkInvalidUrl,
+    #[serde(rename = "network:redirect")]
+    NetworkRedirect,
 
     #[serde(rename = "security:misssing_secret")]
     SecurityMissingSecret,
@@ -232,6 +264,12 @@ pub enum ErrorCode {
     OutcomeUnexpected,
     #[serde(rename = "context:taken")]
     ContextTaken,
+
+    #[serde(rename = "perform:cancelled")]
+    PerformCancelled,
+
+    #[serde(rename = "sleep:failed")]
+    SleepFailed,
 }
 #[derive(Debug, Error)]
 pub enum HttpCallError {
@@ -242,6 +280,8 @@ pub enum HttpCallError {
     InvalidSecurityConfiguration(String),
     #[error("Missing secret value: {0}")]
     MissingSecret(String),
+    #[error("Perform was cancelled")]
+    Cancelled,
 }
 impl From<HostHttpCallError> for HttpCallError {
     fn from(value: HostHttpCallError) -> Self {
@@ -271,6 +311,13 @@ pub enum HttpCallHeadError {
 
     #[error("Handle does not belong to an active http request")]
     InvalidHandle,
+    #[error("Perform was cancelled")]
+    Cancelled,
+
+    #[error("Redirect to {0} is not allowed")]
+    RedirectNotAllowed(String),
+    #[error("Too many redirects, maximum is {0}")]
+    TooManyRedirects(u32),
 }
 impl From<HostHttpCallError> for HttpCallHeadError {
     fn from(value: HostHttpCallError) -> Self {
@@ -284,6 +331,14 @@ impl From<HostHttpCallError> for HttpCallHeadError {
     }
 }
 
+#[derive(Debug, Error)]
+pub enum SleepError {
+    #[error("Sleep failed: {0}")]
+    Failed(String),
+    #[error("Perform was cancelled")]
+    Cancelled,
+}
+
 #[derive(Debug, Error)]
 pub enum TakeContextError {
     #[error("Context has already been taken")]
@@ -301,12 +356,22 @@ pub enum SetOutputError {
 pub trait MapStdUnstable {
     // env
     fn print(&mut self, message: &str);
+    /// Logs `message` at `level` with structured `fields`.
+    fn log(&mut self, level: LogLevel, message: &str, fields: &MapValueObject);
 
     // streams
     fn stream_read(&mut self, handle: Handle, buf: &mut [u8]) -> std::io::Result<usize>;
     fn stream_write(&mut self, handle: Handle, buf: &[u8]) -> std::io::Result<usize>;
     fn stream_close(&mut self, handle: Handle) -> std::io::Result<()>;
 
+    // crypto
+    fn crypto_digest(&mut self, algorithm: &str, data: &[u8]) -> Result<Vec<u8>, CryptoError>;
+    fn crypto_hmac(&mut self, algorithm: &str, key: &[u8], data: &[u8]) -> Result<Vec<u8>, CryptoError>;
+    fn crypto_random_fill(&mut self, buf: &mut [u8]) -> Result<(), CryptoError>;
+
+    // timers
+    fn sleep(&mut self, duration: Duration) -> Result<(), SleepError>;
+
     // http
     fn http_call(&mut self, params: HttpRequest, security: Option<HttpRequestSecurity>) -> Result<Handle, HttpCallError>;
     fn http_call_head(&mut self, handle: Handle) -> Result<HttpResponse, HttpCallHeadError>;
@@ -331,7 +396,9 @@ define_exchange_map_to_core! {
             headers: HeadersMultiMap,
             query: MultiMap,
             security: Option<HttpRequestSecurity>,
+            #[serde(default, with = "sf_std::abi::bytes::option")]
             body: Option<Vec<u8>>,
+            redirect: Option<HttpRedirect>,
         } -> enum Response {
             Ok {
                 request_body_stream: Option<()>, // TODO: think about implementation/ergonomics
@@ -348,6 +415,7 @@ define_exchange_map_to_core! {
                 headers,
                 query,
                 body,
+                redirect: redirect.unwrap_or_default(),
             }, security);
 
             match handle {
@@ -359,6 +427,7 @@ define_exchange_map_to_core! {
                     HttpCallError::Failed(message) => Response::Err { error_code: ErrorCode::NetworkError, message },
                     HttpCallError::MissingSecret(message) => Response::Err { error_code: ErrorCode::SecurityMissingSecret, message },
                     HttpCallError::InvalidSecurityConfiguration(message) => Response::Err { error_code: ErrorCode::SecurityInvalidConfiguration, message },
+                    HttpCallError::Cancelled => Response::Err { error_code: ErrorCode::PerformCancelled, message: err.to_string() },
                 }
             }
         },
@@ -385,6 +454,24 @@ define_exchange_map_to_core! {
                 HttpCallHeadError::Failed(message) => Response::Err { error_code: ErrorCode::NetworkError, message },
                 HttpCallHeadError::ConnectionRefused(message) => Response::Err { error_code: ErrorCode::ConnectionRefused, message },
                 HttpCallHeadError::HostNotFound(message) => Response::Err { error_code: ErrorCode::HostNotFound, message },
+                HttpCallHeadError::Cancelled => Response::Err { error_code: ErrorCode::PerformCancelled, message: err.to_string() },
+                HttpCallHeadError::RedirectNotAllowed(_) | HttpCallHeadError::TooManyRedirects(_) => Response::Err { error_code: ErrorCode::NetworkRedirect, message: err.to_string() },
+            }
+        },
+        // timers
+        Sleep {
+            duration_ms: u64,
+        } -> enum Response {
+            Ok,
+            Err {
+                error_code: ErrorCode,
+                message: String,
+            }
+        } => match state.sleep(Duration::from_millis(duration_ms)) {
+            Ok(()) => Response::Ok,
+            Err(err) => match err {
+                SleepError::Failed(_) => Response::Err { error_code: ErrorCode::SleepFailed, message: err.to_string() },
+                SleepError::Cancelled => Response::Err { error_code: ErrorCode::PerformCancelled, message: err.to_string() },
             }
         },
         // input and output
@@ -429,3 +516,12 @@ define_exchange_map_to_core! {
 pub fn handle_message<H: MapStdUnstable>(state: &mut H, message: &[u8]) -> String {
     RequestUnstable::handle(state, message)
 }
+
+/// Same as [handle_message] for messages in `encoding`.
+pub fn handle_message_encoded<H: MapStdUnstable>(
+    state: &mut H,
+    message: &[u8],
+    encoding: MessageEncoding,
+) -> Vec<u8> {
+    RequestUnstable::handle_encoded(state, message, encoding)
+}
@@ -5,6 +5,7 @@ use base64::Engine;
 use sf_std::{
     unstable::{
         exception::{PerformException, PerformExceptionErrorCode},
+        http::{HttpClientCertificate, HttpTransport},
         provider::ProviderJson,
         HostValue,
     },
@@ -88,6 +89,7 @@ pub enum SecurityValue {
     ApiKey { apikey: String },
     Basic { username: String, password: String },
     Bearer { token: String },
+    ClientCertificate { certificate: String, key: String },
 }
 pub type SecurityValuesMap = HashMap<String, SecurityValue>;
 
@@ -101,6 +103,7 @@ impl From<PrepareSecurityMapError> for PerformException {
         PerformException {
             error_code: PerformExceptionErrorCode::PrepareSecurityMapError,
             message: value.to_string(),
+            js_exception: None,
         }
     }
 }
@@ -127,16 +130,8 @@ impl SecurityMisconfiguredError {
     }
 }
 
-pub fn prepare_security_map(
-    provider_json: &ProviderJson,
-    map_security: &HostValue,
-) -> Result<SecurityMap, PrepareSecurityMapError> {
-    let security_schemes = match &provider_json.security_schemes {
-        Some(security_schemes) => security_schemes,
-        None => return Ok(SecurityMap::new()),
-    };
-
-    let security_values = match &map_security {
+fn parse_security_values(map_security: &HostValue) -> SecurityValuesMap {
+    match &map_security {
         HostValue::Object(obj) => {
             let mut result = SecurityValuesMap::new();
 
@@ -185,6 +180,25 @@ pub fn prepare_security_map(
                                 }
                             },
                         }
+                    } else if obj.contains_key("certificate") {
+                        security_value = SecurityValue::ClientCertificate {
+                            certificate: match obj.get("certificate") {
+                                Some(HostValue::String(str)) => str.to_owned(),
+                                _ => {
+                                    unreachable!(
+                                        "Schema validation ensures there is String value for certificate field."
+                                    );
+                                }
+                            },
+                            key: match obj.get("key") {
+                                Some(HostValue::String(str)) => str.to_owned(),
+                                _ => {
+                                    unreachable!(
+                                        "Schema validation ensures there is String value for key field."
+                                    );
+                                }
+                            },
+                        }
                     } else {
                         unreachable!("Schema validation ensures value is one of the types above.");
                     }
@@ -198,8 +212,20 @@ pub fn prepare_security_map(
             result
         }
         _ => HashMap::new(),
+    }
+}
+
+pub fn prepare_security_map(
+    provider_json: &ProviderJson,
+    map_security: &HostValue,
+) -> Result<SecurityMap, PrepareSecurityMapError> {
+    let security_schemes = match &provider_json.security_schemes {
+        Some(security_schemes) => security_schemes,
+        None => return Ok(SecurityMap::new()),
     };
 
+    let security_values = parse_security_values(map_security);
+
     let mut security_map = SecurityMap::new();
     let mut errors: Vec<SecurityMisconfiguredError> = Vec::new();
 
@@ -316,6 +342,40 @@ pub fn prepare_security_map(
     Ok(security_map)
 }
 
+/// Resolves provider transport settings, looking up the client certificate in security values.
+pub fn prepare_transport(
+    provider_json: &ProviderJson,
+    map_security: &HostValue,
+) -> Result<Option<HttpTransport>, PrepareSecurityMapError> {
+    let transport = match &provider_json.transport {
+        Some(transport) => transport,
+        None => return Ok(None),
+    };
+
+    let client_certificate = match transport.client_certificate {
+        None => None,
+        Some(ref id) => match parse_security_values(map_security).remove(id) {
+            Some(SecurityValue::ClientCertificate { certificate, key }) => {
+                Some(HttpClientCertificate { certificate, key })
+            }
+            _ => {
+                return Err(PrepareSecurityMapError::SecurityMisconfigured(vec![
+                    SecurityMisconfiguredError {
+                        id: id.to_owned(),
+                        expected: "{ certificate: String, key: String }".to_string(),
+                    },
+                ]))
+            }
+        },
+    };
+
+    Ok(Some(HttpTransport {
+        proxy_url: transport.proxy_url.clone(),
+        ca_pem: transport.ca_pem.clone(),
+        client_certificate,
+    }))
+}
+
 pub fn resolve_security(
     security_map: &SecurityMap,
     params: &mut HttpRequest,
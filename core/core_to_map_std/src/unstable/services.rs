@@ -15,6 +15,7 @@ impl From<PrepareServicesMapError> for PerformException {
         PerformException {
             error_code: PerformExceptionErrorCode::PrepareServicesMapError,
             message: value.to_string(),
+            js_exception: None,
         }
     }
 }
@@ -8,6 +8,7 @@ pub use wasm_abi as abi;
 
 pub mod fmt;
 pub mod unstable;
+pub mod v1;
 
 pub type MultiMap<K = String, V = String> = HashMap<K, Vec<V>>;
 pub type HeadersMultiMap = MultiMap<HeaderName, HeaderValue>;
@@ -0,0 +1,112 @@
+use super::{exception::PerformException, ErrorCode};
+use crate::abi::{JsonMessageError, MessageExchange};
+
+crate::abi::define_exchange! {
+    struct CoreConfigRequest {
+        kind: "core-config"
+    } -> enum CoreConfigResponse {
+        Ok {
+            /// Configuration merged over the environment, omitted fields keep their values.
+            #[serde(default)]
+            config: serde_json::Value
+        },
+        Err {
+            error_code: ErrorCode,
+            message: String
+        }
+    }
+}
+
+crate::abi::define_exchange! {
+    struct SetupOutputExceptionRequest {
+        kind: "setup-output-exception",
+        exception: PerformException
+    } -> enum SetupOutputExceptionResponse {
+        Ok,
+        Err {
+            error_code: ErrorCode,
+            message: String
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TakeCoreConfigError {
+    #[error("Host does not provide core configuration: {0}")]
+    NotProvided(JsonMessageError),
+    #[error("Host failed to provide core configuration: {0}")]
+    Failed(String),
+}
+
+/// Takes configuration of the core from the host.
+///
+/// The configuration is returned untyped, it is up to the core to interpret it.
+pub fn take_core_config_in<E: MessageExchange>(
+    message_exchange: E,
+) -> Result<serde_json::Value, TakeCoreConfigError> {
+    let response = CoreConfigRequest::new()
+        .send_in(message_exchange)
+        .map_err(TakeCoreConfigError::NotProvided)?;
+
+    match response {
+        CoreConfigResponse::Ok { config } => Ok(config),
+        CoreConfigResponse::Err {
+            error_code,
+            message,
+        } => Err(TakeCoreConfigError::Failed(format!(
+            "{:?} {}",
+            error_code, message
+        ))),
+    }
+}
+
+/// Sends the exception which failed setup to the host.
+///
+/// Unlike other outputs this returns an error if the host can't receive it, since setup can't fail any other way.
+pub fn set_setup_output_exception_in<E: MessageExchange>(
+    exception: PerformException,
+    message_exchange: E,
+) -> Result<(), String> {
+    match SetupOutputExceptionRequest::new(exception).send_in(message_exchange) {
+        Err(err) => Err(err.to_string()),
+        Ok(SetupOutputExceptionResponse::Ok) => Ok(()),
+        Ok(SetupOutputExceptionResponse::Err {
+            error_code,
+            message,
+        }) => Err(format!("{:?}: {}", error_code, message)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::abi::testing::TestMessageExchangeFn;
+
+    #[test]
+    fn test_take_core_config() {
+        let exchange = TestMessageExchangeFn::new(|message| {
+            assert_eq!(message, json!({ "kind": "core-config" }));
+
+            json!({ "kind": "ok", "config": { "user_log": true } })
+        });
+
+        assert_eq!(
+            take_core_config_in(&exchange).unwrap(),
+            json!({ "user_log": true })
+        );
+    }
+
+    #[test]
+    fn test_take_core_config_legacy_host() {
+        let exchange = TestMessageExchangeFn::new(
+            |_| json!({ "kind": "err", "error": "Unknown message core-config" }),
+        );
+
+        assert!(matches!(
+            take_core_config_in(&exchange),
+            Err(TakeCoreConfigError::NotProvided(_))
+        ));
+    }
+}
@@ -0,0 +1,167 @@
+use super::{exception::PerformException, ErrorCode};
+use crate::abi::{JsonMessageError, MessageExchange};
+
+crate::abi::define_exchange! {
+    struct DescribeInputRequest {
+        kind: "describe-input"
+    } -> enum DescribeInputResponse {
+        Ok {
+            /// Url of the profile to describe.
+            #[serde(default)]
+            profile_url: Option<String>,
+            /// Url of the provider to describe.
+            #[serde(default)]
+            provider_url: Option<String>
+        },
+        Err {
+            error_code: ErrorCode,
+            message: String,
+        }
+    }
+}
+
+crate::abi::define_exchange! {
+    struct DescribeOutputResultRequest {
+        kind: "describe-output-result",
+        /// Description of the profile and provider.
+        result: serde_json::Value
+    } -> enum DescribeOutputResultResponse {
+        Ok,
+        Err {
+            error_code: ErrorCode,
+            message: String
+        }
+    }
+}
+
+crate::abi::define_exchange! {
+    struct DescribeOutputExceptionRequest {
+        kind: "describe-output-exception",
+        exception: PerformException
+    } -> enum DescribeOutputExceptionResponse {
+        Ok,
+        Err {
+            error_code: ErrorCode,
+            message: String
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TakeDescribeInputError {
+    #[error("Invalid input format: {0}")]
+    InvalidFormat(JsonMessageError),
+    #[error("Unknown describe input error: {0}")]
+    Unknown(String),
+}
+impl From<TakeDescribeInputError> for PerformException {
+    fn from(value: TakeDescribeInputError) -> Self {
+        PerformException {
+            error_code: super::exception::PerformExceptionErrorCode::TakeInputError,
+            message: value.to_string(),
+            js_exception: None,
+        }
+    }
+}
+
+/// Documents to describe, either can be omitted.
+pub struct DescribeInput {
+    pub profile_url: Option<String>,
+    pub provider_url: Option<String>,
+}
+impl DescribeInput {
+    pub fn take_in<E: MessageExchange>(
+        message_exchange: E,
+    ) -> Result<DescribeInput, TakeDescribeInputError> {
+        let response = match DescribeInputRequest::new().send_in(message_exchange) {
+            Err(err) => {
+                tracing::error!("Failed to receive describe_input response: {:#}", err);
+                return Err(TakeDescribeInputError::InvalidFormat(err));
+            }
+            Ok(r) => r,
+        };
+
+        match response {
+            DescribeInputResponse::Ok {
+                profile_url,
+                provider_url,
+            } => Ok(DescribeInput {
+                profile_url,
+                provider_url,
+            }),
+            DescribeInputResponse::Err {
+                error_code,
+                message,
+            } => Err(TakeDescribeInputError::Unknown(format!(
+                "{:?} {}",
+                error_code, message
+            ))),
+        }
+    }
+}
+
+pub fn set_describe_output_result_in<E: MessageExchange>(
+    result: serde_json::Value,
+    message_exchange: E,
+) {
+    let response = DescribeOutputResultRequest::new(result)
+        .send_in(message_exchange)
+        .unwrap();
+
+    match response {
+        DescribeOutputResultResponse::Ok => (),
+        DescribeOutputResultResponse::Err {
+            error_code,
+            message,
+        } => panic!(
+            "describe-output-result error: {:?}: {}",
+            error_code, message
+        ),
+    }
+}
+
+pub fn set_describe_output_exception_in<E: MessageExchange>(
+    exception: PerformException,
+    message_exchange: E,
+) {
+    let response = DescribeOutputExceptionRequest::new(exception)
+        .send_in(message_exchange)
+        .unwrap();
+
+    match response {
+        DescribeOutputExceptionResponse::Ok => (),
+        DescribeOutputExceptionResponse::Err {
+            error_code,
+            message,
+        } => panic!(
+            "describe-output-exception error: {:?}: {}",
+            error_code, message
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_message_out_describe_input() {
+        let actual = json!({
+            "kind": "ok",
+            "provider_url": "file://provider.json"
+        });
+
+        match serde_json::from_value::<DescribeInputResponse>(actual).unwrap() {
+            DescribeInputResponse::Ok {
+                profile_url,
+                provider_url,
+            } => {
+                assert_eq!(profile_url, None);
+                assert_eq!(provider_url.as_deref(), Some("file://provider.json"));
+            }
+            DescribeInputResponse::Err { .. } => unreachable!(),
+        }
+    }
+}
@@ -2,21 +2,30 @@ use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum PerformExceptionErrorCode {
+    Cancelled,
     DocumentCacheError,
     InputValidationError,
+    InvalidConfiguration,
     JsInterpreterError,
+    Panic,
     ParametersFormatError,
     PrepareSecurityMapError,
     PrepareServicesMapError,
+    ProfileParseError,
     ReplacementStdlibError,
+    ResourceLimitExceeded,
     TakeInputError,
+    UnsupportedMapLanguage,
 }
 impl std::fmt::Display for PerformExceptionErrorCode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            PerformExceptionErrorCode::Cancelled => write!(f, "Cancelled"),
             PerformExceptionErrorCode::DocumentCacheError => write!(f, "DocumentCacheError"),
             PerformExceptionErrorCode::InputValidationError => write!(f, "InputValidationError"),
+            PerformExceptionErrorCode::InvalidConfiguration => write!(f, "InvalidConfiguration"),
             PerformExceptionErrorCode::JsInterpreterError => write!(f, "JsInterpreterError"),
+            PerformExceptionErrorCode::Panic => write!(f, "Panic"),
             PerformExceptionErrorCode::ParametersFormatError => write!(f, "ParametersFormatError"),
             PerformExceptionErrorCode::PrepareSecurityMapError => {
                 write!(f, "PrepareSecurityMapError")
@@ -24,11 +33,63 @@ impl std::fmt::Display for PerformExceptionErrorCode {
             PerformExceptionErrorCode::PrepareServicesMapError => {
                 write!(f, "PrepareServicesMapError")
             }
+            PerformExceptionErrorCode::ProfileParseError => write!(f, "ProfileParseError"),
             PerformExceptionErrorCode::ReplacementStdlibError => {
                 write!(f, "ReplacementStdlibError")
             }
+            PerformExceptionErrorCode::ResourceLimitExceeded => {
+                write!(f, "ResourceLimitExceeded")
+            }
             PerformExceptionErrorCode::TakeInputError => write!(f, "TakeInputError"),
+            PerformExceptionErrorCode::UnsupportedMapLanguage => {
+                write!(f, "UnsupportedMapLanguage")
+            }
+        }
+    }
+}
+
+/// Frame of a JavaScript stack trace.
+///
+/// Locations are 1-based and point into the original sources when the map was shipped with a source map.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsStackFrame {
+    pub function: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+impl std::fmt::Display for JsStackFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "at {} (",
+            self.function.as_deref().unwrap_or("<anonymous>")
+        )?;
+        match (&self.file, self.line, self.column) {
+            (None, _, _) => write!(f, "native")?,
+            (Some(file), None, _) => write!(f, "{}", file)?,
+            (Some(file), Some(line), None) => write!(f, "{}:{}", file, line)?,
+            (Some(file), Some(line), Some(column)) => write!(f, "{}:{}:{}", file, line, column)?,
         }
+        write!(f, ")")
+    }
+}
+
+/// Uncaught JavaScript exception which caused the perform to fail.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsException {
+    pub name: String,
+    pub message: String,
+    pub stack: Vec<JsStackFrame>,
+}
+impl std::fmt::Display for JsException {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name, self.message)?;
+        for frame in self.stack.iter() {
+            write!(f, "\n    {}", frame)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -36,6 +97,9 @@ impl std::fmt::Display for PerformExceptionErrorCode {
 pub struct PerformException {
     pub error_code: PerformExceptionErrorCode,
     pub message: String,
+    /// Present when the exception was caused by an uncaught exception in the map.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub js_exception: Option<JsException>,
 }
 impl std::fmt::Display for PerformException {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -1,4 +1,4 @@
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 
 use super::{IoStream, IoStreamHandle};
 use crate::abi::{
@@ -108,7 +108,7 @@ impl OpenOptions {
             create: self.create,
             create_new: self.create_new,
         }
-        .send_json_in(message_exchange)
+        .send_in(message_exchange)
         .unwrap();
 
         match response {
@@ -149,4 +149,17 @@ impl<Me: StaticMessageExchange, Se: StaticStreamExchange> FsConvenience<Me, Se>
 
         Ok(data)
     }
+
+    /// Like [std::fs::write].
+    pub fn write(path: &str, data: &[u8]) -> Result<(), io::Error> {
+        let mut file = OpenOptions::new()
+            .read(false)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open_in(path.as_ref(), Me::instance(), Se::instance())?;
+
+        file.write_all(data)?;
+        file.flush()
+    }
 }
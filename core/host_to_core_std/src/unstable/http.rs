@@ -1,5 +1,6 @@
 use std::io::Read;
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use url::Url;
 
@@ -24,7 +25,12 @@ crate::abi::define_exchange! {
         /// Multiple values for one key will not be joined.
         headers: &'a HeadersMultiMap,
         /// Body bytes to be sent.
-        body: Option<&'a [u8]>
+        #[serde(serialize_with = "crate::abi::bytes::option::serialize")]
+        body: Option<&'a [u8]>,
+        /// How the host should handle redirect responses.
+        redirect: HttpRedirect,
+        /// Proxy, CA and client certificate settings, if any.
+        transport: Option<&'a HttpTransport>
     } -> enum HttpCallResponse {
         Ok {
             #[serde(default)]
@@ -55,6 +61,72 @@ crate::abi::define_exchange! {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HttpRedirectMode {
+    /// Follow redirects up to `max_hops` times.
+    Follow,
+    /// Return the redirect response as-is, including its `Location` header.
+    Manual,
+    /// Fail the request when a redirect is received.
+    Error,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpRedirect {
+    pub mode: HttpRedirectMode,
+    /// Maximum number of redirects to follow in `follow` mode.
+    #[serde(default = "HttpRedirect::default_max_hops")]
+    pub max_hops: u32,
+}
+impl HttpRedirect {
+    pub const DEFAULT_MAX_HOPS: u32 = 20;
+
+    fn default_max_hops() -> u32 {
+        Self::DEFAULT_MAX_HOPS
+    }
+
+    pub fn manual() -> Self {
+        Self {
+            mode: HttpRedirectMode::Manual,
+            max_hops: 0,
+        }
+    }
+}
+impl Default for HttpRedirect {
+    fn default() -> Self {
+        Self {
+            mode: HttpRedirectMode::Follow,
+            max_hops: Self::DEFAULT_MAX_HOPS,
+        }
+    }
+}
+
+/// Transport settings for a request, resolved from provider transport and security values.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpTransport {
+    /// URL of the proxy to send the request through.
+    pub proxy_url: Option<String>,
+    /// PEM encoded CA certificates used instead of the host default trust store.
+    pub ca_pem: Option<String>,
+    /// Client certificate for mutual TLS.
+    pub client_certificate: Option<HttpClientCertificate>,
+}
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpClientCertificate {
+    /// PEM encoded certificate chain.
+    pub certificate: String,
+    /// PEM encoded private key.
+    pub key: String,
+}
+impl std::fmt::Debug for HttpClientCertificate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpClientCertificate")
+            .field("certificate", &self.certificate)
+            .field("key", &"<redacted>")
+            .finish()
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum HttpCallError {
     #[error("Invalid fetch url: {0}")]
@@ -66,6 +138,18 @@ pub enum HttpCallError {
     #[error("Unknown http error: {0}")]
     Unknown(String), // TODO: more granular
 }
+
+/// Parts of an http request besides its method and url.
+#[derive(Debug, Clone, Copy)]
+pub struct FetchOptions<'a> {
+    pub headers: &'a HeadersMultiMap,
+    /// Query parameters merged with those already in the url.
+    pub query: &'a MultiMap,
+    pub body: Option<&'a [u8]>,
+    pub redirect: HttpRedirect,
+    pub transport: Option<&'a HttpTransport>,
+}
+
 pub struct HttpRequest<
     Me: MessageExchange = MessageExchangeFfiFn,
     Se: StreamExchange = StreamExchangeFfiFn,
@@ -75,34 +159,26 @@ pub struct HttpRequest<
     stream_exchange: Se,
 }
 impl<Me: StaticMessageExchange, Se: StaticStreamExchange> HttpRequest<Me, Se> {
-    pub fn fetch(
-        method: &str,
-        url: &str,
-        headers: &HeadersMultiMap,
-        query: &MultiMap,
-        body: Option<&[u8]>,
-    ) -> Result<Self, HttpCallError> {
-        Self::fetch_in(
-            method,
-            url,
-            headers,
-            query,
-            body,
-            Me::instance(),
-            Se::instance(),
-        )
+    pub fn fetch(method: &str, url: &str, options: FetchOptions) -> Result<Self, HttpCallError> {
+        Self::fetch_in(method, url, options, Me::instance(), Se::instance())
     }
 }
 impl<Me: MessageExchange, Se: StreamExchange> HttpRequest<Me, Se> {
     fn fetch_in(
         method: &str,
         url: &str,
-        headers: &HeadersMultiMap,
-        query: &MultiMap,
-        body: Option<&[u8]>,
+        options: FetchOptions,
         message_exchange: Me,
         stream_exchange: Se,
     ) -> Result<Self, HttpCallError> {
+        let FetchOptions {
+            headers,
+            query,
+            body,
+            redirect,
+            transport,
+        } = options;
+
         let _span = tracing::trace_span!("HttpRequest::fetch").entered();
 
         let mut url = Url::parse(url).map_err(|err| HttpCallError::InvalidUrl(err.to_string()))?;
@@ -115,7 +191,7 @@ impl<Me: MessageExchange, Se: StreamExchange> HttpRequest<Me, Se> {
         );
 
         if tracing::enabled!(tracing::Level::TRACE) {
-            tracing::trace!(%method, %url, headers = ?AltDebug(&headers), ?body);
+            tracing::trace!(%method, %url, headers = ?AltDebug(&headers), ?body, ?redirect, ?transport);
         }
 
         let response = HttpCallRequest {
@@ -124,8 +200,10 @@ impl<Me: MessageExchange, Se: StreamExchange> HttpRequest<Me, Se> {
             method,
             headers,
             body,
+            redirect,
+            transport,
         }
-        .send_json_in(&message_exchange)
+        .send_in(&message_exchange)
         .unwrap();
 
         match response {
@@ -151,7 +229,7 @@ impl<Me: MessageExchange, Se: StreamExchange> HttpRequest<Me, Se> {
         let _span = tracing::trace_span!("HttpRequest::into_response").entered();
 
         let exchange_response = HttpCallHeadRequest::new(self.handle)
-            .send_json_in(&self.message_exchange)
+            .send_in(&self.message_exchange)
             .unwrap();
 
         match exchange_response {
@@ -225,12 +303,16 @@ mod test {
         HttpRequest::fetch_in(
             "GET",
             "https://example.com/?foo=1&bar=2",
-            &HashMap::new(),
-            &HashMap::from_iter([
-                ("foo".to_string(), vec!["x".to_string(), "y".to_string()]),
-                ("quz".to_string(), vec!["b".to_string(), "c".to_string()]),
-            ]),
-            None,
+            FetchOptions {
+                headers: &HashMap::new(),
+                query: &HashMap::from_iter([
+                    ("foo".to_string(), vec!["x".to_string(), "y".to_string()]),
+                    ("quz".to_string(), vec!["b".to_string(), "c".to_string()]),
+                ]),
+                body: None,
+                redirect: HttpRedirect::default(),
+                transport: None,
+            },
             TestMessageExchangeFn::new(|message| {
                 let query = message["url"].as_str().unwrap().split_once("?").unwrap().1;
                 let mut pairs = query.split("&").collect::<Vec<_>>();
@@ -251,4 +333,83 @@ mod test {
         )
         .unwrap();
     }
+
+    #[test]
+    fn test_http_fetch_redirect_serialization() {
+        HttpRequest::fetch_in(
+            "GET",
+            "https://example.com/",
+            FetchOptions {
+                headers: &HashMap::new(),
+                query: &HashMap::new(),
+                body: None,
+                redirect: HttpRedirect::manual(),
+                transport: None,
+            },
+            TestMessageExchangeFn::new(|message| {
+                assert_eq!(
+                    message["redirect"],
+                    serde_json::json!({ "mode": "manual", "max_hops": 0 })
+                );
+
+                serde_json::json!({ "kind": "ok", "handle": 1 })
+            }),
+            TestStreamExchangeFn::new(
+                |_handle, _buf| unimplemented!(),
+                |_handle, _buf| unimplemented!(),
+                |_handle| unimplemented!(),
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_http_fetch_transport_serialization() {
+        let transport = HttpTransport {
+            proxy_url: Some("http://proxy.example.com:8080".to_string()),
+            ca_pem: None,
+            client_certificate: Some(HttpClientCertificate {
+                certificate: "CERT".to_string(),
+                key: "KEY".to_string(),
+            }),
+        };
+
+        HttpRequest::fetch_in(
+            "GET",
+            "https://example.com/",
+            FetchOptions {
+                headers: &HashMap::new(),
+                query: &HashMap::new(),
+                body: None,
+                redirect: HttpRedirect::default(),
+                transport: Some(&transport),
+            },
+            TestMessageExchangeFn::new(|message| {
+                assert_eq!(
+                    message["transport"],
+                    serde_json::json!({
+                        "proxy_url": "http://proxy.example.com:8080",
+                        "ca_pem": null,
+                        "client_certificate": { "certificate": "CERT", "key": "KEY" }
+                    })
+                );
+
+                serde_json::json!({ "kind": "ok", "handle": 1 })
+            }),
+            TestStreamExchangeFn::new(
+                |_handle, _buf| unimplemented!(),
+                |_handle, _buf| unimplemented!(),
+                |_handle| unimplemented!(),
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_http_redirect_default_max_hops() {
+        let redirect: HttpRedirect =
+            serde_json::from_value(serde_json::json!({ "mode": "follow" })).unwrap();
+
+        assert_eq!(redirect, HttpRedirect::default());
+    }
 }
@@ -1,10 +1,13 @@
 //! Unstable functions provide no stability guarantees
 
+pub mod config;
+pub mod describe;
 pub mod exception;
 pub mod fs;
 pub mod http;
 pub mod perform;
 pub mod provider;
+pub mod timer;
 
 mod value;
 use serde::Deserialize;
@@ -13,6 +16,25 @@ pub use value::HostValue;
 mod stream;
 pub use stream::{IoStream, IoStreamHandle};
 
+/// Kinds of all messages sent to the host through this module, reported to the host during [handshake](crate::v1::handshake).
+pub const MESSAGE_KINDS: &[&str] = &[
+    "core-config",
+    "describe-input",
+    "describe-output-result",
+    "describe-output-exception",
+    "file-open",
+    "http-call",
+    "http-call-head",
+    "perform-input",
+    "perform-output-result",
+    "perform-output-error",
+    "perform-output-exception",
+    "perform-batch-input",
+    "perform-batch-output",
+    "setup-output-exception",
+    "sleep",
+];
+
 /// Host JS counterpart: host/javascript/src/common/app.ts
 #[derive(Debug, Serialize, Deserialize)]
 pub enum ErrorCode {
@@ -1,9 +1,20 @@
-use super::{exception::PerformException, ErrorCode, HostValue};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    exception::{PerformException, PerformExceptionErrorCode},
+    ErrorCode, HostValue,
+};
 use crate::abi::{JsonMessageError, MessageExchange};
 
+/// Identifies a perform on one core instance.
+///
+/// Assigned by the core and sent with every perform message, so that hosts can tell performs apart and cancel them by id.
+pub type PerformId = u32;
+
 crate::abi::define_exchange! {
     struct PerformInputRequest {
-        kind: "perform-input"
+        kind: "perform-input",
+        perform_id: PerformId
     } -> enum PerformInputResponse {
         Ok {
             /// Url of the profile.
@@ -19,7 +30,16 @@ crate::abi::define_exchange! {
             /// Integrations parameters.
             map_parameters: HostValue,
             /// Security values
-            map_security: HostValue
+            map_security: HostValue,
+            /// Providers to try in order when the perform with `provider_url` and `map_url` fails.
+            #[serde(default)]
+            failover: Vec<ProviderCandidate>,
+            /// When to fail over to the next provider.
+            #[serde(default)]
+            failover_policy: FailoverPolicy,
+            /// Whether to only record http requests of the map instead of sending them.
+            #[serde(default)]
+            dry_run: bool
         },
         Err {
             error_code: ErrorCode,
@@ -31,6 +51,7 @@ crate::abi::define_exchange! {
 crate::abi::define_exchange! {
     struct PerformOutputResultRequest {
         kind: "perform-output-result",
+        perform_id: PerformId,
         /// Result of the map.
         result: HostValue
     } -> enum PerformOutputResultResponse {
@@ -45,6 +66,7 @@ crate::abi::define_exchange! {
 crate::abi::define_exchange! {
     struct PerformOutputErrorRequest {
         kind: "perform-output-error",
+        perform_id: PerformId,
         /// Only errors defined in the profile are returned here.
         error: HostValue
     } -> enum PerformOutputErrorResponse {
@@ -59,6 +81,7 @@ crate::abi::define_exchange! {
 crate::abi::define_exchange! {
     struct PerformOutputExceptionRequest {
         kind: "perform-output-exception",
+        perform_id: PerformId,
         /// All other unexpected errors are returned here.
         exception: PerformException
     } -> enum PerformOutputExceptionResponse {
@@ -70,6 +93,37 @@ crate::abi::define_exchange! {
     }
 }
 
+crate::abi::define_exchange! {
+    struct PerformBatchInputRequest {
+        kind: "perform-batch-input",
+        perform_id: PerformId
+    } -> enum PerformBatchInputResponse {
+        Ok {
+            /// Performs in the batch, each in the shape of a `perform-input` response.
+            performs: Vec<PerformInput>
+        },
+        Err {
+            error_code: ErrorCode,
+            message: String,
+        }
+    }
+}
+
+crate::abi::define_exchange! {
+    struct PerformBatchOutputRequest<'a> {
+        kind: "perform-batch-output",
+        perform_id: PerformId,
+        /// Outcomes in the order of performs in the batch.
+        outcomes: &'a [PerformOutcome]
+    } -> enum PerformBatchOutputResponse {
+        Ok,
+        Err {
+            error_code: ErrorCode,
+            message: String
+        }
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum TakePerformInputError {
     #[error("Invalid input format: {0}")]
@@ -82,10 +136,12 @@ impl From<TakePerformInputError> for PerformException {
         PerformException {
             error_code: super::exception::PerformExceptionErrorCode::TakeInputError,
             message: value.to_string(),
+            js_exception: None,
         }
     }
 }
 
+#[derive(Debug, Serialize, Deserialize)]
 pub struct PerformInput {
     pub profile_url: String,
     pub provider_url: String,
@@ -94,12 +150,90 @@ pub struct PerformInput {
     pub map_input: HostValue,
     pub map_parameters: HostValue,
     pub map_security: HostValue,
+    #[serde(default)]
+    pub failover: Vec<ProviderCandidate>,
+    #[serde(default)]
+    pub failover_policy: FailoverPolicy,
+    /// Http requests of the map are recorded and returned as the result instead of being sent.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Provider and its map a use case can be performed with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderCandidate {
+    pub provider_url: String,
+    pub map_url: String,
+}
+
+/// Decides whether a failed perform is retried with the next provider candidate.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FailoverPolicy {
+    /// Fail over on any exception except cancellation.
+    pub on_exception: bool,
+    /// Fail over when the map failed with an uncaught network error (e.g. `network:ECONNREFUSED`).
+    pub on_network_error: bool,
+    /// Fail over when the map returned an error matching any of these shapes.
+    ///
+    /// An object shape matches objects which have all of its fields with matching values, other shapes must be equal.
+    pub on_error: Vec<HostValue>,
+}
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            on_exception: true,
+            on_network_error: true,
+            on_error: Vec::new(),
+        }
+    }
+}
+impl FailoverPolicy {
+    const NETWORK_ERROR_PREFIX: &'static str = "network:";
+
+    pub fn should_fail_over(
+        &self,
+        outcome: &Result<Result<HostValue, HostValue>, PerformException>,
+    ) -> bool {
+        match outcome {
+            Ok(Ok(_)) => false,
+            Ok(Err(error)) => self
+                .on_error
+                .iter()
+                .any(|shape| Self::matches_shape(shape, error)),
+            Err(exception) => match exception.error_code {
+                PerformExceptionErrorCode::Cancelled => false,
+                _ if self.on_exception => true,
+                _ => {
+                    self.on_network_error
+                        && exception
+                            .js_exception
+                            .as_ref()
+                            .is_some_and(|js| js.name.starts_with(Self::NETWORK_ERROR_PREFIX))
+                }
+            },
+        }
+    }
+
+    fn matches_shape(shape: &HostValue, value: &HostValue) -> bool {
+        match (shape, value) {
+            (HostValue::Object(shape), HostValue::Object(value)) => {
+                shape.iter().all(|(key, shape)| {
+                    value
+                        .get(key)
+                        .is_some_and(|value| Self::matches_shape(shape, value))
+                })
+            }
+            (shape, value) => shape == value,
+        }
+    }
 }
 impl PerformInput {
     pub fn take_in<E: MessageExchange>(
+        perform_id: PerformId,
         message_exchange: E,
     ) -> Result<PerformInput, TakePerformInputError> {
-        let response = match PerformInputRequest::new().send_json_in(message_exchange) {
+        let response = match PerformInputRequest::new(perform_id).send_in(message_exchange) {
             Err(err) => {
                 tracing::error!("Failed to receive perform_input response: {:#}", err);
                 return Err(TakePerformInputError::InvalidFormat(err));
@@ -116,6 +250,9 @@ impl PerformInput {
                 map_input,
                 map_parameters,
                 map_security,
+                failover,
+                failover_policy,
+                dry_run,
             } => Ok(PerformInput {
                 profile_url,
                 provider_url,
@@ -124,6 +261,9 @@ impl PerformInput {
                 map_input,
                 map_parameters,
                 map_security,
+                failover,
+                failover_policy,
+                dry_run,
             }),
             PerformInputResponse::Err {
                 error_code,
@@ -134,11 +274,58 @@ impl PerformInput {
             ))),
         }
     }
+
+    /// Takes inputs of all performs in a batch.
+    pub fn take_batch_in<E: MessageExchange>(
+        perform_id: PerformId,
+        message_exchange: E,
+    ) -> Result<Vec<PerformInput>, TakePerformInputError> {
+        let response = match PerformBatchInputRequest::new(perform_id).send_in(message_exchange) {
+            Err(err) => {
+                tracing::error!("Failed to receive perform_batch_input response: {:#}", err);
+                return Err(TakePerformInputError::InvalidFormat(err));
+            }
+            Ok(r) => r,
+        };
+
+        match response {
+            PerformBatchInputResponse::Ok { performs } => Ok(performs),
+            PerformBatchInputResponse::Err {
+                error_code,
+                message,
+            } => Err(TakePerformInputError::Unknown(format!(
+                "{:?} {}",
+                error_code, message
+            ))),
+        }
+    }
 }
 
-pub fn set_perform_output_result_in<E: MessageExchange>(result: HostValue, message_exchange: E) {
-    let response = PerformOutputResultRequest::new(result)
-        .send_json_in(message_exchange)
+/// Outcome of one perform in a batch, the same as the `perform-output-*` messages of a single perform.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum PerformOutcome {
+    Result { result: HostValue },
+    Error { error: HostValue },
+    Exception { exception: PerformException },
+}
+impl From<Result<Result<HostValue, HostValue>, PerformException>> for PerformOutcome {
+    fn from(value: Result<Result<HostValue, HostValue>, PerformException>) -> Self {
+        match value {
+            Ok(Ok(result)) => Self::Result { result },
+            Ok(Err(error)) => Self::Error { error },
+            Err(exception) => Self::Exception { exception },
+        }
+    }
+}
+
+pub fn set_perform_output_result_in<E: MessageExchange>(
+    perform_id: PerformId,
+    result: HostValue,
+    message_exchange: E,
+) {
+    let response = PerformOutputResultRequest::new(perform_id, result)
+        .send_in(message_exchange)
         .unwrap();
 
     match response {
@@ -150,9 +337,13 @@ pub fn set_perform_output_result_in<E: MessageExchange>(result: HostValue, messa
     }
 }
 
-pub fn set_perform_output_error_in<E: MessageExchange>(error: HostValue, message_exchange: E) {
-    let response = PerformOutputErrorRequest::new(error)
-        .send_json_in(message_exchange)
+pub fn set_perform_output_error_in<E: MessageExchange>(
+    perform_id: PerformId,
+    error: HostValue,
+    message_exchange: E,
+) {
+    let response = PerformOutputErrorRequest::new(perform_id, error)
+        .send_in(message_exchange)
         .unwrap();
 
     match response {
@@ -165,11 +356,12 @@ pub fn set_perform_output_error_in<E: MessageExchange>(error: HostValue, message
 }
 
 pub fn set_perform_output_exception_in<E: MessageExchange>(
+    perform_id: PerformId,
     exception: PerformException,
     message_exchange: E,
 ) {
-    let response = PerformOutputExceptionRequest::new(exception)
-        .send_json_in(message_exchange)
+    let response = PerformOutputExceptionRequest::new(perform_id, exception)
+        .send_in(message_exchange)
         .unwrap();
 
     match response {
@@ -184,6 +376,24 @@ pub fn set_perform_output_exception_in<E: MessageExchange>(
     }
 }
 
+pub fn set_perform_batch_output_in<E: MessageExchange>(
+    perform_id: PerformId,
+    outcomes: &[PerformOutcome],
+    message_exchange: E,
+) {
+    let response = PerformBatchOutputRequest::new(perform_id, outcomes)
+        .send_in(message_exchange)
+        .unwrap();
+
+    match response {
+        PerformBatchOutputResponse::Ok => (),
+        PerformBatchOutputResponse::Err {
+            error_code,
+            message,
+        } => panic!("perform-batch-output error: {:?}: {}", error_code, message),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::collections::BTreeMap;
@@ -191,18 +401,21 @@ mod test {
     use serde_json::json;
 
     use super::*;
+    use crate::unstable::exception::JsException;
 
     #[test]
     fn test_message_in_perform_input() {
         let actual = serde_json::to_value(PerformInputRequest {
             kind: PerformInputRequest::KIND,
+            perform_id: 3,
         })
         .unwrap();
 
         assert_eq!(
             serde_json::to_value(actual).unwrap(),
             json!({
-                "kind": "perform-input"
+                "kind": "perform-input",
+                "perform_id": 3
             })
         )
     }
@@ -234,6 +447,9 @@ mod test {
                 map_input,
                 map_parameters,
                 map_security,
+                failover,
+                failover_policy,
+                dry_run,
             } => {
                 assert_eq!(profile_url, "quz");
                 assert_eq!(provider_url, "baz");
@@ -255,15 +471,55 @@ mod test {
                 let mut security = BTreeMap::new();
                 security.insert("basic".to_string(), HostValue::Object(basic));
                 assert_eq!(map_security, HostValue::Object(security));
+                assert!(failover.is_empty());
+                assert!(failover_policy.on_exception);
+                assert!(!dry_run);
             }
             PerformInputResponse::Err { .. } => unreachable!(),
         }
     }
 
+    #[test]
+    fn test_failover_policy() {
+        let policy: FailoverPolicy = serde_json::from_value(json!({
+            "on_exception": false,
+            "on_error": [{ "status": 503 }]
+        }))
+        .unwrap();
+        let error = |value| Ok(Err(serde_json::from_value(value).unwrap()));
+        let exception = |error_code, name: &str| {
+            Err(PerformException {
+                error_code,
+                message: "failed".to_string(),
+                js_exception: Some(JsException {
+                    name: name.to_string(),
+                    message: "failed".to_string(),
+                    stack: Vec::new(),
+                }),
+            })
+        };
+
+        assert!(!policy.should_fail_over(&Ok(Ok(HostValue::None))));
+        assert!(policy.should_fail_over(&error(json!({ "status": 503, "title": "Unavailable" }))));
+        assert!(!policy.should_fail_over(&error(json!({ "status": 404 }))));
+        assert!(!policy.should_fail_over(&error(json!(503))));
+        assert!(policy.should_fail_over(&exception(
+            PerformExceptionErrorCode::JsInterpreterError,
+            "network:ENOTFOUND"
+        )));
+        assert!(!policy.should_fail_over(&exception(
+            PerformExceptionErrorCode::JsInterpreterError,
+            "TypeError"
+        )));
+        assert!(!FailoverPolicy::default()
+            .should_fail_over(&exception(PerformExceptionErrorCode::Cancelled, "Error")));
+    }
+
     #[test]
     fn test_message_in_perform_output_result() {
         let actual = serde_json::to_value(PerformOutputResultRequest {
             kind: PerformOutputResultRequest::KIND,
+            perform_id: 3,
             result: HostValue::String("hello".into()),
         })
         .unwrap();
@@ -272,6 +528,7 @@ mod test {
             serde_json::to_value(actual).unwrap(),
             json!({
                 "kind": "perform-output-result",
+                "perform_id": 3,
                 "result": "hello"
             })
         )
@@ -305,4 +562,29 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_message_in_perform_batch_output() {
+        let outcomes = [
+            PerformOutcome::Result {
+                result: HostValue::Bool(true),
+            },
+            PerformOutcome::Error {
+                error: HostValue::String("not found".into()),
+            },
+        ];
+        let actual = serde_json::to_value(PerformBatchOutputRequest::new(4, &outcomes)).unwrap();
+
+        assert_eq!(
+            actual,
+            json!({
+                "kind": "perform-batch-output",
+                "perform_id": 4,
+                "outcomes": [
+                    { "kind": "result", "result": true },
+                    { "kind": "error", "error": "not found" }
+                ]
+            })
+        )
+    }
 }
@@ -9,6 +9,8 @@ pub struct ProviderJson {
     pub parameters: Option<Vec<IntegrationParameter>>,
     #[serde(rename = "defaultService", default)]
     pub default_service: Option<String>,
+    #[serde(default)]
+    pub transport: Option<ProviderTransport>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -25,6 +27,26 @@ pub struct IntegrationParameter {
     pub default: Option<String>,
 }
 
+/// Transport settings applied to all requests to the provider.
+///
+/// Example:
+/// {
+///   "proxyUrl": "http://proxy.example.com:8080",
+///   "caPem": "-----BEGIN CERTIFICATE-----\n...",
+///   "clientCertificate": "client_cert"
+/// }
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProviderTransport {
+    #[serde(rename = "proxyUrl", default)]
+    pub proxy_url: Option<String>,
+    /// PEM encoded CA certificates used to verify the provider.
+    #[serde(rename = "caPem", default)]
+    pub ca_pem: Option<String>,
+    /// Id of a security value containing `certificate` and `key`.
+    #[serde(rename = "clientCertificate", default)]
+    pub client_certificate: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum ApiKeyPlacement {
@@ -216,4 +238,31 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_provider_transport() {
+        let provider_json: ProviderJson = serde_json::from_value(serde_json::json!(
+        {
+            "name": "example",
+            "services": [
+              {
+                "id": "example",
+                "baseUrl": "https://example.org"
+              }
+            ],
+            "transport": {
+              "proxyUrl": "http://proxy.example.org:8080",
+              "clientCertificate": "client_cert"
+            }
+        }))
+        .unwrap();
+
+        let transport = provider_json.transport.unwrap();
+        assert_eq!(
+            transport.proxy_url.as_deref(),
+            Some("http://proxy.example.org:8080")
+        );
+        assert_eq!(transport.ca_pem, None);
+        assert_eq!(transport.client_certificate.as_deref(), Some("client_cert"));
+    }
 }
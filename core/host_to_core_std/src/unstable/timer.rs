@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use crate::abi::{MessageExchange, StaticMessageExchange};
+
+crate::abi::define_exchange! {
+    struct SleepRequest {
+        kind: "sleep",
+        /// Duration to wait for, in milliseconds.
+        duration_ms: u64
+    } -> enum SleepResponse {
+        Ok,
+        Err {
+            error: String
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SleepError {
+    #[error("Host failed to sleep: {0}")]
+    Failed(String),
+}
+
+/// Suspends the core until the host waits for `duration`.
+///
+/// The host may suspend the whole core (asyncify) instead of blocking the thread.
+pub fn sleep_in<E: MessageExchange>(
+    duration: Duration,
+    message_exchange: E,
+) -> Result<(), SleepError> {
+    let duration_ms = u64::try_from(duration.as_millis()).unwrap_or(u64::MAX);
+    let response = SleepRequest::new(duration_ms)
+        .send_in(message_exchange)
+        .map_err(|err| SleepError::Failed(err.to_string()))?;
+
+    match response {
+        SleepResponse::Ok => Ok(()),
+        SleepResponse::Err { error } => Err(SleepError::Failed(error)),
+    }
+}
+
+pub struct TimerConvenience<Me: StaticMessageExchange>(std::marker::PhantomData<Me>);
+impl<Me: StaticMessageExchange> TimerConvenience<Me> {
+    /// Like [std::thread::sleep].
+    pub fn sleep(duration: Duration) -> Result<(), SleepError> {
+        sleep_in(duration, Me::instance())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    struct MockExchange(serde_json::Value);
+    impl MessageExchange for MockExchange {
+        fn invoke(&self, message: &[u8]) -> Vec<u8> {
+            let message: serde_json::Value = serde_json::from_slice(message).unwrap();
+            assert_eq!(message, json!({ "kind": "sleep", "duration_ms": 1500 }));
+
+            serde_json::to_vec(&self.0).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_sleep_message() {
+        sleep_in(
+            Duration::from_millis(1500),
+            MockExchange(json!({ "kind": "ok" })),
+        )
+        .unwrap();
+
+        let err = sleep_in(
+            Duration::from_millis(1500),
+            MockExchange(json!({ "kind": "err", "error": "Unknown message sleep" })),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Host failed to sleep: Unknown message sleep"
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::BTreeSet;
+
+use super::ABI_VERSION;
+use crate::abi::{JsonMessageError, MessageEncoding, MessageExchange};
+
+crate::abi::define_exchange! {
+    struct HandshakeRequest<'a> {
+        kind: "handshake",
+        /// Version of the core.
+        core_version: &'a str,
+        /// Version of the ABI implemented by the core.
+        abi_version: u32,
+        /// Kinds of messages the core may send.
+        message_kinds: &'a [&'a str],
+        /// Encodings the core supports for all following messages, in order of preference.
+        encodings: &'a [MessageEncoding]
+    } -> enum HandshakeResponse {
+        Ok {
+            /// Version of the ABI implemented by the host.
+            abi_version: u32,
+            /// Version of the host, for diagnostics.
+            #[serde(default)]
+            host_version: Option<String>,
+            /// Kinds of messages the host handles.
+            message_kinds: BTreeSet<String>,
+            /// Encoding chosen by the host from the ones the core supports.
+            #[serde(default)]
+            encoding: MessageEncoding
+        },
+        Err {
+            message: String
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HandshakeError {
+    #[error("Host does not support the handshake message, it predates ABI versioning: {0}")]
+    NotSupported(JsonMessageError),
+    #[error("Host implements ABI version {host}, core implements ABI version {core}")]
+    IncompatibleVersion { host: u32, core: u32 },
+    #[error("Host rejected the core: {0}")]
+    Rejected(String),
+    #[error("Host chose encoding {0:?} which the core does not support")]
+    UnsupportedEncoding(MessageEncoding),
+}
+
+/// What the host reported about itself during handshake.
+#[derive(Debug)]
+pub struct HostCapabilities {
+    pub abi_version: u32,
+    pub host_version: Option<String>,
+    pub message_kinds: BTreeSet<String>,
+    /// Encoding of all following messages.
+    pub encoding: MessageEncoding,
+}
+impl HostCapabilities {
+    /// Returns kinds from `message_kinds` which the host doesn't handle.
+    pub fn unsupported<'a>(&self, message_kinds: &[&'a str]) -> Vec<&'a str> {
+        message_kinds
+            .iter()
+            .filter(|kind| !self.message_kinds.contains(**kind))
+            .copied()
+            .collect()
+    }
+}
+
+/// Exchanges versions and supported messages with the host.
+///
+/// The handshake itself is always sent as JSON, the host chooses the encoding of all following messages from `encodings`.
+/// Fails if the host implements a different ABI version or rejects the core.
+pub fn handshake_in<E: MessageExchange>(
+    core_version: &str,
+    message_kinds: &[&str],
+    encodings: &[MessageEncoding],
+    message_exchange: E,
+) -> Result<HostCapabilities, HandshakeError> {
+    let response = HandshakeRequest::new(core_version, ABI_VERSION, message_kinds, encodings)
+        .send_json_in(message_exchange)
+        .map_err(HandshakeError::NotSupported)?;
+
+    match response {
+        HandshakeResponse::Ok {
+            abi_version,
+            host_version,
+            message_kinds,
+            encoding,
+        } => {
+            if abi_version != ABI_VERSION {
+                return Err(HandshakeError::IncompatibleVersion {
+                    host: abi_version,
+                    core: ABI_VERSION,
+                });
+            }
+            if !encodings.contains(&encoding) {
+                return Err(HandshakeError::UnsupportedEncoding(encoding));
+            }
+
+            Ok(HostCapabilities {
+                abi_version,
+                host_version,
+                message_kinds,
+                encoding,
+            })
+        }
+        HandshakeResponse::Err { message } => Err(HandshakeError::Rejected(message)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::abi::testing::TestMessageExchangeFn;
+
+    #[test]
+    fn test_handshake() {
+        let exchange = TestMessageExchangeFn::new(|message| {
+            assert_eq!(
+                message,
+                json!({
+                    "kind": "handshake",
+                    "core_version": "1.2.3",
+                    "abi_version": 1,
+                    "message_kinds": ["sleep", "file-open"],
+                    "encodings": ["msgpack", "json"]
+                })
+            );
+
+            json!({ "kind": "ok", "abi_version": 1, "message_kinds": ["sleep"] })
+        });
+
+        let capabilities = handshake_in(
+            "1.2.3",
+            &["sleep", "file-open"],
+            MessageEncoding::ALL,
+            &exchange,
+        )
+        .unwrap();
+        assert_eq!(capabilities.host_version, None);
+        assert_eq!(capabilities.encoding, MessageEncoding::Json);
+        assert_eq!(
+            capabilities.unsupported(&["sleep", "file-open"]),
+            vec!["file-open"]
+        );
+    }
+
+    #[test]
+    fn test_handshake_incompatible_version() {
+        let exchange = TestMessageExchangeFn::new(
+            |_| json!({ "kind": "ok", "abi_version": 2, "message_kinds": [] }),
+        );
+
+        assert!(matches!(
+            handshake_in("1.2.3", &[], MessageEncoding::ALL, &exchange),
+            Err(HandshakeError::IncompatibleVersion { host: 2, core: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_handshake_unsupported_encoding() {
+        let exchange = TestMessageExchangeFn::new(
+            |_| json!({ "kind": "ok", "abi_version": 1, "message_kinds": [], "encoding": "msgpack" }),
+        );
+
+        assert!(matches!(
+            handshake_in("1.2.3", &[], &[MessageEncoding::Json], &exchange),
+            Err(HandshakeError::UnsupportedEncoding(
+                MessageEncoding::MessagePack
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_handshake_legacy_host() {
+        // hosts before versioning respond to unknown messages with a different shape
+        let exchange = TestMessageExchangeFn::new(
+            |_| json!({ "kind": "err", "error": "Unknown message handshake" }),
+        );
+
+        assert!(matches!(
+            handshake_in("1.2.3", &[], MessageEncoding::ALL, &exchange),
+            Err(HandshakeError::NotSupported(_))
+        ));
+    }
+}
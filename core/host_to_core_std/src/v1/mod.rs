@@ -0,0 +1,9 @@
+//! Stable functions, changed only together with [ABI_VERSION].
+//!
+//! Messages are promoted here from [unstable](crate::unstable) once they stabilise, so that hosts and the core
+//! can be released independently and detect incompatibility during [handshake] instead of failing on the first unknown message.
+
+pub mod handshake;
+
+/// Version of the host <-> core ABI implemented by this crate.
+pub const ABI_VERSION: u32 = 1;
@@ -76,6 +76,8 @@ macro_rules! ensure_arguments {
 
     (#arg_type(bool) $val: expr) => { $val.as_bool().ok() };
     (#arg_type(i32) $val: expr) => { $val.try_as_integer().ok() };
+    // integers are represented differently from floats
+    (#arg_type(f64) $val: expr) => { $val.as_f64().or_else(|_| $val.try_as_integer().map(f64::from)).ok() };
     (#arg_type(str) $val: expr) => { $val.as_str().ok() };
     (#arg_type(mut_bytes) $val: expr) => { $val.as_bytes_mut().ok() };
     (#arg_type(bytes) $val: expr) => { $val.as_bytes().ok() };
@@ -83,6 +85,7 @@ macro_rules! ensure_arguments {
 }
 
 pub mod unstable;
+mod web;
 
 /// Returns the end of the `keys` chain by recursively traversing into objects, creating properties along the way if they don't exist.
 fn traverse_object<'ctx>(
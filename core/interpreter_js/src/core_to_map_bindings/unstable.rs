@@ -1,13 +1,13 @@
-use std::{cell::RefCell, ops::DerefMut, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, ops::DerefMut, rc::Rc};
 
 use anyhow::Context as AnyhowContext;
 use base64::Engine;
 use quickjs_wasm_rs::{JSContextRef, JSError, JSValue, JSValueRef};
 
-use map_std::unstable::MapStdUnstable;
+use map_std::unstable::{date, LogLevel, MapStdUnstable, MapValue};
 use sf_std::MultiMap;
 
-use super::JSValueDebug;
+use super::{web, JSValueDebug};
 
 pub const MODULE_NAME: &[&str] = &["__ffi", "unstable"];
 
@@ -25,12 +25,28 @@ pub fn link<H: MapStdUnstable + 'static>(
             // debug
             "printDebug": __export_print_debug,
             "print": __export_print,
+            "log": __export_log,
             // coding
             "bytes_to_utf8": __export_bytes_to_utf8,
             "utf8_to_bytes": __export_utf8_to_bytes,
             "bytes_to_base64": __export_bytes_to_base64,
             "base64_to_bytes": __export_base64_to_bytes,
             "record_to_urlencoded": __export_record_to_urlencoded,
+            // web apis
+            "utf8_decode": __export_utf8_decode,
+            "btoa": __export_btoa,
+            "atob": __export_atob,
+            "url_parse": __export_url_parse,
+            "url_set": __export_url_set,
+            "urlencoded_to_entries": __export_urlencoded_to_entries,
+            "entries_to_urlencoded": __export_entries_to_urlencoded,
+            // dates
+            "date_parse": __export_date_parse,
+            "date_format": __export_date_format,
+            // crypto
+            "crypto_digest": __export_crypto_digest,
+            "crypto_hmac": __export_crypto_hmac,
+            "crypto_random_fill": __export_crypto_random_fill,
             // messages
             "message_exchange": __export_message_exchange,
             // streams
@@ -93,6 +109,74 @@ fn __export_stream_close<'ctx, H: MapStdUnstable + 'static>(
     }
 }
 
+fn __export_date_parse<'ctx, H: MapStdUnstable + 'static>(
+    _state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    let (input, format) = ensure_arguments!("date_parse" args; 0: str, 1: str);
+
+    let format = date::DateFormat::parse(format).map_err(|err| JSError::Type(err.to_string()))?;
+    match date::parse_date(input, format) {
+        Ok(timestamp) => Ok(JSValue::Float(timestamp)),
+        Err(err) => Err(JSError::Range(err.to_string())),
+    }
+}
+
+fn __export_date_format<'ctx, H: MapStdUnstable + 'static>(
+    _state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    let (timestamp, format, offset_minutes) =
+        ensure_arguments!("date_format" args; 0: f64, 1: str, 2: i32);
+
+    let format = date::DateFormat::parse(format).map_err(|err| JSError::Type(err.to_string()))?;
+    match date::format_date(timestamp, format, offset_minutes) {
+        Ok(formatted) => Ok(formatted.into()),
+        Err(err) => Err(JSError::Range(err.to_string())),
+    }
+}
+
+fn __export_crypto_digest<'ctx, H: MapStdUnstable + 'static>(
+    state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    let (algorithm, data) = ensure_arguments!("crypto_digest" args; 0: str, 1: bytes);
+
+    match state.crypto_digest(algorithm, data) {
+        Ok(digest) => Ok(JSValue::ArrayBuffer(digest)),
+        Err(err) => Err(JSError::Type(format!("crypto_digest: {}", err))),
+    }
+}
+
+fn __export_crypto_hmac<'ctx, H: MapStdUnstable + 'static>(
+    state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    let (algorithm, key, data) = ensure_arguments!("crypto_hmac" args; 0: str, 1: bytes, 2: bytes);
+
+    match state.crypto_hmac(algorithm, key, data) {
+        Ok(signature) => Ok(JSValue::ArrayBuffer(signature)),
+        Err(err) => Err(JSError::Type(format!("crypto_hmac: {}", err))),
+    }
+}
+
+fn __export_crypto_random_fill<'ctx, H: MapStdUnstable + 'static>(
+    state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    let buf = ensure_arguments!("crypto_random_fill" args; 0: mut_bytes);
+
+    match state.crypto_random_fill(buf) {
+        Ok(()) => Ok(JSValue::Undefined),
+        Err(err) => Err(JSError::Type(format!("crypto_random_fill: {}", err))),
+    }
+}
+
 fn __export_print<'ctx, H: MapStdUnstable + 'static>(
     state: &mut H,
     _this: &JSValueRef<'ctx>,
@@ -104,6 +188,28 @@ fn __export_print<'ctx, H: MapStdUnstable + 'static>(
     Ok(JSValue::Undefined)
 }
 
+fn __export_log<'ctx, H: MapStdUnstable + 'static>(
+    state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    let (level, message, fields) = ensure_arguments!("log" args; 0: str, 1: str, 2: str);
+
+    let level = LogLevel::parse(level)
+        .ok_or_else(|| JSError::Type(format!("log: unknown level \"{}\"", level)))?;
+    let fields = match serde_json::from_str::<MapValue>(fields) {
+        Ok(MapValue::Object(fields)) => fields,
+        _ => {
+            return Err(JSError::Type(
+                "log: argument 2 must be a JSON object".to_string(),
+            ))
+        }
+    };
+    state.log(level, message, &fields);
+
+    Ok(JSValue::Undefined)
+}
+
 fn __export_print_debug<'ctx, H: MapStdUnstable + 'static>(
     _state: &mut H,
     _this: &JSValueRef<'ctx>,
@@ -215,3 +321,127 @@ fn __export_record_to_urlencoded<'ctx, H: MapStdUnstable + 'static>(
 
     Ok(result.into())
 }
+
+fn __export_utf8_decode<'ctx, H: MapStdUnstable + 'static>(
+    _state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    let (bytes, fatal, ignore_bom) =
+        ensure_arguments!("utf8_decode" args; 0: bytes, 1: bool, 2: bool);
+
+    match web::utf8_decode(bytes, fatal, ignore_bom) {
+        Err(err) => Err(JSError::Type(err)),
+        Ok(s) => Ok(s.into()),
+    }
+}
+
+fn __export_btoa<'ctx, H: MapStdUnstable + 'static>(
+    _state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    let data = ensure_arguments!("btoa" args; 0: str);
+
+    web::btoa(data).map(JSValue::from).map_err(JSError::Type)
+}
+
+fn __export_atob<'ctx, H: MapStdUnstable + 'static>(
+    _state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    let data = ensure_arguments!("atob" args; 0: str);
+
+    web::atob(data).map(JSValue::from).map_err(JSError::Type)
+}
+
+fn url_to_components(url: &url::Url) -> JSValue {
+    let components: HashMap<String, JSValue> = web::url_components(url)
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.into()))
+        .collect();
+
+    components.into()
+}
+
+fn __export_url_parse<'ctx, H: MapStdUnstable + 'static>(
+    _state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    let input = ensure_arguments!("url_parse" args; 0: str);
+    let base = match args.get(1) {
+        Some(base) if !base.is_null_or_undefined() => Some(
+            base.as_str()
+                .map_err(|_| JSError::Type("url_parse: argument 1 must be str".to_string()))?,
+        ),
+        _ => None,
+    };
+
+    match web::url_parse(input, base) {
+        Err(err) => Err(JSError::Type(err)),
+        Ok(url) => Ok(url_to_components(&url)),
+    }
+}
+
+fn __export_url_set<'ctx, H: MapStdUnstable + 'static>(
+    _state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    let (href, component, value) = ensure_arguments!("url_set" args; 0: str, 1: str, 2: str);
+
+    let mut url = web::url_parse(href, None).map_err(JSError::Type)?;
+    web::url_set(&mut url, component, value).map_err(JSError::Type)?;
+
+    Ok(url_to_components(&url))
+}
+
+fn __export_urlencoded_to_entries<'ctx, H: MapStdUnstable + 'static>(
+    _state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    let query = ensure_arguments!("urlencoded_to_entries" args; 0: str);
+
+    let entries = web::urlencoded_to_entries(query)
+        .into_iter()
+        .map(|(name, value)| JSValue::Array(vec![name.into(), value.into()]))
+        .collect::<Vec<_>>();
+
+    Ok(entries.into())
+}
+
+fn __export_entries_to_urlencoded<'ctx, H: MapStdUnstable + 'static>(
+    _state: &mut H,
+    _this: &JSValueRef<'ctx>,
+    args: &[JSValueRef<'ctx>],
+) -> Result<JSValue, JSError> {
+    const INVALID: &str = "entries_to_urlencoded: argument 0 must be an array of string pairs";
+    let entries = ensure_arguments!("entries_to_urlencoded" args; 0: value);
+    if !entries.is_array() {
+        return Err(JSError::Type(INVALID.to_string()));
+    }
+
+    let length = entries
+        .get_property("length")
+        .and_then(|length| length.try_as_integer())
+        .map_err(|_| JSError::Type(INVALID.to_string()))? as u32;
+    let mut pairs = Vec::with_capacity(length as usize);
+    for i in 0..length {
+        let pair = entries
+            .get_indexed_property(i)
+            .map_err(|_| JSError::Type(INVALID.to_string()))?;
+        let (name, value) = match (pair.get_indexed_property(0), pair.get_indexed_property(1)) {
+            (Ok(name), Ok(value)) if name.is_str() && value.is_str() => (name, value),
+            _ => return Err(JSError::Type(INVALID.to_string())),
+        };
+        pairs.push((
+            name.as_str().unwrap().to_string(),
+            value.as_str().unwrap().to_string(),
+        ));
+    }
+
+    Ok(web::entries_to_urlencoded(pairs.iter().map(|(k, v)| (k.as_str(), v.as_str()))).into())
+}
@@ -0,0 +1,214 @@
+//! Rust implementations backing web API globals (`TextDecoder`, `atob`/`btoa`, `URL` and `URLSearchParams`) defined by the map stdlib.
+
+use base64::{
+    alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+use url::{form_urlencoded, quirks, Url};
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// Decodes UTF-8 as specified by the `TextDecoder` decode algorithm.
+pub fn utf8_decode(bytes: &[u8], fatal: bool, ignore_bom: bool) -> Result<String, String> {
+    let bytes = match ignore_bom {
+        true => bytes,
+        false => bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes),
+    };
+
+    if fatal {
+        std::str::from_utf8(bytes)
+            .map(|s| s.to_string())
+            .map_err(|err| format!("The encoded data was not valid UTF-8: {}", err))
+    } else {
+        Ok(String::from_utf8_lossy(bytes).into_owned())
+    }
+}
+
+/// Encodes a Latin1 string as base64, see <https://html.spec.whatwg.org/multipage/webappapis.html#dom-btoa>.
+pub fn btoa(data: &str) -> Result<String, String> {
+    let bytes = data
+        .chars()
+        .map(|c| u8::try_from(c as u32))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| {
+            "The string to be encoded contains characters outside of the Latin1 range".to_string()
+        })?;
+
+    Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+}
+
+/// Decodes base64 into a Latin1 string using the [forgiving-base64 decode](https://infra.spec.whatwg.org/#forgiving-base64-decode) algorithm.
+pub fn atob(data: &str) -> Result<String, String> {
+    const INVALID: &str = "The string to be decoded is not correctly encoded";
+    const FORGIVING: GeneralPurpose = GeneralPurpose::new(
+        &alphabet::STANDARD,
+        GeneralPurposeConfig::new()
+            .with_decode_padding_mode(DecodePaddingMode::RequireNone)
+            .with_decode_allow_trailing_bits(true),
+    );
+
+    let mut data: String = data
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\x0C' | '\r' | ' '))
+        .collect();
+    if data.len() % 4 == 0 {
+        for _ in 0..2 {
+            if data.ends_with('=') {
+                data.pop();
+            }
+        }
+    }
+    if data.len() % 4 == 1 {
+        return Err(INVALID.to_string());
+    }
+
+    let bytes = FORGIVING.decode(data).map_err(|_| INVALID.to_string())?;
+
+    Ok(bytes.into_iter().map(char::from).collect())
+}
+
+/// Parses `input` relative to `base` as specified by the `URL` constructor.
+pub fn url_parse(input: &str, base: Option<&str>) -> Result<Url, String> {
+    let base = base
+        .map(|base| {
+            Url::parse(base).map_err(|err| format!("Invalid base URL \"{}\": {}", base, err))
+        })
+        .transpose()?;
+
+    Url::options()
+        .base_url(base.as_ref())
+        .parse(input)
+        .map_err(|err| format!("Invalid URL \"{}\": {}", input, err))
+}
+
+/// Returns the components of `url` as exposed by `URL` getters.
+pub fn url_components(url: &Url) -> [(&'static str, String); 11] {
+    [
+        ("href", quirks::href(url).to_string()),
+        ("origin", quirks::origin(url)),
+        ("protocol", quirks::protocol(url).to_string()),
+        ("username", quirks::username(url).to_string()),
+        ("password", quirks::password(url).to_string()),
+        ("host", quirks::host(url).to_string()),
+        ("hostname", quirks::hostname(url).to_string()),
+        ("port", quirks::port(url).to_string()),
+        ("pathname", quirks::pathname(url).to_string()),
+        ("search", quirks::search(url).to_string()),
+        ("hash", quirks::hash(url).to_string()),
+    ]
+}
+
+/// Sets `component` of `url` as specified by `URL` setters.
+///
+/// Invalid values are ignored as in browsers, except for `href` which fails.
+pub fn url_set(url: &mut Url, component: &str, value: &str) -> Result<(), String> {
+    match component {
+        "href" => quirks::set_href(url, value)
+            .map_err(|err| format!("Invalid URL \"{}\": {}", value, err))?,
+        "protocol" => {
+            let _ = quirks::set_protocol(url, value);
+        }
+        "username" => {
+            let _ = quirks::set_username(url, value);
+        }
+        "password" => {
+            let _ = quirks::set_password(url, value);
+        }
+        "host" => {
+            let _ = quirks::set_host(url, value);
+        }
+        "hostname" => {
+            let _ = quirks::set_hostname(url, value);
+        }
+        "port" => {
+            let _ = quirks::set_port(url, value);
+        }
+        "pathname" => quirks::set_pathname(url, value),
+        "search" => quirks::set_search(url, value),
+        "hash" => quirks::set_hash(url, value),
+        _ => return Err(format!("Unknown URL component \"{}\"", component)),
+    }
+
+    Ok(())
+}
+
+/// Parses `application/x-www-form-urlencoded` data into name-value pairs.
+pub fn urlencoded_to_entries(query: &str) -> Vec<(String, String)> {
+    form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect()
+}
+
+/// Serializes name-value pairs as `application/x-www-form-urlencoded` data.
+pub fn entries_to_urlencoded<'a>(entries: impl IntoIterator<Item = (&'a str, &'a str)>) -> String {
+    form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(entries)
+        .finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_utf8_decode() {
+        assert_eq!(
+            utf8_decode(b"\xEF\xBB\xBFabc", false, false).unwrap(),
+            "abc"
+        );
+        assert_eq!(
+            utf8_decode(b"\xEF\xBB\xBFabc", false, true).unwrap(),
+            "\u{FEFF}abc"
+        );
+        assert_eq!(utf8_decode(b"a\xFFb", false, false).unwrap(), "a\u{FFFD}b");
+        assert!(utf8_decode(b"a\xFFb", true, false).is_err());
+    }
+
+    #[test]
+    fn test_btoa_atob() {
+        assert_eq!(btoa("hello\u{FF}").unwrap(), "aGVsbG//");
+        assert!(btoa("\u{100}").is_err());
+
+        assert_eq!(atob("aGVsbG//").unwrap(), "hello\u{FF}");
+        assert_eq!(atob(" aGVs bG8 ").unwrap(), "hello");
+        assert_eq!(atob("aGVsbG8=").unwrap(), "hello");
+        assert!(atob("aGVsb").is_err());
+        assert!(atob("aGVsbG8*").is_err());
+    }
+
+    #[test]
+    fn test_url() {
+        let mut url = url_parse("../b?c=d#e", Some("https://user@example.com:8443/a/x")).unwrap();
+        let components = url_components(&url);
+        assert_eq!(components[0].1, "https://user@example.com:8443/b?c=d#e");
+        assert_eq!(components[1].1, "https://example.com:8443");
+        assert_eq!(components[7].1, "8443");
+        assert_eq!(components[9].1, "?c=d");
+
+        url_set(&mut url, "port", "443").unwrap();
+        url_set(&mut url, "port", "not a port").unwrap();
+        url_set(&mut url, "search", "q=a b").unwrap();
+        assert_eq!(url.as_str(), "https://user@example.com/b?q=a%20b#e");
+
+        assert!(url_parse("not a url", None).is_err());
+        assert!(url_set(&mut url, "href", "not a url").is_err());
+    }
+
+    #[test]
+    fn test_urlencoded() {
+        let entries = urlencoded_to_entries("a=1&b=x+y&a=%C3%A9");
+        assert_eq!(
+            entries,
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "x y".to_string()),
+                ("a".to_string(), "é".to_string())
+            ]
+        );
+        assert_eq!(
+            entries_to_urlencoded(entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))),
+            "a=1&b=x+y&a=%C3%A9"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Reading of uncaught exceptions thrown out of QuickJS.
+
+use std::ffi::CStr;
+
+use quickjs_wasm_sys::{JSContext, JSValue};
+use sf_std::unstable::exception::{JsException, JsStackFrame};
+
+const FRAME_PREFIX: &str = "at ";
+/// Message of the `InternalError` QuickJS throws when an allocation fails.
+const OUT_OF_MEMORY_MESSAGE: &str = "out of memory";
+
+/// Exception pending in a context.
+pub enum PendingException {
+    /// QuickJS ran out of memory.
+    OutOfMemory,
+    Thrown(JsException),
+}
+
+/// Takes the pending exception of `context` and reads its `name`, `message` and `stack` properties.
+///
+/// Thrown values which are not errors are reported as `Error` with the value converted to a string as message.
+///
+/// # Safety
+/// `context` must be valid and QuickJS must have just returned the exception marker for it.
+pub unsafe fn take_pending(context: *mut JSContext) -> PendingException {
+    // SAFETY: the exception value is owned by us after it is taken
+    unsafe {
+        let value = quickjs_wasm_sys::JS_GetException(context);
+        let exception = if is_out_of_memory(context, value) {
+            PendingException::OutOfMemory
+        } else if quickjs_wasm_sys::JS_IsError(context, value) != 0 {
+            PendingException::Thrown(JsException {
+                name: property_string(context, value, c"name")
+                    .unwrap_or_else(|| "Error".to_string()),
+                message: property_string(context, value, c"message").unwrap_or_default(),
+                stack: property_string(context, value, c"stack")
+                    .map(|stack| parse_stack(&stack))
+                    .unwrap_or_default(),
+            })
+        } else {
+            PendingException::Thrown(JsException {
+                name: "Error".to_string(),
+                message: to_string(context, value).unwrap_or_default(),
+                stack: Vec::new(),
+            })
+        };
+        quickjs_wasm_sys::JS_FreeValue(context, value);
+
+        exception
+    }
+}
+
+/// Returns whether `value` is the `InternalError` QuickJS throws when it runs out of memory.
+unsafe fn is_out_of_memory(context: *mut JSContext, value: JSValue) -> bool {
+    // SAFETY: the global object and constructor are freed before returning
+    unsafe {
+        let global = quickjs_wasm_sys::JS_GetGlobalObject(context);
+        let constructor =
+            quickjs_wasm_sys::JS_GetPropertyStr(context, global, c"InternalError".as_ptr());
+        quickjs_wasm_sys::JS_FreeValue(context, global);
+
+        // `instanceof` throws if the map replaced the constructor with a non-function
+        let is_internal_error = quickjs_wasm_sys::JS_IsFunction(context, constructor) != 0
+            && quickjs_wasm_sys::JS_IsInstanceOf(context, value, constructor) > 0;
+        quickjs_wasm_sys::JS_FreeValue(context, constructor);
+
+        is_internal_error
+            && property_string(context, value, c"message").as_deref() == Some(OUT_OF_MEMORY_MESSAGE)
+    }
+}
+
+/// Returns the property `name` of `object` if it is a string.
+unsafe fn property_string(context: *mut JSContext, object: JSValue, name: &CStr) -> Option<String> {
+    // SAFETY: the property value is freed before returning
+    unsafe {
+        let value = quickjs_wasm_sys::JS_GetPropertyStr(context, object, name.as_ptr());
+        let string = if quickjs_wasm_sys::JS_VALUE_GET_TAG(value) == quickjs_wasm_sys::JS_TAG_STRING
+        {
+            to_string(context, value)
+        } else {
+            None
+        };
+        quickjs_wasm_sys::JS_FreeValue(context, value);
+
+        string
+    }
+}
+
+/// Converts `value` to a string like `String(value)`, returns `None` if the conversion throws.
+unsafe fn to_string(context: *mut JSContext, value: JSValue) -> Option<String> {
+    // SAFETY: the returned string is valid for `len` bytes until freed
+    unsafe {
+        let mut len = 0;
+        let string = quickjs_wasm_sys::JS_ToCStringLen2(context, &mut len, value, 0);
+        if string.is_null() {
+            // a throwing `toString`, the new exception must not be reported in place of the original one
+            quickjs_wasm_sys::JS_FreeValue(context, quickjs_wasm_sys::JS_GetException(context));
+            return None;
+        }
+
+        let result = String::from_utf8_lossy(std::slice::from_raw_parts(string as *const u8, len))
+            .into_owned();
+        quickjs_wasm_sys::JS_FreeCString(context, string);
+
+        Some(result)
+    }
+}
+
+/// Parses the `stack` property of an error, which has one frame `    at <function> (<file>:<line>[:<column>])` per line.
+fn parse_stack(stack: &str) -> Vec<JsStackFrame> {
+    stack
+        .lines()
+        .filter_map(|line| line.trim_start().strip_prefix(FRAME_PREFIX))
+        .map(parse_frame)
+        .collect()
+}
+
+fn parse_frame(frame: &str) -> JsStackFrame {
+    let (function, location) = match frame.strip_suffix(')').and_then(|f| f.split_once(" (")) {
+        Some((function, location)) => (Some(function.to_string()), location),
+        None => (None, frame),
+    };
+
+    if location == "native" {
+        return JsStackFrame {
+            function,
+            file: None,
+            line: None,
+            column: None,
+        };
+    }
+
+    // file names may contain colons, so parse numbers from the end
+    let (file, line, column) = match split_number(location) {
+        None => (location, None, None),
+        Some((rest, last)) => match split_number(rest) {
+            Some((file, line)) => (file, Some(line), Some(last)),
+            None => (rest, Some(last), None),
+        },
+    };
+
+    JsStackFrame {
+        function,
+        file: Some(file.to_string()),
+        line,
+        column,
+    }
+}
+
+/// Splits `<rest>:<number>` into its parts.
+fn split_number(value: &str) -> Option<(&str, u32)> {
+    value
+        .rsplit_once(':')
+        .and_then(|(rest, number)| number.parse::<u32>().ok().map(|number| (rest, number)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_stack() {
+        let stack = parse_stack(
+            "    at Example (map.js:12)\n    at <eval> (map.js:20:5)\n    at forEach (native)\n",
+        );
+
+        assert_eq!(
+            stack,
+            vec![
+                JsStackFrame {
+                    function: Some("Example".to_string()),
+                    file: Some("map.js".to_string()),
+                    line: Some(12),
+                    column: None,
+                },
+                JsStackFrame {
+                    function: Some("<eval>".to_string()),
+                    file: Some("map.js".to_string()),
+                    line: Some(20),
+                    column: Some(5),
+                },
+                JsStackFrame {
+                    function: Some("forEach".to_string()),
+                    file: None,
+                    line: None,
+                    column: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_stack_empty() {
+        assert!(parse_stack("").is_empty());
+    }
+}
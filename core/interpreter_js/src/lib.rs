@@ -1,13 +1,27 @@
-use std::{cell::RefCell, ops::DerefMut, rc::Rc};
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    ops::DerefMut,
+    rc::Rc,
+};
 
 use anyhow::Context as AnyhowContext;
 use quickjs_wasm_rs::JSContextRef;
-use sf_std::unstable::exception::{PerformException, PerformExceptionErrorCode};
+use quickjs_wasm_sys::JSValue;
+use sf_std::unstable::exception::{JsException, PerformException, PerformExceptionErrorCode};
 use thiserror::Error;
 
-use map_std::MapStdFull;
+use map_std::{interpreter::Interpreter, MapStdFull};
 
 mod core_to_map_bindings;
+mod exception;
+mod module_loader;
+mod source_map;
+
+use exception::PendingException;
+use module_loader::ModuleLoader;
+
+pub use source_map::{SourceMap, SourceMapError};
 
 #[derive(Debug, Error)]
 pub enum JsInterpreterError {
@@ -16,26 +30,86 @@ pub enum JsInterpreterError {
     InitializationFailed(anyhow::Error),
     #[error("Code evaluation failed: {0:#}")]
     EvalFailed(anyhow::Error),
+    #[error("Uncaught {0}")]
+    UncaughtException(JsException),
     #[error("Code compilation failed: {0:#}")]
     CompilationFailed(anyhow::Error),
     #[error("Eval code cannot be an empty string")]
     EvalCodeEmpty,
+    #[error("Execution budget of {0} interrupt checks exhausted")]
+    ExecutionBudgetExceeded(u64),
+    #[error("Heap limit of {0} bytes exceeded")]
+    HeapLimitExceeded(usize),
 }
 impl From<JsInterpreterError> for PerformException {
     fn from(value: JsInterpreterError) -> Self {
+        let js_exception = match value {
+            JsInterpreterError::UncaughtException(ref exception) => Some(exception.clone()),
+            _ => None,
+        };
+        let error_code = match value {
+            JsInterpreterError::ExecutionBudgetExceeded(_)
+            | JsInterpreterError::HeapLimitExceeded(_) => {
+                PerformExceptionErrorCode::ResourceLimitExceeded
+            }
+            _ => PerformExceptionErrorCode::JsInterpreterError,
+        };
+
         PerformException {
-            error_code: PerformExceptionErrorCode::JsInterpreterError,
+            error_code,
             message: value.to_string(),
+            js_exception,
         }
     }
 }
 
+/// Resource limits enforced on code run by the interpreter.
+#[derive(Debug, Clone, Default)]
+pub struct JsInterpreterLimits {
+    /// Number of QuickJS interrupt checks (roughly one per 10000 calls or loop iterations) one evaluation may take.
+    pub execution_budget: Option<u64>,
+    /// Maximum size of the QuickJS heap in bytes.
+    pub max_heap_size: Option<usize>,
+}
+
+/// Handler polled by QuickJS during execution. Returning `true` interrupts the currently running code.
+type InterruptHandler = Box<dyn FnMut() -> bool>;
+
+#[derive(Default)]
+struct InterruptState {
+    handler: Option<InterruptHandler>,
+    execution_budget: Option<u64>,
+    /// Interrupt checks left in the current evaluation.
+    remaining: u64,
+    budget_exhausted: bool,
+}
+impl InterruptState {
+    fn reset_budget(&mut self) {
+        self.remaining = self.execution_budget.unwrap_or(0);
+        self.budget_exhausted = false;
+    }
+}
+
 pub struct JsInterpreter<S: MapStdFull + 'static> {
     context: JSContextRef,
+    /// Must be declared after `context` so that it is dropped after the runtime which points to it.
+    interrupt_state: Box<InterruptState>,
+    /// Must be declared after `context` for the same reason as `interrupt_state`.
+    module_loader: Box<ModuleLoader>,
+    limits: JsInterpreterLimits,
+    /// Map evaluated by the last [JsInterpreter::run].
+    loaded_map: Option<LoadedMap>,
     #[allow(dead_code)]
     state: Rc<RefCell<S>>,
 }
+struct LoadedMap {
+    name: String,
+    code: String,
+    is_module: bool,
+}
 impl<S: MapStdFull + 'static> JsInterpreter<S> {
+    const START_FUNCTION: &'static CStr = c"_start";
+
     pub fn new(state: S) -> Result<Self, JsInterpreterError> {
         let mut context = JSContextRef::default();
         let state = Rc::new(RefCell::new(state));
@@ -45,23 +119,156 @@ impl<S: MapStdFull + 'static> JsInterpreter<S> {
             .context("Failed to export sf_unstable")
             .map_err(JsInterpreterError::InitializationFailed)?;
 
-        Ok(Self { context, state })
+        let mut interrupt_state = Box::<InterruptState>::default();
+        let opaque = interrupt_state.as_mut() as *mut InterruptState as *mut c_void;
+        // SAFETY: the state is boxed so its address is stable and it is only dropped after the runtime (see field order)
+        unsafe {
+            let runtime = quickjs_wasm_sys::JS_GetRuntime(context.inner());
+            quickjs_wasm_sys::JS_SetInterruptHandler(
+                runtime,
+                Some(Self::interrupt_handler_trampoline),
+                opaque,
+            );
+        }
+
+        let mut module_loader = Box::<ModuleLoader>::default();
+        let opaque = module_loader.as_mut() as *mut ModuleLoader as *mut c_void;
+        // SAFETY: same as for the interrupt state above
+        unsafe {
+            let runtime = quickjs_wasm_sys::JS_GetRuntime(context.inner());
+            quickjs_wasm_sys::JS_SetModuleLoaderFunc(
+                runtime,
+                None,
+                Some(ModuleLoader::load_trampoline),
+                opaque,
+            );
+        }
+
+        Ok(Self {
+            context,
+            interrupt_state,
+            module_loader,
+            limits: JsInterpreterLimits::default(),
+            loaded_map: None,
+            state,
+        })
     }
 
     pub fn state_mut(&mut self) -> impl DerefMut<Target = S> + '_ {
         self.state.borrow_mut()
     }
 
+    /// Sets resource limits for code evaluated from now on.
+    pub fn set_limits(&mut self, limits: JsInterpreterLimits) {
+        // SAFETY: runtime is valid for the lifetime of the context
+        unsafe {
+            let runtime = quickjs_wasm_sys::JS_GetRuntime(self.context.inner());
+            // QuickJS treats 0 as no limit
+            quickjs_wasm_sys::JS_SetMemoryLimit(runtime, limits.max_heap_size.unwrap_or(0) as _);
+        }
+        self.interrupt_state.execution_budget = limits.execution_budget;
+        self.limits = limits;
+    }
+
+    /// Sets a handler which is periodically polled by QuickJS while code is running.
+    ///
+    /// When the handler returns `true` the running code is interrupted with an uncatchable error.
+    pub fn set_interrupt_handler(&mut self, handler: impl FnMut() -> bool + 'static) {
+        self.interrupt_state.handler = Some(Box::new(handler));
+    }
+
+    unsafe extern "C" fn interrupt_handler_trampoline(
+        _runtime: *mut quickjs_wasm_sys::JSRuntime,
+        opaque: *mut c_void,
+    ) -> c_int {
+        // SAFETY: opaque is the pointer registered in `new`
+        let state = unsafe { &mut *(opaque as *mut InterruptState) };
+
+        if state.execution_budget.is_some() {
+            if state.remaining == 0 {
+                state.budget_exhausted = true;
+                return 1;
+            }
+            state.remaining -= 1;
+        }
+
+        match state.handler {
+            Some(ref mut handler) => handler() as c_int,
+            None => 0,
+        }
+    }
+
+    /// Takes the pending exception and maps it to an error, distinguishing exceeded limits from uncaught exceptions.
+    fn exception_error(&self) -> JsInterpreterError {
+        // SAFETY: only called after QuickJS returned the exception marker for this context
+        let exception = unsafe { exception::take_pending(self.context.inner()) };
+
+        if self.interrupt_state.budget_exhausted {
+            return JsInterpreterError::ExecutionBudgetExceeded(
+                self.limits.execution_budget.unwrap_or(0),
+            );
+        }
+
+        let exception = match (exception, self.limits.max_heap_size) {
+            (PendingException::OutOfMemory, Some(max_heap_size)) => {
+                return JsInterpreterError::HeapLimitExceeded(max_heap_size)
+            }
+            (PendingException::OutOfMemory, None) => JsException {
+                name: "InternalError".to_string(),
+                message: "out of memory".to_string(),
+                stack: Vec::new(),
+            },
+            (PendingException::Thrown(exception), _) => exception,
+        };
+
+        JsInterpreterError::UncaughtException(exception)
+    }
+
+    /// Frees `value` returned by QuickJS, or returns the pending exception if it is the exception marker.
+    fn check_value(&self, value: JSValue) -> Result<(), JsInterpreterError> {
+        if quickjs_wasm_sys::JS_VALUE_GET_TAG(value) == quickjs_wasm_sys::JS_TAG_EXCEPTION {
+            return Err(self.exception_error());
+        }
+
+        // SAFETY: value was returned by QuickJS for this context and is not used afterwards
+        unsafe { quickjs_wasm_sys::JS_FreeValue(self.context.inner(), value) };
+        Ok(())
+    }
+
+    /// Evaluates `code` through QuickJS directly, so that a thrown exception can be read from its value.
+    fn eval_raw(&self, name: &str, code: &str, eval_type: u32) -> Result<(), JsInterpreterError> {
+        let name = CString::new(name).map_err(|err| JsInterpreterError::EvalFailed(err.into()))?;
+        let code = CString::new(code).map_err(|err| JsInterpreterError::EvalFailed(err.into()))?;
+
+        // SAFETY: code is nul-terminated as required by QuickJS
+        let value = unsafe {
+            quickjs_wasm_sys::JS_Eval(
+                self.context.inner(),
+                code.as_ptr(),
+                code.as_bytes().len() as _,
+                name.as_ptr(),
+                eval_type as c_int,
+            )
+        };
+
+        self.check_value(value)
+    }
+
     pub fn eval_code(&mut self, name: &str, code: &str) -> Result<(), JsInterpreterError> {
+        self.interrupt_state.reset_budget();
+        self.eval_code_within_budget(name, code)
+    }
+
+    fn eval_code_within_budget(
+        &mut self,
+        name: &str,
+        code: &str,
+    ) -> Result<(), JsInterpreterError> {
         if code.is_empty() {
             return Err(JsInterpreterError::EvalCodeEmpty);
         }
 
-        self.context
-            .eval_global(name, code)
-            .map_err(JsInterpreterError::EvalFailed)?;
-
-        Ok(())
+        self.eval_raw(name, code, quickjs_wasm_sys::JS_EVAL_TYPE_GLOBAL)
     }
 
     pub fn compile_code(&mut self, name: &str, code: &str) -> Result<Vec<u8>, JsInterpreterError> {
@@ -78,24 +285,129 @@ impl<S: MapStdFull + 'static> JsInterpreter<S> {
     }
 
     pub fn eval_bytecode(&mut self, bytecode: &[u8]) -> Result<(), JsInterpreterError> {
-        self.context
-            .eval_binary(bytecode)
-            .map_err(JsInterpreterError::EvalFailed)?;
+        self.interrupt_state.reset_budget();
+
+        let context = self.context.inner();
+        // SAFETY: bytecode is only read within its length, the read function is consumed by `JS_EvalFunction`
+        let value = unsafe {
+            let function = quickjs_wasm_sys::JS_ReadObject(
+                context,
+                bytecode.as_ptr(),
+                bytecode.len() as _,
+                quickjs_wasm_sys::JS_READ_OBJ_BYTECODE as c_int,
+            );
+            if quickjs_wasm_sys::JS_VALUE_GET_TAG(function) == quickjs_wasm_sys::JS_TAG_EXCEPTION {
+                return Err(self.exception_error());
+            }
 
-        Ok(())
+            quickjs_wasm_sys::JS_EvalFunction(context, function)
+        };
+
+        self.check_value(value)
     }
 
+    /// Evaluates map code and runs `usecase`, both within one execution budget.
+    ///
+    /// The map is either a script defining use cases as global functions or an ES module exporting them.
+    ///
+    /// Running the same map again only runs `usecase`, the map is not evaluated twice.
     pub fn run(&mut self, name: &str, code: &str, usecase: &str) -> Result<(), JsInterpreterError> {
         if code.is_empty() {
             return Err(JsInterpreterError::EvalCodeEmpty);
         }
 
-        self.eval_code(name, code)?;
+        self.interrupt_state.reset_budget();
+        let is_module = match self.loaded_map {
+            // evaluating a script again would redeclare its top-level bindings and modules are cached by name
+            Some(ref loaded) if loaded.name == name && loaded.code == code => loaded.is_module,
+            _ => {
+                self.loaded_map = None;
+
+                let is_module = ModuleLoader::is_module(code);
+                if is_module {
+                    self.module_loader.set_map(name, code);
+                    self.eval_raw(
+                        module_loader::ENTRY_MODULE_NAME,
+                        module_loader::ENTRY_MODULE,
+                        quickjs_wasm_sys::JS_EVAL_TYPE_MODULE,
+                    )?;
+                } else {
+                    self.eval_code_within_budget(name, code)?;
+                }
+
+                self.loaded_map = Some(LoadedMap {
+                    name: name.to_string(),
+                    code: code.to_string(),
+                    is_module,
+                });
+                is_module
+            }
+        };
+
+        self.call_start(usecase, is_module)
+    }
 
-        let entry = format!("_start('{}');", usecase);
-        self.eval_code("", &entry)?;
+    /// Calls the stdlib entry point `_start(usecase, mapModule?)`.
+    ///
+    /// The use case name is passed as a value so that it is never interpreted as code.
+    fn call_start(&self, usecase: &str, is_module: bool) -> Result<(), JsInterpreterError> {
+        let context = self.context.inner();
+
+        // SAFETY: every value obtained here is freed before returning
+        unsafe {
+            let global = quickjs_wasm_sys::JS_GetGlobalObject(context);
+            let start =
+                quickjs_wasm_sys::JS_GetPropertyStr(context, global, Self::START_FUNCTION.as_ptr());
+
+            let mut args = vec![quickjs_wasm_sys::JS_NewStringLen(
+                context,
+                usecase.as_ptr() as *const c_char,
+                usecase.len() as _,
+            )];
+            if is_module {
+                args.push(quickjs_wasm_sys::JS_GetPropertyStr(
+                    context,
+                    global,
+                    module_loader::MAP_MODULE_GLOBAL.as_ptr(),
+                ));
+            }
 
-        Ok(())
+            let result = if quickjs_wasm_sys::JS_IsFunction(context, start) == 0 {
+                Err(JsInterpreterError::EvalFailed(anyhow::anyhow!(
+                    "Map stdlib does not define _start"
+                )))
+            } else {
+                let value = quickjs_wasm_sys::JS_Call(
+                    context,
+                    start,
+                    global,
+                    args.len() as c_int,
+                    args.as_mut_ptr(),
+                );
+                self.check_value(value)
+            };
+
+            for arg in args {
+                quickjs_wasm_sys::JS_FreeValue(context, arg);
+            }
+            quickjs_wasm_sys::JS_FreeValue(context, start);
+            quickjs_wasm_sys::JS_FreeValue(context, global);
+
+            result
+        }
+    }
+}
+impl<S: MapStdFull + 'static> Interpreter<S> for JsInterpreter<S> {
+    fn eval(&mut self, name: &str, code: &str) -> Result<(), PerformException> {
+        Ok(self.eval_code(name, code)?)
+    }
+
+    fn run(&mut self, name: &str, code: &str, usecase: &str) -> Result<(), PerformException> {
+        Ok(JsInterpreter::run(self, name, code, usecase)?)
+    }
+
+    fn state_mut(&mut self) -> Box<dyn DerefMut<Target = S> + '_> {
+        Box::new(self.state.borrow_mut())
     }
 }
 
@@ -116,6 +428,15 @@ mod test {
             eprintln!("mock print: {}", message)
         }
 
+        fn log(
+            &mut self,
+            level: map_std::unstable::LogLevel,
+            message: &str,
+            fields: &map_std::unstable::MapValueObject,
+        ) {
+            eprintln!("mock log {:?}: {} {:?}", level, message, fields)
+        }
+
         fn stream_read(
             &mut self,
             _handle: sf_std::abi::Handle,
@@ -136,6 +457,40 @@ mod test {
             Ok(())
         }
 
+        fn crypto_digest(
+            &mut self,
+            algorithm: &str,
+            data: &[u8],
+        ) -> Result<Vec<u8>, map_std::unstable::CryptoError> {
+            let algorithm = map_std::unstable::crypto::DigestAlgorithm::parse(algorithm)?;
+            Ok(map_std::unstable::crypto::digest(algorithm, data))
+        }
+
+        fn crypto_hmac(
+            &mut self,
+            algorithm: &str,
+            key: &[u8],
+            data: &[u8],
+        ) -> Result<Vec<u8>, map_std::unstable::CryptoError> {
+            let algorithm = map_std::unstable::crypto::DigestAlgorithm::parse(algorithm)?;
+            Ok(map_std::unstable::crypto::hmac(algorithm, key, data))
+        }
+
+        fn crypto_random_fill(
+            &mut self,
+            buf: &mut [u8],
+        ) -> Result<(), map_std::unstable::CryptoError> {
+            buf.fill(4);
+            Ok(())
+        }
+
+        fn sleep(
+            &mut self,
+            _duration: std::time::Duration,
+        ) -> Result<(), map_std::unstable::SleepError> {
+            Ok(())
+        }
+
         fn http_call(
             &mut self,
             _params: map_std::unstable::HttpRequest,
@@ -171,7 +526,13 @@ mod test {
             Ok(())
         }
     }
-    impl MapStdFull for MockMapStd {}
+    impl MapStdFull for MockMapStd {
+        fn take_output(
+            &mut self,
+        ) -> Option<Result<map_std::unstable::MapValue, map_std::unstable::MapValue>> {
+            None
+        }
+    }
 
     #[test]
     fn test_creating_two_instances_does_not_crash() {
@@ -199,4 +560,157 @@ mod test {
         interpreter2.eval_code("test2", CODE).unwrap();
         std::mem::drop(interpreter2);
     }
+
+    #[test]
+    fn test_execution_budget_exceeded() {
+        let mut interpreter = JsInterpreter::new(MockMapStd::new()).unwrap();
+        interpreter.set_limits(JsInterpreterLimits {
+            execution_budget: Some(10),
+            max_heap_size: None,
+        });
+
+        let err = interpreter
+            .eval_code("test", "while (true) {}")
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            JsInterpreterError::ExecutionBudgetExceeded(10)
+        ));
+
+        // budget is renewed for each evaluation
+        interpreter.eval_code("test", "const x = 1;").unwrap();
+    }
+
+    #[test]
+    fn test_heap_limit_exceeded() {
+        let mut interpreter = JsInterpreter::new(MockMapStd::new()).unwrap();
+        interpreter.set_limits(JsInterpreterLimits {
+            execution_budget: None,
+            max_heap_size: Some(4 * 1024 * 1024),
+        });
+
+        let err = interpreter
+            .eval_code(
+                "test",
+                "const chunks = []; while (true) { chunks.push(new Uint8Array(1024 * 1024)); }",
+            )
+            .unwrap_err();
+        assert!(matches!(err, JsInterpreterError::HeapLimitExceeded(_)));
+    }
+
+    #[test]
+    fn test_out_of_memory_message_is_not_heap_limit() {
+        let mut interpreter = JsInterpreter::new(MockMapStd::new()).unwrap();
+        interpreter.set_limits(JsInterpreterLimits {
+            execution_budget: None,
+            max_heap_size: Some(4 * 1024 * 1024),
+        });
+
+        let err = interpreter
+            .eval_code("test", "throw new Error('out of memory')")
+            .unwrap_err();
+        match err {
+            JsInterpreterError::UncaughtException(exception) => {
+                assert_eq!(exception.message, "out of memory")
+            }
+            err => panic!("Unexpected error: {:?}", err),
+        }
+    }
+
+    const TEST_START: &str = r#"
+        globalThis._start = function _start(useCaseName, mapModule) {
+            const usecase = mapModule === undefined ? globalThis[useCaseName] : mapModule[useCaseName];
+            globalThis.result = usecase();
+        };
+        globalThis.std = { answer: 42 };
+    "#;
+
+    #[test]
+    fn test_run_module_map() {
+        let mut interpreter = JsInterpreter::new(MockMapStd::new()).unwrap();
+        interpreter.eval_code("map_std.js", TEST_START).unwrap();
+
+        interpreter
+            .run(
+                "map.js",
+                "import { std } from 'map-std';\nexport function Example() { return std.answer; }",
+                "Example",
+            )
+            .unwrap();
+        interpreter
+            .eval_code(
+                "check.js",
+                "if (globalThis.result !== 42) { throw new Error('wrong result'); }",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_run_same_map_twice() {
+        let mut interpreter = JsInterpreter::new(MockMapStd::new()).unwrap();
+        interpreter.eval_code("map_std.js", TEST_START).unwrap();
+
+        const MAP: &str = "const answer = 42;\nfunction Example() { return answer; }";
+        interpreter.run("map.js", MAP, "Example").unwrap();
+        interpreter.run("map.js", MAP, "Example").unwrap();
+        interpreter
+            .eval_code(
+                "check.js",
+                "if (globalThis.result !== 42) { throw new Error('wrong result'); }",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_run_usecase_name_is_not_code() {
+        let mut interpreter = JsInterpreter::new(MockMapStd::new()).unwrap();
+        interpreter.eval_code("map_std.js", TEST_START).unwrap();
+
+        let err = interpreter
+            .run(
+                "map.js",
+                "function Example() { return 1; }",
+                "Example'); globalThis.injected = true; ('",
+            )
+            .unwrap_err();
+        assert!(matches!(err, JsInterpreterError::UncaughtException(_)));
+        interpreter
+            .eval_code(
+                "check.js",
+                "if (globalThis.injected) { throw new Error('injected'); }",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_uncaught_exception_is_read_from_value() {
+        let mut interpreter = JsInterpreter::new(MockMapStd::new()).unwrap();
+        interpreter.eval_code("map_std.js", TEST_START).unwrap();
+
+        let err = interpreter
+            .run(
+                "map.js",
+                "function Example() {\n  throw new TypeError('bad: input\\nsecond line');\n}",
+                "Example",
+            )
+            .unwrap_err();
+        let exception = match err {
+            JsInterpreterError::UncaughtException(exception) => exception,
+            err => panic!("Unexpected error: {}", err),
+        };
+        assert_eq!(exception.name, "TypeError");
+        assert_eq!(exception.message, "bad: input\nsecond line");
+        assert_eq!(exception.stack[0].function.as_deref(), Some("Example"));
+        assert_eq!(exception.stack[0].file.as_deref(), Some("map.js"));
+        assert_eq!(exception.stack[0].line, Some(2));
+
+        let err = interpreter
+            .run("thrown.js", "function Example() { throw 42; }", "Example")
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            JsInterpreterError::UncaughtException(JsException { ref name, ref message, .. })
+                if name == "Error" && message == "42"
+        ));
+    }
 }
@@ -0,0 +1,123 @@
+//! Resolution of ES module imports.
+//!
+//! Maps written as ES modules are imported by a fixed entry module and may themselves only import the built-in `map-std` module.
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+
+use quickjs_wasm_sys::{JSContext, JSModuleDef};
+
+/// Name of the built-in module exposing the map stdlib.
+pub const MAP_STD_MODULE_NAME: &str = "map-std";
+/// Re-exports the globals installed by the map stdlib, so it must be evaluated before any module imports it.
+const MAP_STD_MODULE: &str = r#"const std = globalThis.std;
+const Buffer = globalThis.Buffer;
+export { std, Buffer };
+export default std;
+"#;
+
+/// Specifier under which the entry module imports the map being run.
+const MAP_MODULE_SPECIFIER: &str = "superface:map";
+pub const ENTRY_MODULE_NAME: &str = "<entry>";
+/// Global under which the entry module exposes the map module namespace.
+pub const MAP_MODULE_GLOBAL: &CStr = c"__mapModule";
+pub const ENTRY_MODULE: &str = r#"import * as map from 'superface:map';
+globalThis.__mapModule = map;
+"#;
+
+#[derive(Debug, Default)]
+pub struct ModuleLoader {
+    /// Name and code of the map module.
+    map: Option<(String, String)>,
+}
+impl ModuleLoader {
+    pub fn set_map(&mut self, name: &str, code: &str) {
+        self.map = Some((name.to_string(), code.to_string()));
+    }
+
+    fn resolve(&self, specifier: &str) -> Option<(&str, &str)> {
+        match specifier {
+            MAP_STD_MODULE_NAME => Some((MAP_STD_MODULE_NAME, MAP_STD_MODULE)),
+            MAP_MODULE_SPECIFIER => self
+                .map
+                .as_ref()
+                .map(|(name, code)| (name.as_str(), code.as_str())),
+            _ => None,
+        }
+    }
+
+    /// Returns whether `code` is an ES module rather than a script.
+    pub fn is_module(code: &str) -> bool {
+        // SAFETY: input is only read within given length
+        unsafe {
+            quickjs_wasm_sys::JS_DetectModule(code.as_ptr() as *const c_char, code.len() as _) != 0
+        }
+    }
+
+    pub unsafe extern "C" fn load_trampoline(
+        context: *mut JSContext,
+        module_name: *const c_char,
+        opaque: *mut c_void,
+    ) -> *mut JSModuleDef {
+        // SAFETY: opaque is the pointer registered in `JsInterpreter::new` and module_name is a valid C string
+        let loader = unsafe { &*(opaque as *const ModuleLoader) };
+        let specifier = unsafe { CStr::from_ptr(module_name) }.to_string_lossy();
+
+        let (name, code) = match loader.resolve(&specifier) {
+            Some(source) => source,
+            None => {
+                return Self::throw_reference_error(
+                    context,
+                    &format!(
+                        "Module \"{}\" not found, maps can only import \"{}\"",
+                        specifier, MAP_STD_MODULE_NAME
+                    ),
+                )
+            }
+        };
+        let (name, code) = match (CString::new(name), CString::new(code)) {
+            (Ok(name), Ok(code)) => (name, code),
+            _ => {
+                return Self::throw_reference_error(
+                    context,
+                    &format!("Module \"{}\" contains a nul byte", specifier),
+                )
+            }
+        };
+
+        // SAFETY: code is nul-terminated as required by QuickJS
+        unsafe {
+            let value = quickjs_wasm_sys::JS_Eval(
+                context,
+                code.as_ptr(),
+                code.as_bytes().len() as _,
+                name.as_ptr(),
+                (quickjs_wasm_sys::JS_EVAL_TYPE_MODULE
+                    | quickjs_wasm_sys::JS_EVAL_FLAG_COMPILE_ONLY) as c_int,
+            );
+            if quickjs_wasm_sys::JS_VALUE_GET_TAG(value) == quickjs_wasm_sys::JS_TAG_EXCEPTION {
+                return std::ptr::null_mut();
+            }
+
+            // compiled module value holds the module definition, which is kept alive by the context
+            let module = quickjs_wasm_sys::JS_VALUE_GET_PTR(value) as *mut JSModuleDef;
+            quickjs_wasm_sys::JS_FreeValue(context, value);
+
+            module
+        }
+    }
+
+    /// Throws a `ReferenceError` in `context`, returns null to be returned from the loader.
+    unsafe fn throw_reference_error(context: *mut JSContext, message: &str) -> *mut JSModuleDef {
+        let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+        // SAFETY: message is passed through a constant format string
+        unsafe {
+            quickjs_wasm_sys::JS_ThrowReferenceError(
+                context,
+                b"%s\0".as_ptr() as *const c_char,
+                message.as_ptr(),
+            )
+        };
+
+        std::ptr::null_mut()
+    }
+}
@@ -0,0 +1,264 @@
+//! Minimal [source map v3](https://sourcemaps.info/spec.html) decoder used to map stack traces back to original sources.
+
+use serde::Deserialize;
+
+use sf_std::unstable::exception::{JsException, JsStackFrame};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SourceMapError {
+    #[error("Failed to parse source map: {0}")]
+    ParseFailed(#[from] serde_json::Error),
+    #[error("Unsupported source map version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Invalid source map mappings")]
+    InvalidMappings,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SourceMapJson {
+    version: u32,
+    #[serde(default)]
+    source_root: Option<String>,
+    sources: Vec<Option<String>>,
+    mappings: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Mapping {
+    generated_column: u32,
+    source: u32,
+    line: u32,
+    column: u32,
+}
+
+/// Original location, 0-based as stored in the source map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginalLocation<'a> {
+    pub source: &'a str,
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Debug)]
+pub struct SourceMap {
+    sources: Vec<String>,
+    /// Mappings for each generated line, sorted by generated column.
+    lines: Vec<Vec<Mapping>>,
+}
+impl SourceMap {
+    pub fn parse(data: &[u8]) -> Result<Self, SourceMapError> {
+        let json: SourceMapJson = serde_json::from_slice(data)?;
+        if json.version != 3 {
+            return Err(SourceMapError::UnsupportedVersion(json.version));
+        }
+
+        let source_root = json.source_root.unwrap_or_default();
+        let sources = json
+            .sources
+            .into_iter()
+            .map(|source| {
+                let source = source.unwrap_or_default();
+                if source_root.is_empty() || source_root.ends_with('/') {
+                    format!("{}{}", source_root, source)
+                } else {
+                    format!("{}/{}", source_root, source)
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            sources,
+            lines: Self::decode_mappings(&json.mappings)?,
+        })
+    }
+
+    fn decode_mappings(mappings: &str) -> Result<Vec<Vec<Mapping>>, SourceMapError> {
+        // source, line and column are relative to the previous segment across the whole map
+        let mut source = 0i64;
+        let mut line = 0i64;
+        let mut column = 0i64;
+
+        let mut lines = Vec::new();
+        for generated_line in mappings.split(';') {
+            // generated column is relative to the previous segment on the same line
+            let mut generated_column = 0i64;
+            let mut line_mappings = Vec::new();
+
+            for segment in generated_line.split(',').filter(|s| !s.is_empty()) {
+                let fields = decode_vlq(segment).ok_or(SourceMapError::InvalidMappings)?;
+                match fields.len() {
+                    1 => {
+                        // segment without an original location
+                        generated_column += fields[0];
+                        continue;
+                    }
+                    // the optional fifth field is a name index, names are not needed to map locations
+                    4 | 5 => (),
+                    _ => return Err(SourceMapError::InvalidMappings),
+                }
+
+                generated_column += fields[0];
+                source += fields[1];
+                line += fields[2];
+                column += fields[3];
+
+                if generated_column < 0 || source < 0 || line < 0 || column < 0 {
+                    return Err(SourceMapError::InvalidMappings);
+                }
+                line_mappings.push(Mapping {
+                    generated_column: generated_column as u32,
+                    source: source as u32,
+                    line: line as u32,
+                    column: column as u32,
+                });
+            }
+
+            line_mappings.sort_by_key(|m| m.generated_column);
+            lines.push(line_mappings);
+        }
+
+        Ok(lines)
+    }
+
+    /// Looks up the original location of a 0-based generated position.
+    ///
+    /// When `column` is not known the first mapping on the line is used.
+    pub fn lookup(&self, line: u32, column: Option<u32>) -> Option<OriginalLocation<'_>> {
+        let mappings = self.lines.get(line as usize)?;
+        let mapping = match column {
+            None => mappings.first()?,
+            Some(column) => match mappings.partition_point(|m| m.generated_column <= column) {
+                0 => mappings.first()?,
+                index => &mappings[index - 1],
+            },
+        };
+
+        Some(OriginalLocation {
+            source: self.sources.get(mapping.source as usize)?,
+            line: mapping.line,
+            column: mapping.column,
+        })
+    }
+
+    /// Maps frames of `exception` located in `file_name` to their original locations.
+    pub fn apply(&self, file_name: &str, exception: &mut JsException) {
+        for frame in exception.stack.iter_mut() {
+            if frame.file.as_deref() != Some(file_name) {
+                continue;
+            }
+            let line = match frame.line {
+                Some(line) if line > 0 => line - 1,
+                _ => continue,
+            };
+
+            if let Some(original) = self.lookup(line, frame.column.map(|c| c.saturating_sub(1))) {
+                *frame = JsStackFrame {
+                    function: frame.function.take(),
+                    file: Some(original.source.to_string()),
+                    line: Some(original.line + 1),
+                    column: Some(original.column + 1),
+                };
+            }
+        }
+    }
+}
+
+/// Decodes a base64 VLQ segment into its fields.
+fn decode_vlq(segment: &str) -> Option<Vec<i64>> {
+    let mut fields = Vec::new();
+    let mut value = 0i64;
+    let mut shift = 0;
+
+    for byte in segment.bytes() {
+        let digit = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        } as i64;
+        if shift > 60 {
+            return None;
+        }
+
+        value += (digit & 0b11111) << shift;
+        if digit & 0b100000 != 0 {
+            shift += 5;
+            continue;
+        }
+
+        // lowest bit is the sign
+        let magnitude = value >> 1;
+        fields.push(if value & 1 == 1 {
+            -magnitude
+        } else {
+            magnitude
+        });
+        value = 0;
+        shift = 0;
+    }
+
+    if shift != 0 {
+        return None;
+    }
+
+    Some(fields)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_vlq() {
+        assert_eq!(decode_vlq("AAAA"), Some(vec![0, 0, 0, 0]));
+        assert_eq!(decode_vlq("SAAQD"), Some(vec![9, 0, 0, 8, -1]));
+        assert_eq!(decode_vlq("2HAAA"), Some(vec![123, 0, 0, 0]));
+        assert_eq!(decode_vlq("g"), None);
+    }
+
+    #[test]
+    fn test_apply_source_map() {
+        // line 1: `function a() {` -> map.ts 1:0
+        // line 2: `  throw new Error()` at columns 2 and 8 -> map.ts 3:4 and 3:10
+        let source_map = SourceMap::parse(
+            br#"{"version":3,"sources":["map.ts"],"names":[],"mappings":"AAAA;EAEI,MAAM"}"#,
+        )
+        .unwrap();
+
+        let mut exception = JsException {
+            name: "Error".to_string(),
+            message: "test".to_string(),
+            stack: vec![
+                JsStackFrame {
+                    function: Some("a".to_string()),
+                    file: Some("map.js".to_string()),
+                    line: Some(2),
+                    column: Some(10),
+                },
+                JsStackFrame {
+                    function: Some("b".to_string()),
+                    file: Some("map.js".to_string()),
+                    line: Some(2),
+                    column: None,
+                },
+                JsStackFrame {
+                    function: Some("c".to_string()),
+                    file: Some("map_std.js".to_string()),
+                    line: Some(2),
+                    column: None,
+                },
+            ],
+        };
+        source_map.apply("map.js", &mut exception);
+
+        assert_eq!(exception.stack[0].file.as_deref(), Some("map.ts"));
+        assert_eq!(exception.stack[0].line, Some(3));
+        assert_eq!(exception.stack[0].column, Some(11));
+        assert_eq!(exception.stack[1].line, Some(3));
+        assert_eq!(exception.stack[1].column, Some(5));
+        assert_eq!(exception.stack[2].file.as_deref(), Some("map_std.js"));
+    }
+}
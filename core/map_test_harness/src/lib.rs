@@ -0,0 +1,166 @@
+//! Runs maps natively against an in-memory [MockMapStd], without a wasm host.
+//!
+//! ```ignore
+//! let harness = MapTestHarness::from_files("map_std.js", "example.provider.map.js")?;
+//! let state = MockMapStd::new(map_value!({ "id": 1 }))
+//!     .mock_http(MockHttpResponse::new("GET", "https://example.com/items/1").body(r#"{"name":"one"}"#));
+//!
+//! let outcome = harness.run("GetItem", state)?;
+//! assert_eq!(outcome.output, Ok(map_value!({ "name": "one" })));
+//! ```
+
+use std::{path::Path, time::Duration};
+
+use interpreter_js::{JsInterpreter, JsInterpreterError};
+use map_std::{unstable::MapValue, MapStdFull};
+use thiserror::Error;
+
+mod mock;
+pub use mock::{MockHttpResponse, MockMapStd, RecordedHttpRequest, RecordedLog};
+
+#[derive(Debug, Error)]
+pub enum HarnessError {
+    #[error(transparent)]
+    Interpreter(#[from] JsInterpreterError),
+    #[error("Map did not set any output")]
+    NoOutput,
+}
+
+/// What a map run produced and did.
+#[derive(Debug)]
+pub struct MapTestOutcome {
+    pub output: Result<MapValue, MapValue>,
+    pub requests: Vec<RecordedHttpRequest>,
+    pub logs: Vec<RecordedLog>,
+    pub sleeps: Vec<Duration>,
+    /// Mocked responses the map never requested.
+    pub unused_http_mocks: Vec<MockHttpResponse>,
+}
+
+pub struct MapTestHarness {
+    stdlib_name: String,
+    stdlib_code: String,
+    map_name: String,
+    map_code: String,
+}
+impl MapTestHarness {
+    pub fn new(
+        stdlib_name: impl Into<String>,
+        stdlib_code: impl Into<String>,
+        map_name: impl Into<String>,
+        map_code: impl Into<String>,
+    ) -> Self {
+        Self {
+            stdlib_name: stdlib_name.into(),
+            stdlib_code: stdlib_code.into(),
+            map_name: map_name.into(),
+            map_code: map_code.into(),
+        }
+    }
+
+    /// Loads the map stdlib (i.e. `map_std.js` built from `core_js/map-std`) and the map from files.
+    pub fn from_files(
+        stdlib_path: impl AsRef<Path>,
+        map_path: impl AsRef<Path>,
+    ) -> std::io::Result<Self> {
+        let stdlib_path = stdlib_path.as_ref();
+        let map_path = map_path.as_ref();
+
+        Ok(Self::new(
+            stdlib_path.to_string_lossy(),
+            std::fs::read_to_string(stdlib_path)?,
+            map_path.to_string_lossy(),
+            std::fs::read_to_string(map_path)?,
+        ))
+    }
+
+    /// Runs `usecase` in a fresh interpreter on top of `state`.
+    ///
+    /// Each run gets its own interpreter so state cannot leak between tests.
+    pub fn run(&self, usecase: &str, state: MockMapStd) -> Result<MapTestOutcome, HarnessError> {
+        let mut interpreter = JsInterpreter::new(state)?;
+        interpreter.eval_code(&self.stdlib_name, &self.stdlib_code)?;
+        interpreter.run(&self.map_name, &self.map_code, usecase)?;
+
+        let mut state = interpreter.state_mut();
+        let output = state.take_output().ok_or(HarnessError::NoOutput)?;
+
+        Ok(MapTestOutcome {
+            output,
+            requests: std::mem::take(&mut state.requests),
+            logs: std::mem::take(&mut state.logs),
+            sleeps: std::mem::take(&mut state.sleeps),
+            unused_http_mocks: state.unused_http_mocks().to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use map_std::map_value;
+
+    use super::*;
+
+    /// Just enough of the map stdlib to take the context, make http calls and set the output.
+    const STDLIB: &str = r#"
+function exchange(message) {
+  const response = JSON.parse(__ffi.unstable.message_exchange(JSON.stringify(message)));
+  if (response.kind !== 'ok') {
+    throw new Error(response.message);
+  }
+  return response;
+}
+globalThis.std = {
+  fetch(url) {
+    const { handle } = exchange({ kind: 'http-call', method: 'GET', url, headers: {}, query: {}, security: null });
+    const { status, body_stream } = exchange({ kind: 'http-call-head', handle });
+    const buffer = new ArrayBuffer(1024);
+    const count = __ffi.unstable.stream_read(body_stream, buffer);
+    __ffi.unstable.stream_close(body_stream);
+    return { status, body: __ffi.unstable.bytes_to_utf8(buffer.slice(0, count)) };
+  }
+};
+globalThis._start = function _start(useCaseName) {
+  const { context } = exchange({ kind: 'take-context' });
+  const output = globalThis[useCaseName](context);
+  exchange({ kind: 'set-output-success', output });
+};
+"#;
+    const MAP: &str = r#"
+function GetItem({ input }) {
+  __ffi.unstable.print(`fetching ${input.id}`);
+  const response = std.fetch(`https://example.com/items/${input.id}`);
+  return { status: response.status, name: response.body };
+}
+"#;
+
+    #[test]
+    fn test_run() {
+        let harness = MapTestHarness::new("std.js", STDLIB, "map.js", MAP);
+        let state = MockMapStd::new(map_value!({ "id": MapValue::Number(1.into()) }))
+            .mock_http(MockHttpResponse::new("GET", "https://example.com/items/1").body("one"))
+            .mock_http(MockHttpResponse::new("GET", "https://example.com/items/2"));
+
+        let outcome = harness.run("GetItem", state).unwrap();
+        let output = match outcome.output {
+            Ok(MapValue::Object(output)) => output,
+            output => panic!("Unexpected output: {:?}", output),
+        };
+        assert!(
+            matches!(&output["status"], MapValue::Number(status) if status.as_u64() == Some(200))
+        );
+        assert!(matches!(&output["name"], MapValue::String(name) if name == "one"));
+        assert_eq!(outcome.requests.len(), 1);
+        assert_eq!(outcome.requests[0].method, "GET");
+        assert_eq!(outcome.requests[0].url, "https://example.com/items/1");
+        assert_eq!(
+            outcome.logs,
+            vec![RecordedLog {
+                level: None,
+                message: "fetching 1".to_string(),
+            }]
+        );
+        assert!(outcome.sleeps.is_empty());
+        assert_eq!(outcome.unused_http_mocks.len(), 1);
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    io::{Cursor, Read},
+    time::Duration,
+};
+
+use map_std::{
+    handle_map::HandleMap,
+    map_value,
+    unstable::{
+        crypto::{self, CryptoError, DigestAlgorithm},
+        security::{resolve_security, SecurityMap},
+        HttpCallError, HttpCallHeadError, HttpRequest, HttpRequestSecurity, HttpResponse, LogLevel,
+        MapStdUnstable, MapValue, MapValueObject, SetOutputError, SleepError, TakeContextError,
+    },
+    MapStdFull,
+};
+use sf_std::{abi::Handle, HeadersMultiMap, MultiMap};
+
+/// Response served to a map http call matching `method` and `url`.
+#[derive(Debug, Clone)]
+pub struct MockHttpResponse {
+    pub method: String,
+    /// Url without the query, which is matched separately by the map std.
+    pub url: String,
+    pub status: u16,
+    pub headers: HeadersMultiMap,
+    pub body: Vec<u8>,
+}
+impl MockHttpResponse {
+    pub fn new(method: &str, url: &str) -> Self {
+        Self {
+            method: method.to_string(),
+            url: url.to_string(),
+            status: 200,
+            headers: HeadersMultiMap::new(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .entry(name.to_ascii_lowercase().as_str().into())
+            .or_default()
+            .push(value.to_string());
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    fn matches(&self, request: &HttpRequest) -> bool {
+        self.method.eq_ignore_ascii_case(&request.method) && self.url == request.url
+    }
+}
+
+/// Http call made by the map, with security already applied.
+#[derive(Debug, Clone)]
+pub struct RecordedHttpRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: HeadersMultiMap,
+    pub query: MultiMap,
+    pub body: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedLog {
+    /// `None` for messages printed with `print`.
+    pub level: Option<LogLevel>,
+    pub message: String,
+}
+
+/// In-memory [MapStdUnstable] serving scripted http responses and recording everything the map does.
+#[derive(Default)]
+pub struct MockMapStd {
+    context: Option<MapValue>,
+    security: Option<SecurityMap>,
+    output: Option<Result<MapValue, MapValue>>,
+    /// Responses not yet served, each is served at most once in the order they were added.
+    http_mocks: Vec<MockHttpResponse>,
+    http_requests: HandleMap<MockHttpResponse>,
+    streams: HandleMap<Cursor<Vec<u8>>>,
+    pub requests: Vec<RecordedHttpRequest>,
+    pub logs: Vec<RecordedLog>,
+    pub sleeps: Vec<Duration>,
+}
+impl MockMapStd {
+    /// Creates the map std with use case `input`, empty parameters and services.
+    pub fn new(input: MapValue) -> Self {
+        Self::default().with_context(input, MapValueObject::new(), MapValueObject::new())
+    }
+
+    pub fn with_context(
+        mut self,
+        input: MapValue,
+        parameters: MapValueObject,
+        services: MapValueObject,
+    ) -> Self {
+        self.context = Some(map_value!({
+            "input": input,
+            "parameters": MapValue::Object(parameters),
+            "services": MapValue::Object(services)
+        }));
+        self
+    }
+
+    /// Sets security applied to http calls which request it.
+    pub fn with_security(mut self, security: SecurityMap) -> Self {
+        self.security = Some(security);
+        self
+    }
+
+    pub fn mock_http(mut self, response: MockHttpResponse) -> Self {
+        self.http_mocks.push(response);
+        self
+    }
+
+    /// Returns responses which were not requested by the map.
+    pub fn unused_http_mocks(&self) -> &[MockHttpResponse] {
+        &self.http_mocks
+    }
+}
+impl MapStdUnstable for MockMapStd {
+    fn print(&mut self, message: &str) {
+        self.logs.push(RecordedLog {
+            level: None,
+            message: message.to_string(),
+        });
+    }
+
+    fn log(&mut self, level: LogLevel, message: &str, _fields: &MapValueObject) {
+        self.logs.push(RecordedLog {
+            level: Some(level),
+            message: message.to_string(),
+        });
+    }
+
+    fn stream_read(&mut self, handle: Handle, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.streams.get_mut(handle) {
+            None => Err(std::io::ErrorKind::NotFound.into()),
+            Some(stream) => stream.read(buf),
+        }
+    }
+
+    fn stream_write(&mut self, _handle: Handle, _buf: &[u8]) -> std::io::Result<usize> {
+        // response bodies are the only streams
+        Err(std::io::ErrorKind::Unsupported.into())
+    }
+
+    fn stream_close(&mut self, handle: Handle) -> std::io::Result<()> {
+        match self.streams.try_remove(handle) {
+            None => Err(std::io::ErrorKind::NotFound.into()),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn crypto_digest(&mut self, algorithm: &str, data: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        Ok(crypto::digest(DigestAlgorithm::parse(algorithm)?, data))
+    }
+
+    fn crypto_hmac(
+        &mut self,
+        algorithm: &str,
+        key: &[u8],
+        data: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        Ok(crypto::hmac(DigestAlgorithm::parse(algorithm)?, key, data))
+    }
+
+    fn crypto_random_fill(&mut self, buf: &mut [u8]) -> Result<(), CryptoError> {
+        crypto::random_fill(buf)
+    }
+
+    fn sleep(&mut self, duration: Duration) -> Result<(), SleepError> {
+        // tests don't wait, the duration is only recorded
+        self.sleeps.push(duration);
+        Ok(())
+    }
+
+    fn http_call(
+        &mut self,
+        mut params: HttpRequest,
+        security: Option<HttpRequestSecurity>,
+    ) -> Result<Handle, HttpCallError> {
+        if let Some(ref security) = security {
+            let security_map = self.security.as_ref().ok_or_else(|| {
+                HttpCallError::InvalidSecurityConfiguration(
+                    "No security is configured in the harness".to_string(),
+                )
+            })?;
+            resolve_security(security_map, &mut params, security)?;
+        }
+
+        let index = self
+            .http_mocks
+            .iter()
+            .position(|mock| mock.matches(&params))
+            .ok_or_else(|| {
+                HttpCallError::Failed(format!(
+                    "No mocked response for {} {}",
+                    params.method, params.url
+                ))
+            })?;
+        let response = self.http_mocks.remove(index);
+
+        self.requests.push(RecordedHttpRequest {
+            method: params.method,
+            url: params.url,
+            headers: params.headers,
+            query: params.query,
+            body: params.body,
+        });
+
+        Ok(self.http_requests.insert(response))
+    }
+
+    fn http_call_head(&mut self, handle: Handle) -> Result<HttpResponse, HttpCallHeadError> {
+        let response = self
+            .http_requests
+            .try_remove(handle)
+            .ok_or(HttpCallHeadError::InvalidHandle)?;
+
+        Ok(HttpResponse {
+            status: response.status,
+            headers: response.headers,
+            body_stream: self.streams.insert(Cursor::new(response.body)),
+        })
+    }
+
+    fn take_context(&mut self) -> Result<MapValue, TakeContextError> {
+        self.context.take().ok_or(TakeContextError::AlreadyTaken)
+    }
+
+    fn set_output_success(&mut self, output: MapValue) -> Result<(), SetOutputError> {
+        self.set_output(Ok(output))
+    }
+
+    fn set_output_failure(&mut self, output: MapValue) -> Result<(), SetOutputError> {
+        self.set_output(Err(output))
+    }
+}
+impl MockMapStd {
+    fn set_output(&mut self, output: Result<MapValue, MapValue>) -> Result<(), SetOutputError> {
+        if self.output.is_some() {
+            return Err(SetOutputError::AlreadySet);
+        }
+
+        self.output = Some(output);
+        Ok(())
+    }
+}
+impl MapStdFull for MockMapStd {
+    fn take_output(&mut self) -> Option<Result<MapValue, MapValue>> {
+        self.output.take()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request(method: &str, url: &str) -> HttpRequest {
+        HttpRequest {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: HeadersMultiMap::new(),
+            query: MultiMap::from([("page".to_string(), vec!["2".to_string()])]),
+            body: None,
+            redirect: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_scripted_http() {
+        let mut state = MockMapStd::new(MapValue::None)
+            .mock_http(MockHttpResponse::new("GET", "https://example.com/a").body("first"))
+            .mock_http(
+                MockHttpResponse::new("GET", "https://example.com/a")
+                    .status(404)
+                    .body("second"),
+            );
+
+        let handle = state
+            .http_call(request("get", "https://example.com/a"), None)
+            .unwrap();
+        let response = state.http_call_head(handle).unwrap();
+        assert_eq!(response.status, 200);
+
+        let mut body = [0u8; 16];
+        let read = state.stream_read(response.body_stream, &mut body).unwrap();
+        assert_eq!(&body[..read], b"first");
+        state.stream_close(response.body_stream).unwrap();
+
+        let handle = state
+            .http_call(request("GET", "https://example.com/a"), None)
+            .unwrap();
+        assert_eq!(state.http_call_head(handle).unwrap().status, 404);
+
+        assert!(matches!(
+            state.http_call(request("GET", "https://example.com/a"), None),
+            Err(HttpCallError::Failed(_))
+        ));
+        assert_eq!(state.requests.len(), 2);
+        assert_eq!(state.requests[0].query["page"], vec!["2".to_string()]);
+        assert!(state.unused_http_mocks().is_empty());
+    }
+
+    #[test]
+    fn test_context_and_output() {
+        let mut state = MockMapStd::new(MapValue::Bool(true));
+        assert!(state.take_context().is_ok());
+        assert!(state.take_context().is_err());
+
+        state.set_output_failure(MapValue::None).unwrap();
+        assert!(state.set_output_success(MapValue::None).is_err());
+        assert!(matches!(state.take_output(), Some(Err(MapValue::None))));
+    }
+}
@@ -0,0 +1,139 @@
+//! Serde helpers for byte fields of messages.
+//!
+//! Bytes are serialized as such, so that binary encodings can represent them natively. JSON represents them as an array of numbers,
+//! which is also accepted when deserializing.
+//!
+//! ```ignore
+//! #[serde(with = "wasm_abi::bytes")]
+//! data: Vec<u8>,
+//! #[serde(default, with = "wasm_abi::bytes::option")]
+//! body: Option<Vec<u8>>,
+//! ```
+
+use std::fmt;
+
+use serde::{
+    de::{SeqAccess, Visitor},
+    Deserializer, Serializer,
+};
+
+pub fn serialize<T: AsRef<[u8]>, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_bytes(value.as_ref())
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    deserializer.deserialize_bytes(BytesVisitor)
+}
+
+pub mod option {
+    use super::*;
+
+    pub fn serialize<T: AsRef<[u8]>, S: Serializer>(
+        value: &Option<T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match value {
+            None => serializer.serialize_none(),
+            Some(value) => serializer.serialize_some(&Bytes(value.as_ref())),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Vec<u8>>, D::Error> {
+        deserializer.deserialize_option(OptionBytesVisitor)
+    }
+
+    struct Bytes<'a>(&'a [u8]);
+    impl serde::Serialize for Bytes<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    struct OptionBytesVisitor;
+    impl<'de> Visitor<'de> for OptionBytesVisitor {
+        type Value = Option<Vec<u8>>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("optional bytes")
+        }
+
+        fn visit_none<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: serde::de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(
+            self,
+            deserializer: D,
+        ) -> Result<Self::Value, D::Error> {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+struct BytesVisitor;
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("bytes or an array of numbers")
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        #[serde(with = "super")]
+        data: Vec<u8>,
+        #[serde(default, with = "super::option")]
+        body: Option<Vec<u8>>,
+    }
+
+    #[test]
+    fn test_bytes_json_array() {
+        let message = Message {
+            data: vec![1, 2],
+            body: Some(vec![3]),
+        };
+
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json, serde_json::json!({ "data": [1, 2], "body": [3] }));
+        assert_eq!(serde_json::from_value::<Message>(json).unwrap(), message);
+
+        assert_eq!(
+            serde_json::from_value::<Message>(serde_json::json!({ "data": [] })).unwrap(),
+            Message {
+                data: vec![],
+                body: None
+            }
+        );
+    }
+}
@@ -5,7 +5,7 @@ use std::io;
 use serde::{de::DeserializeOwned, Serialize};
 use thiserror::Error;
 
-use super::{AbiResult, AbiResultRepr, Handle, Ptr, Size};
+use super::{AbiResult, AbiResultRepr, Handle, MessageEncoding, Ptr, Size};
 
 #[derive(Debug, Error)]
 pub enum JsonMessageError {
@@ -13,6 +13,12 @@ pub enum JsonMessageError {
     SerializeFailed(serde_json::Error),
     #[error("Failed to deserialize message: {0}")]
     DeserializeFailed(serde_json::Error),
+    #[error("Failed to serialize MessagePack message: {0}")]
+    MessagePackSerializeFailed(rmp_serde::encode::Error),
+    #[error("Failed to deserialize MessagePack message: {0}")]
+    MessagePackDeserializeFailed(rmp_serde::decode::Error),
+    #[error("Message \"{0}\" is not supported by the host")]
+    UnsupportedMessage(&'static str),
 }
 
 /// Implementation of a channel over which messages can be exchanged.
@@ -41,6 +47,38 @@ pub trait MessageExchange {
 
         Ok(response)
     }
+
+    /// Sends a message using [invoke](Self::invoke) by serializing and deserializing MessagePack.
+    fn invoke_msgpack<M: Serialize, R: DeserializeOwned>(
+        &self,
+        message: &M,
+    ) -> Result<R, JsonMessageError> {
+        let _span = tracing::trace_span!("host/MessageExchange::invoke_msgpack").entered();
+
+        let message = rmp_serde::to_vec_named(message)
+            .map_err(JsonMessageError::MessagePackSerializeFailed)?;
+
+        tracing::trace!(request_len = message.len());
+
+        let response = self.invoke(message.as_slice());
+
+        tracing::trace!(response_len = response.len());
+
+        rmp_serde::from_slice(response.as_slice())
+            .map_err(JsonMessageError::MessagePackDeserializeFailed)
+    }
+
+    /// Sends a message using [invoke](Self::invoke) in given `encoding`.
+    fn invoke_encoded<M: Serialize, R: DeserializeOwned>(
+        &self,
+        message: &M,
+        encoding: MessageEncoding,
+    ) -> Result<R, JsonMessageError> {
+        match encoding {
+            MessageEncoding::Json => self.invoke_json(message),
+            MessageEncoding::MessagePack => self.invoke_msgpack(message),
+        }
+    }
 }
 impl<E: MessageExchange + ?Sized> MessageExchange for &E {
     fn invoke(&self, message: &[u8]) -> Vec<u8> {
@@ -437,6 +475,33 @@ mod test {
         assert_eq!(response.f2, long_string);
     }
 
+    #[derive(Serialize, Deserialize)]
+    struct TestBytesMsg {
+        #[serde(with = "crate::bytes")]
+        data: Vec<u8>,
+    }
+
+    struct EchoMessageExchange;
+    impl MessageExchange for EchoMessageExchange {
+        fn invoke(&self, message: &[u8]) -> Vec<u8> {
+            message.to_vec()
+        }
+    }
+
+    #[test]
+    fn test_invoke_message_encoded_roundtrip() {
+        for encoding in MessageEncoding::ALL.iter().copied() {
+            let message = TestBytesMsg {
+                data: vec![0, 1, 255],
+            };
+            let response = EchoMessageExchange
+                .invoke_encoded::<TestBytesMsg, TestBytesMsg>(&message, encoding)
+                .unwrap();
+
+            assert_eq!(response.data, vec![0, 1, 255]);
+        }
+    }
+
     extern "C" fn test_stream_read(
         handle: Handle,
         mut out_ptr: Ptr<u8>,
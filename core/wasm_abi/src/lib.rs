@@ -13,7 +13,7 @@
 //! This handle can be used to retrieve the message with a second call. By selecting a reasonable buffer size, the caller can avoid
 //! most secondary calls.
 //!
-//! The format of messages is UTF-8 JSON.
+//! The format of messages is UTF-8 JSON, unless MessagePack was negotiated (see [MessageEncoding]).
 //!
 //! ## Streams
 //!
@@ -21,7 +21,9 @@
 //! taken from WASI `fd_read` and `fd_write`. See <https://github.com/WebAssembly/WASI/blob/main/phases/snapshot/docs.md#fd_read>.
 
 mod bits;
+pub mod bytes;
 mod exchange;
+mod negotiation;
 mod result;
 
 pub use self::{
@@ -30,6 +32,10 @@ pub use self::{
         JsonMessageError, MessageExchange, MessageExchangeFfiFn, StaticMessageExchange,
         StaticStreamExchange, StreamExchange, StreamExchangeFfiFn,
     },
+    negotiation::{
+        is_message_supported, message_encoding, set_message_encoding, set_supported_message_kinds,
+        MessageEncoding,
+    },
     result::{err_from_wasi_errno, AbiResult, AbiResultRepr},
 };
 
@@ -42,7 +48,8 @@ pub use exchange::testing;
 /// the struct and its fields. Defines a response enum with newtype or struct variants and kebab-case kind.
 ///
 /// Defines a `new` method which automatically fills `kind` field.
-/// Implements trait `MessageExchange`.
+/// Implements trait `MessageExchange`, `send_in` uses the negotiated [MessageEncoding] and `send_json_in` always JSON. Sending fails with [JsonMessageError::UnsupportedMessage] if the kind was not negotiated,
+/// see [set_supported_message_kinds].
 ///
 /// For example, the following defines `InMessage` with kind `my-kind` and two fields and defines a response `OutMessage` enum with three
 /// variants with kinds `ok`, `try-again`, and `err`, each with different number of fields.
@@ -112,7 +119,21 @@ macro_rules! define_exchange {
                 }
             }
 
+            /// Sends the message in the negotiated encoding.
+            pub fn send_in<E: $crate::MessageExchange>(&self, message_exchange: E) -> Result<$response_name, $crate::JsonMessageError> {
+                if !$crate::is_message_supported(Self::KIND) {
+                    return Err($crate::JsonMessageError::UnsupportedMessage(Self::KIND));
+                }
+
+                message_exchange.invoke_encoded(self, $crate::message_encoding())
+            }
+
+            /// Sends the message as JSON regardless of the negotiated encoding.
             pub fn send_json_in<E: $crate::MessageExchange>(&self, message_exchange: E) -> Result<$response_name, $crate::JsonMessageError> {
+                if !$crate::is_message_supported(Self::KIND) {
+                    return Err($crate::JsonMessageError::UnsupportedMessage(Self::KIND));
+                }
+
                 message_exchange.invoke_json(self)
             }
         }
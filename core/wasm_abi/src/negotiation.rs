@@ -0,0 +1,50 @@
+//! Message kinds and encoding negotiated with the other side of the exchange.
+
+use std::{collections::BTreeSet, sync::RwLock};
+
+use serde::{Deserialize, Serialize};
+
+/// Encoding of messages sent with exchanges defined by [define_exchange](crate::define_exchange).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MessageEncoding {
+    /// UTF-8 JSON, bytes are encoded as arrays of numbers.
+    #[default]
+    #[serde(rename = "json")]
+    Json,
+    /// MessagePack with named fields, bytes are encoded as `bin`.
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+impl MessageEncoding {
+    /// All encodings in order of preference.
+    pub const ALL: &'static [MessageEncoding] =
+        &[MessageEncoding::MessagePack, MessageEncoding::Json];
+}
+
+static MESSAGE_ENCODING: RwLock<MessageEncoding> = RwLock::new(MessageEncoding::Json);
+static SUPPORTED_MESSAGE_KINDS: RwLock<Option<BTreeSet<String>>> = RwLock::new(None);
+
+/// Restricts messages sent with exchanges defined by [define_exchange](crate::define_exchange) to `kinds`.
+///
+/// `None` allows all messages, which is the default until the kinds are negotiated.
+pub fn set_supported_message_kinds(kinds: Option<BTreeSet<String>>) {
+    *SUPPORTED_MESSAGE_KINDS.write().unwrap() = kinds;
+}
+
+/// Returns whether a message of `kind` can be sent.
+pub fn is_message_supported(kind: &str) -> bool {
+    match SUPPORTED_MESSAGE_KINDS.read().unwrap().as_ref() {
+        None => true,
+        Some(kinds) => kinds.contains(kind),
+    }
+}
+
+/// Sets encoding of messages sent with exchanges defined by [define_exchange](crate::define_exchange).
+pub fn set_message_encoding(encoding: MessageEncoding) {
+    *MESSAGE_ENCODING.write().unwrap() = encoding;
+}
+
+/// Returns encoding of messages, JSON until negotiated otherwise.
+pub fn message_encoding() -> MessageEncoding {
+    *MESSAGE_ENCODING.read().unwrap()
+}
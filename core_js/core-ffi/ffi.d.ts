@@ -13,8 +13,37 @@ declare type StdFfi = {
     bytes_to_base64(bytes: ArrayBuffer, url_safe: boolean): string,
     base64_to_bytes(base64: string, url_safe: boolean): ArrayBuffer,
     record_to_urlencoded(value: Record<string, string[]>): string,
+    // web apis
+    utf8_decode(bytes: ArrayBuffer, fatal: boolean, ignore_bom: boolean): string,
+    btoa(data: string): string,
+    atob(data: string): string,
+    url_parse(input: string, base?: string): UrlComponents,
+    url_set(href: string, component: keyof UrlComponents, value: string): UrlComponents,
+    urlencoded_to_entries(query: string): [string, string][],
+    entries_to_urlencoded(entries: [string, string][]): string,
+    // dates
+    date_parse(input: string, format: string): number,
+    date_format(timestamp: number, format: string, offset_minutes: number): string,
+    // crypto
+    crypto_digest(algorithm: string, data: ArrayBuffer): ArrayBuffer,
+    crypto_hmac(algorithm: string, key: ArrayBuffer, data: ArrayBuffer): ArrayBuffer,
+    crypto_random_fill(out: ArrayBuffer): void,
     // env
     print(message: string): void,
+    log(level: string, message: string, fields: string): void,
     printDebug(...data: unknown[]): void
   }
 };
+declare type UrlComponents = {
+  href: string,
+  origin: string,
+  protocol: string,
+  username: string,
+  password: string,
+  host: string,
+  hostname: string,
+  port: string,
+  pathname: string,
+  search: string,
+  hash: string
+};
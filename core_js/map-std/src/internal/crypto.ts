@@ -0,0 +1,134 @@
+// Web Crypto subset backed by the core, see `map_std::unstable::crypto`.
+
+type BufferSource = ArrayBuffer | ArrayBufferView;
+type IntegerTypedArray = Int8Array | Uint8Array | Uint8ClampedArray | Int16Array | Uint16Array | Int32Array | Uint32Array | BigInt64Array | BigUint64Array;
+type DigestAlgorithmIdentifier = string | { name: string };
+type HmacImportParams = { name: 'HMAC', hash: DigestAlgorithmIdentifier };
+
+const RANDOM_MAX_LENGTH = 65536;
+
+function toArrayBuffer(input: BufferSource): ArrayBuffer {
+  if (input instanceof ArrayBuffer) {
+    return input;
+  }
+
+  return input.buffer.slice(input.byteOffset, input.byteOffset + input.byteLength);
+}
+
+function algorithmName(algorithm: DigestAlgorithmIdentifier): string {
+  return typeof algorithm === 'string' ? algorithm : algorithm.name;
+}
+
+/** Computes digest of `data` synchronously, `algorithm` is one of `SHA-256`, `SHA-384` or `SHA-512`. */
+export function digest(algorithm: DigestAlgorithmIdentifier, data: BufferSource): ArrayBuffer {
+  return __ffi.unstable.crypto_digest(algorithmName(algorithm), toArrayBuffer(data));
+}
+
+/** Computes HMAC of `data` synchronously, `algorithm` is the hash function as in `digest`. */
+export function hmac(algorithm: DigestAlgorithmIdentifier, key: BufferSource, data: BufferSource): ArrayBuffer {
+  return __ffi.unstable.crypto_hmac(algorithmName(algorithm), toArrayBuffer(key), toArrayBuffer(data));
+}
+
+export function getRandomValues<T extends IntegerTypedArray>(array: T): T {
+  if (array.byteLength > RANDOM_MAX_LENGTH) {
+    const error = new Error(`The requested length ${array.byteLength} exceeds the maximum of ${RANDOM_MAX_LENGTH} bytes`);
+    error.name = 'QuotaExceededError';
+    throw error;
+  }
+
+  const random = new ArrayBuffer(array.byteLength);
+  __ffi.unstable.crypto_random_fill(random);
+  new Uint8Array(array.buffer, array.byteOffset, array.byteLength).set(new Uint8Array(random));
+
+  return array;
+}
+
+export function randomUUID(): string {
+  const bytes = getRandomValues(new Uint8Array(16));
+  // version 4, variant 10
+  bytes[6] = (bytes[6] & 0x0f) | 0x40;
+  bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+  const hex = Array.from(bytes, byte => byte.toString(16).padStart(2, '0')).join('');
+  return `${hex.slice(0, 8)}-${hex.slice(8, 12)}-${hex.slice(12, 16)}-${hex.slice(16, 20)}-${hex.slice(20)}`;
+}
+
+export class CryptoKey {
+  readonly type = 'secret';
+
+  /** @internal */
+  constructor(
+    readonly algorithm: { name: 'HMAC', hash: { name: string } },
+    readonly extractable: boolean,
+    readonly usages: string[],
+    /** @internal */
+    readonly _keyData: ArrayBuffer
+  ) { }
+}
+
+function hmacKey(algorithm: DigestAlgorithmIdentifier, key: CryptoKey, usage: string): CryptoKey {
+  if (algorithmName(algorithm).toUpperCase() !== 'HMAC' || key.algorithm.name !== 'HMAC') {
+    throw new TypeError(`Unsupported algorithm "${algorithmName(algorithm)}", only HMAC is supported`);
+  }
+  if (!key.usages.includes(usage)) {
+    throw new TypeError(`Key does not allow "${usage}"`);
+  }
+
+  return key;
+}
+
+/**
+ * Subset of `SubtleCrypto`: `digest` and raw `HMAC` keys for `sign` and `verify`.
+ *
+ * Results are returned as already settled promises for compatibility, use `digest` and `hmac` in maps which cannot await.
+ */
+export class SubtleCrypto {
+  async digest(algorithm: DigestAlgorithmIdentifier, data: BufferSource): Promise<ArrayBuffer> {
+    return digest(algorithm, data);
+  }
+
+  async importKey(format: 'raw', keyData: BufferSource, algorithm: HmacImportParams, extractable: boolean, usages: string[]): Promise<CryptoKey> {
+    if (format !== 'raw') {
+      throw new TypeError(`Unsupported key format "${format}", only raw is supported`);
+    }
+    if (algorithmName(algorithm).toUpperCase() !== 'HMAC') {
+      throw new TypeError(`Unsupported algorithm "${algorithmName(algorithm)}", only HMAC is supported`);
+    }
+
+    return new CryptoKey(
+      { name: 'HMAC', hash: { name: algorithmName(algorithm.hash) } },
+      extractable,
+      usages,
+      toArrayBuffer(keyData).slice(0)
+    );
+  }
+
+  async sign(algorithm: DigestAlgorithmIdentifier, key: CryptoKey, data: BufferSource): Promise<ArrayBuffer> {
+    hmacKey(algorithm, key, 'sign');
+
+    return hmac(key.algorithm.hash, key._keyData, data);
+  }
+
+  async verify(algorithm: DigestAlgorithmIdentifier, key: CryptoKey, signature: BufferSource, data: BufferSource): Promise<boolean> {
+    hmacKey(algorithm, key, 'verify');
+
+    const expected = new Uint8Array(hmac(key.algorithm.hash, key._keyData, data));
+    const actual = new Uint8Array(toArrayBuffer(signature));
+    if (expected.length !== actual.length) {
+      return false;
+    }
+
+    // constant time comparison
+    let difference = 0;
+    for (let i = 0; i < expected.length; i += 1) {
+      difference |= expected[i] ^ actual[i];
+    }
+    return difference === 0;
+  }
+}
+
+export const crypto = {
+  getRandomValues,
+  randomUUID,
+  subtle: new SubtleCrypto()
+};
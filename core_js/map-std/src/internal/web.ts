@@ -0,0 +1,344 @@
+// Web API globals backed by the core, see `core_to_map_bindings::web`.
+
+function toArrayBuffer(input: ArrayBuffer | ArrayBufferView): ArrayBuffer {
+  if (input instanceof ArrayBuffer) {
+    return input;
+  }
+
+  // TODO: Javy doesn't support TypedArrays in ffi, so we pass a copy of the viewed range
+  return input.buffer.slice(input.byteOffset, input.byteOffset + input.byteLength);
+}
+
+const UTF8_LABELS = ['unicode-1-1-utf-8', 'unicode11utf8', 'unicode20utf8', 'utf-8', 'utf8', 'x-unicode20utf8'];
+
+export class TextEncoder {
+  get encoding(): string {
+    return 'utf-8';
+  }
+
+  encode(input: string = ''): Uint8Array {
+    return new Uint8Array(__ffi.unstable.utf8_to_bytes(String(input)));
+  }
+
+  encodeInto(source: string, destination: Uint8Array): { read: number, written: number } {
+    let read = 0;
+    let written = 0;
+    for (const char of source) {
+      const codePoint = char.codePointAt(0)!;
+      const length = codePoint < 0x80 ? 1 : codePoint < 0x800 ? 2 : codePoint < 0x10000 ? 3 : 4;
+      if (written + length > destination.length) {
+        break;
+      }
+
+      destination.set(this.encode(char), written);
+      read += char.length;
+      written += length;
+    }
+
+    return { read, written };
+  }
+}
+
+export class TextDecoder {
+  readonly fatal: boolean;
+  readonly ignoreBOM: boolean;
+
+  constructor(label: string = 'utf-8', options: { fatal?: boolean, ignoreBOM?: boolean } = {}) {
+    if (!UTF8_LABELS.includes(String(label).trim().toLowerCase())) {
+      throw new RangeError(`The encoding label provided ('${label}') is not supported, only utf-8 is supported`);
+    }
+
+    this.fatal = options.fatal ?? false;
+    this.ignoreBOM = options.ignoreBOM ?? false;
+  }
+
+  get encoding(): string {
+    return 'utf-8';
+  }
+
+  decode(input?: ArrayBuffer | ArrayBufferView, options: { stream?: boolean } = {}): string {
+    if (options.stream === true) {
+      throw new TypeError('Streaming decode is not supported');
+    }
+    if (input === undefined) {
+      return '';
+    }
+
+    return __ffi.unstable.utf8_decode(toArrayBuffer(input), this.fatal, this.ignoreBOM);
+  }
+}
+
+export function btoa(data: string): string {
+  return __ffi.unstable.btoa(String(data));
+}
+
+export function atob(data: string): string {
+  return __ffi.unstable.atob(String(data));
+}
+
+type URLSearchParamsInit = string | [string, string][] | Record<string, string> | URLSearchParams;
+
+export class URLSearchParams {
+  #entries: [string, string][];
+  #onUpdate: ((query: string) => void) | undefined;
+
+  constructor(init: URLSearchParamsInit = '') {
+    if (init instanceof URLSearchParams) {
+      this.#entries = init.#entries.map(([name, value]) => [name, value]);
+    } else if (Array.isArray(init)) {
+      this.#entries = init.map(pair => {
+        if (pair.length !== 2) {
+          throw new TypeError('Each query pair must be an iterable [name, value] tuple');
+        }
+        return [String(pair[0]), String(pair[1])];
+      });
+    } else if (typeof init === 'object' && init !== null) {
+      this.#entries = Object.entries(init).map(([name, value]) => [name, String(value)]);
+    } else {
+      this.#entries = URLSearchParams.#parse(String(init));
+    }
+  }
+
+  static #parse(query: string): [string, string][] {
+    return __ffi.unstable.urlencoded_to_entries(query.startsWith('?') ? query.slice(1) : query);
+  }
+
+  /** @internal Links these params to the query of an URL. */
+  static _link(params: URLSearchParams, onUpdate: (query: string) => void) {
+    params.#onUpdate = onUpdate;
+  }
+
+  /** @internal Replaces the entries after the query of the linked URL changed. */
+  static _reset(params: URLSearchParams, query: string) {
+    params.#entries = URLSearchParams.#parse(query);
+  }
+
+  #update() {
+    this.#onUpdate?.(this.toString());
+  }
+
+  get size(): number {
+    return this.#entries.length;
+  }
+
+  append(name: string, value: string) {
+    this.#entries.push([String(name), String(value)]);
+    this.#update();
+  }
+
+  delete(name: string, value?: string) {
+    this.#entries = this.#entries.filter(([n, v]) => !(n === name && (value === undefined || v === value)));
+    this.#update();
+  }
+
+  get(name: string): string | null {
+    return this.#entries.find(([n]) => n === name)?.[1] ?? null;
+  }
+
+  getAll(name: string): string[] {
+    return this.#entries.filter(([n]) => n === name).map(([_, v]) => v);
+  }
+
+  has(name: string, value?: string): boolean {
+    return this.#entries.some(([n, v]) => n === name && (value === undefined || v === value));
+  }
+
+  set(name: string, value: string) {
+    const index = this.#entries.findIndex(([n]) => n === name);
+    if (index === -1) {
+      this.#entries.push([String(name), String(value)]);
+    } else {
+      this.#entries[index] = [String(name), String(value)];
+      this.#entries = this.#entries.filter(([n], i) => n !== name || i <= index);
+    }
+    this.#update();
+  }
+
+  sort() {
+    // stable sort by code units as required by the spec
+    this.#entries = this.#entries
+      .map((entry, index) => ({ entry, index }))
+      .sort((a, b) => a.entry[0] < b.entry[0] ? -1 : a.entry[0] > b.entry[0] ? 1 : a.index - b.index)
+      .map(({ entry }) => entry);
+    this.#update();
+  }
+
+  forEach(callback: (value: string, name: string, params: URLSearchParams) => void, thisArg?: unknown) {
+    for (const [name, value] of this.#entries) {
+      callback.call(thisArg, value, name, this);
+    }
+  }
+
+  *keys(): IterableIterator<string> {
+    for (const [name] of this.#entries) {
+      yield name;
+    }
+  }
+
+  *values(): IterableIterator<string> {
+    for (const [_, value] of this.#entries) {
+      yield value;
+    }
+  }
+
+  *entries(): IterableIterator<[string, string]> {
+    for (const [name, value] of this.#entries) {
+      yield [name, value];
+    }
+  }
+
+  [Symbol.iterator](): IterableIterator<[string, string]> {
+    return this.entries();
+  }
+
+  toString(): string {
+    return __ffi.unstable.entries_to_urlencoded(this.#entries);
+  }
+}
+
+export class URL {
+  #components: UrlComponents;
+  #searchParams: URLSearchParams;
+
+  constructor(url: string | URL, base?: string | URL) {
+    this.#components = __ffi.unstable.url_parse(String(url), base === undefined ? undefined : String(base));
+    this.#searchParams = new URLSearchParams(this.#components.search);
+    URLSearchParams._link(this.#searchParams, query => {
+      this.#components = __ffi.unstable.url_set(this.href, 'search', query);
+    });
+  }
+
+  static canParse(url: string | URL, base?: string | URL): boolean {
+    try {
+      new URL(url, base);
+      return true;
+    } catch {
+      return false;
+    }
+  }
+
+  #set(component: keyof UrlComponents, value: string) {
+    this.#components = __ffi.unstable.url_set(this.href, component, String(value));
+    if (component === 'href' || component === 'search') {
+      URLSearchParams._reset(this.#searchParams, this.#components.search);
+    }
+  }
+
+  get href(): string { return this.#components.href; }
+  set href(value: string) { this.#set('href', value); }
+
+  get origin(): string { return this.#components.origin; }
+
+  get protocol(): string { return this.#components.protocol; }
+  set protocol(value: string) { this.#set('protocol', value); }
+
+  get username(): string { return this.#components.username; }
+  set username(value: string) { this.#set('username', value); }
+
+  get password(): string { return this.#components.password; }
+  set password(value: string) { this.#set('password', value); }
+
+  get host(): string { return this.#components.host; }
+  set host(value: string) { this.#set('host', value); }
+
+  get hostname(): string { return this.#components.hostname; }
+  set hostname(value: string) { this.#set('hostname', value); }
+
+  get port(): string { return this.#components.port; }
+  set port(value: string) { this.#set('port', value); }
+
+  get pathname(): string { return this.#components.pathname; }
+  set pathname(value: string) { this.#set('pathname', value); }
+
+  get search(): string { return this.#components.search; }
+  set search(value: string) { this.#set('search', value); }
+
+  get hash(): string { return this.#components.hash; }
+  set hash(value: string) { this.#set('hash', value); }
+
+  get searchParams(): URLSearchParams {
+    return this.#searchParams;
+  }
+
+  toString(): string {
+    return this.href;
+  }
+
+  toJSON(): string {
+    return this.href;
+  }
+}
+
+function dataCloneError(message: string): Error {
+  const error = new Error(message);
+  error.name = 'DataCloneError';
+  return error;
+}
+
+/** Deep clones `value` using the structured clone algorithm, supporting cycles and the common built-in types. */
+export function structuredClone<T>(value: T): T {
+  const memory = new Map<unknown, unknown>();
+
+  function clone(value: unknown): unknown {
+    if (typeof value === 'function' || typeof value === 'symbol') {
+      throw dataCloneError(`${String(value)} could not be cloned`);
+    }
+    if (typeof value !== 'object' || value === null) {
+      return value;
+    }
+    if (memory.has(value)) {
+      return memory.get(value);
+    }
+
+    let result: any;
+    if (value instanceof Date) {
+      result = new Date(value.getTime());
+    } else if (value instanceof RegExp) {
+      result = new RegExp(value.source, value.flags);
+    } else if (value instanceof ArrayBuffer) {
+      result = value.slice(0);
+    } else if (ArrayBuffer.isView(value)) {
+      const buffer = clone(value.buffer) as ArrayBuffer;
+      const View = value.constructor as any;
+      const length = value instanceof DataView ? value.byteLength : (value as any).length;
+      result = new View(buffer, value.byteOffset, length);
+    } else if (value instanceof Map) {
+      result = new Map();
+      memory.set(value, result);
+      for (const [k, v] of value) {
+        result.set(clone(k), clone(v));
+      }
+      return result;
+    } else if (value instanceof Set) {
+      result = new Set();
+      memory.set(value, result);
+      for (const v of value) {
+        result.add(clone(v));
+      }
+      return result;
+    } else if (value instanceof Error) {
+      result = new Error(value.message);
+      result.name = value.name;
+      if (value.stack !== undefined) {
+        result.stack = value.stack;
+      }
+    } else if (Array.isArray(value)) {
+      result = new Array(value.length);
+    } else if (Object.getPrototypeOf(value) === Object.prototype || Object.getPrototypeOf(value) === null) {
+      result = {};
+    } else {
+      throw dataCloneError(`${Object.prototype.toString.call(value)} could not be cloned`);
+    }
+
+    memory.set(value, result);
+    if (Array.isArray(value) || !(value instanceof Date || value instanceof RegExp || value instanceof ArrayBuffer || ArrayBuffer.isView(value) || value instanceof Error)) {
+      for (const key of Object.keys(value)) {
+        result[key] = clone((value as any)[key]);
+      }
+    }
+
+    return result;
+  }
+
+  return clone(value) as T;
+}
@@ -1,5 +1,7 @@
 import { Buffer as NodeBuffer } from './internal/node_buffer';
 import * as unstable from './unstable';
+import * as web from './internal/web';
+import { crypto as webCrypto } from './internal/crypto';
 
 declare global {
   // types
@@ -40,18 +42,32 @@ declare global {
     unstable: typeof unstable
   };
   var Buffer: typeof NodeBuffer;
+  var TextEncoder: typeof web.TextEncoder;
+  var TextDecoder: typeof web.TextDecoder;
+  var URL: typeof web.URL;
+  var URLSearchParams: typeof web.URLSearchParams;
+  var atob: typeof web.atob;
+  var btoa: typeof web.btoa;
+  var structuredClone: typeof web.structuredClone;
+  var crypto: typeof webCrypto;
   // functions
-  function _start(useCaseName: string): void;
+  function _start(useCaseName: string, mapModule?: Record<string, unknown>): void;
 };
 globalThis.std = { unstable };
 globalThis.Buffer = NodeBuffer;
+globalThis.TextEncoder = web.TextEncoder;
+globalThis.TextDecoder = web.TextDecoder;
+globalThis.URL = web.URL;
+globalThis.URLSearchParams = web.URLSearchParams;
+globalThis.atob = web.atob;
+globalThis.btoa = web.btoa;
+globalThis.structuredClone = web.structuredClone;
+globalThis.crypto = webCrypto;
 
-globalThis._start = function _start(useCaseName: string): void {
-  const context = globalThis.std.unstable.takeContext() as Record<string, unstable.AnyValue>;
-
+function findScriptUseCase(useCaseName: string): UseCase<UseCaseOptions> {
   // search for the use case as a freestanding function
   // TODO: this is best-effort - these are functions currently visible in the global scope
-  const globalSymbols = new Set(['_start', 'Object', 'Function', 'Error', 'EvalError', 'RangeError', 'ReferenceError', 'SyntaxError', 'TypeError', 'URIError', 'InternalError', 'AggregateError', 'Array', 'parseInt', 'parseFloat', 'isNaN', 'isFinite', 'decodeURI', 'decodeURIComponent', 'encodeURI', 'encodeURIComponent', 'escape', 'unescape', '__date_clock', 'Number', 'Boolean', 'String', 'Symbol', 'eval', 'Date', 'RegExp', 'Proxy', 'Map', 'Set', 'WeakMap', 'WeakSet', 'ArrayBuffer', 'SharedArrayBuffer', 'Uint8ClampedArray', 'Int8Array', 'Uint8Array', 'Int16Array', 'Uint16Array', 'Int32Array', 'Uint32Array', 'BigInt64Array', 'BigUint64Array', 'Float32Array', 'Float64Array', 'DataView', 'Promise', 'BigInt', 'Buffer']);
+  const globalSymbols = new Set(['_start', 'Object', 'Function', 'Error', 'EvalError', 'RangeError', 'ReferenceError', 'SyntaxError', 'TypeError', 'URIError', 'InternalError', 'AggregateError', 'Array', 'parseInt', 'parseFloat', 'isNaN', 'isFinite', 'decodeURI', 'decodeURIComponent', 'encodeURI', 'encodeURIComponent', 'escape', 'unescape', '__date_clock', 'Number', 'Boolean', 'String', 'Symbol', 'eval', 'Date', 'RegExp', 'Proxy', 'Map', 'Set', 'WeakMap', 'WeakSet', 'ArrayBuffer', 'SharedArrayBuffer', 'Uint8ClampedArray', 'Int8Array', 'Uint8Array', 'Int16Array', 'Uint16Array', 'Int32Array', 'Uint32Array', 'BigInt64Array', 'BigUint64Array', 'Float32Array', 'Float64Array', 'DataView', 'Promise', 'BigInt', 'Buffer', 'TextEncoder', 'TextDecoder', 'URL', 'URLSearchParams', 'atob', 'btoa', 'structuredClone', 'crypto']);
   const usecases = Object.getOwnPropertyNames(globalThis).filter(
     v => typeof (globalThis as any)[v] === 'function' && !globalSymbols.has(v)
   );
@@ -59,7 +75,22 @@ globalThis._start = function _start(useCaseName: string): void {
     throw new Error(`Use case ${useCaseName} not defined, use cases: ${usecases.join(', ')}`);
   }
 
-  const usecase: UseCase<UseCaseOptions> = (globalThis as any)[useCaseName];
+  return (globalThis as any)[useCaseName];
+}
+
+function findModuleUseCase(mapModule: Record<string, unknown>, useCaseName: string): UseCase<UseCaseOptions> {
+  const usecases = Object.keys(mapModule).filter(v => typeof mapModule[v] === 'function');
+  if (!usecases.includes(useCaseName)) {
+    throw new Error(`Use case ${useCaseName} not exported, use cases: ${usecases.join(', ')}`);
+  }
+
+  return mapModule[useCaseName] as UseCase<UseCaseOptions>;
+}
+
+/** Entry point called by the interpreter, `mapModule` is the namespace of maps written as ES modules. */
+globalThis._start = function _start(useCaseName: string, mapModule?: Record<string, unknown>): void {
+  const context = globalThis.std.unstable.takeContext() as Record<string, unstable.AnyValue>;
+  const usecase = mapModule === undefined ? findScriptUseCase(useCaseName) : findModuleUseCase(mapModule, useCaseName);
 
   try {
     const result = usecase({
@@ -5,6 +5,7 @@ import type { MultiMap } from './internal/types';
 import { Buffer } from './internal/node_buffer';
 
 export type { MultiMap, Encoding } from './internal/types';
+export { digest, hmac } from './internal/crypto';
 
 export type FetchOptions = {
   method?: string,
@@ -13,7 +14,13 @@ export type FetchOptions = {
   body?: AnyValue,
   /** Security configs to apply to this request. Specifying a string is equal to using `first-valid` */
   security?: string | { kind: 'first-valid', ids: string[] } | { kind: 'all', ids: string[] },
+  /** How to handle redirect responses. Specifying a string is equal to using the default `maxHops` of 20.
+   * 
+   * In `manual` mode the redirect response is returned as-is, including its `Location` header.
+   */
+  redirect?: RedirectMode | { mode: RedirectMode, maxHops?: number },
 };
+export type RedirectMode = 'follow' | 'manual' | 'error';
 
 // Can't use Record<string, AnyValue> but can use { [s in string]: AnyValue }. Typescript go brr.
 /** Any value that can be safely passed in and out of a map.
@@ -116,6 +123,47 @@ export function print(message: unknown) {
   }
 }
 
+export type LogLevel = 'trace' | 'debug' | 'info' | 'warn' | 'error';
+/** Logs `message` at `level` with structured `fields`, logs are correlated with the perform and its http transactions. */
+export function log(level: LogLevel, message: string, fields: Record<string, AnyValue> = {}) {
+  __ffi.unstable.log(level, String(message), JSON.stringify(fields ?? {}, jsonReplacerMapValue));
+}
+
+// timers
+/**
+ * Blocks the map for `milliseconds` while the host waits, use for polling and backoff.
+ *
+ * Sleeps longer than the configured maximum fail, cancelling the perform doesn't interrupt a sleep in progress.
+ */
+export function sleep(milliseconds: number) {
+  if (!Number.isFinite(milliseconds)) {
+    throw new Error(`Sleep duration must be a finite number of milliseconds, got ${milliseconds}`);
+  }
+
+  const response = messageExchange({
+    kind: 'sleep',
+    duration_ms: Math.max(0, Math.trunc(milliseconds))
+  });
+
+  if (response.kind === 'ok') {
+    return;
+  } else {
+    throw responseErrorToError(response);
+  }
+}
+
+// dates
+export type DateFormat = 'iso8601' | 'rfc2822';
+/** Parses an ISO 8601 or RFC 2822 date, ISO 8601 dates without an offset are treated as UTC. */
+export function parseDate(input: string, format: DateFormat = 'iso8601'): Date {
+  return new Date(__ffi.unstable.date_parse(String(input), format));
+}
+/** Formats `date` as ISO 8601 or RFC 2822 in the timezone `offsetMinutes` east of UTC. */
+export function formatDate(date: Date | number, format: DateFormat = 'iso8601', offsetMinutes: number = 0): string {
+  const timestamp = typeof date === 'number' ? date : date.getTime();
+  return __ffi.unstable.date_format(timestamp, format, Math.trunc(offsetMinutes));
+}
+
 // input and output
 export function takeContext(): AnyValue {
   const response = messageExchange({
@@ -208,6 +256,11 @@ export function fetch(url: string, options: FetchOptions): HttpRequest {
     security = { kind: "first-valid", ids: [security] }
   }
 
+  let redirect = options.redirect;
+  if (typeof redirect === "string") {
+    redirect = { mode: redirect };
+  }
+
   const response = messageExchange({
     kind: 'http-call',
     method: options.method ?? 'GET',
@@ -216,6 +269,7 @@ export function fetch(url: string, options: FetchOptions): HttpRequest {
     query: ensureMultimap(options.query ?? {}),
     body: finalBody,
     security,
+    redirect: redirect !== undefined ? { mode: redirect.mode, max_hops: redirect.maxHops } : undefined,
   });
 
   if (response.kind === 'ok') {
@@ -5,6 +5,7 @@ import { App, HandleMap, UnexpectedError } from "./common/index.js";
 import coreModule from "../assets/core-async.wasm";
 import type {
   FileSystem,
+  HttpTransport,
   Network,
   SecurityValuesMap,
   TextCoder,
@@ -113,7 +114,12 @@ class CfwTimers implements Timers {
   }
 }
 class CfwNetwork implements Network {
-  async fetch(input: RequestInfo, init?: RequestInit): Promise<Response> {
+  async fetch(input: RequestInfo, init?: RequestInit, transport?: HttpTransport): Promise<Response> {
+    if (transport?.proxyUrl !== undefined || transport?.caPem !== undefined || transport?.clientCertificate !== undefined) {
+      // Workers fetch cannot be configured with a proxy or custom TLS settings
+      throw new HostError(ErrorCode.NetworkError, "Proxy, CA and client certificate transport settings are not supported by this host");
+    }
+
     let response;
     try {
       response = await fetch(input, init);
@@ -5,7 +5,7 @@ import { createRequire } from 'module';
 import { WASI } from 'wasi';
 
 import { App } from './app.js';
-import { UnexpectedError, ValidationError } from './error.js';
+import { PerformError, UnexpectedError, ValidationError } from './error.js';
 import { FileSystem, Persistence, Network, TextCoder, Timers } from './interfaces.js';
 
 
@@ -158,4 +158,44 @@ describe('App', () => {
       {},
     )).rejects.toThrowError(ValidationError);
   });
-});
\ No newline at end of file
+
+  test('scenario error', async () => {
+    handleMessage.mockRestore();
+
+    await expect(app.perform(
+      '',
+      '',
+      '',
+      'Scenario',
+      { '$mock_scenario': { outcome: { kind: 'error', error: { title: 'Not found' } } } },
+      {},
+      {},
+    )).rejects.toThrowError(PerformError);
+  });
+
+  test('scenario exception', async () => {
+    handleMessage.mockRestore();
+
+    await expect(app.perform(
+      '',
+      '',
+      '',
+      'Scenario',
+      { '$mock_scenario': { outcome: { kind: 'exception', exception: { error_code: 'Cancelled', message: 'Test cancel' } } } },
+      {},
+      {},
+    )).rejects.toThrowError(UnexpectedError);
+  });
+
+  test('describe', async () => {
+    handleMessage.mockRestore();
+
+    await expect(app.describe({ profileUrl: 'profile' })).resolves.toEqual({});
+  });
+
+  test('describe exception', async () => {
+    handleMessage.mockRestore();
+
+    await expect(app.describe({ profileUrl: 'CORE_DESCRIBE_EXCEPTION' })).rejects.toThrowError(UnexpectedError);
+  });
+});
@@ -2,6 +2,7 @@ import type { SecurityValuesMap } from "./security.js";
 import {
   AppContext,
   FileSystem,
+  HttpTransport,
   Network,
   Persistence,
   TextCoder,
@@ -9,6 +10,7 @@ import {
   WasiContext,
 } from "./interfaces.js";
 import {
+  ErrorCode,
   PerformError,
   UnexpectedError,
   UninitializedError,
@@ -25,6 +27,28 @@ import {
   sf_host,
 } from "./lib/index.js";
 
+/** Version of the host <-> core ABI implemented by this host. */
+const ABI_VERSION = 1;
+/** Kinds of messages handled in `App.handleMessage`, reported to the core during handshake. */
+const MESSAGE_KINDS = [
+  "handshake",
+  "core-config",
+  "setup-output-exception",
+  "perform-input",
+  "perform-output-result",
+  "perform-output-error",
+  "perform-output-exception",
+  "perform-batch-input",
+  "perform-batch-output",
+  "describe-input",
+  "describe-output-result",
+  "describe-output-exception",
+  "file-open",
+  "http-call",
+  "http-call-head",
+  "sleep",
+];
+
 function headersToMultimap(headers: Headers): Record<string, string[]> {
   const result: Record<string, string[]> = {};
 
@@ -40,12 +64,72 @@ function headersToMultimap(headers: Headers): Record<string, string[]> {
   return result;
 }
 
+/**
+ * Providers to try in order when the perform fails, with the core defaults used for omitted conditions.
+ */
+export type PerformFailover = {
+  providers: { providerUrl: string; mapUrl: string }[];
+  onException?: boolean;
+  onNetworkError?: boolean;
+  onError?: unknown[];
+};
+export type PerformBatchEntry = {
+  profileUrl: string;
+  providerUrl: string;
+  mapUrl: string;
+  usecase: string;
+  input: unknown;
+  parameters: Record<string, string>;
+  security: SecurityValuesMap;
+  failover?: PerformFailover;
+  /** Only record http requests of the map, they are returned as the result. */
+  dryRun?: boolean;
+};
+export type PerformBatchOutcome =
+  | { kind: "result"; result: unknown }
+  | { kind: "error"; error: PerformError }
+  | { kind: "exception"; exception: ValidationError | UnexpectedError };
+
+function failoverToMessage(failover: PerformFailover | undefined) {
+  if (failover === undefined) {
+    return {};
+  }
+
+  return {
+    failover: failover.providers.map((provider) => ({
+      provider_url: provider.providerUrl,
+      map_url: provider.mapUrl,
+    })),
+    // omitted conditions are left out of the message so the core defaults apply
+    failover_policy: {
+      on_exception: failover.onException,
+      on_network_error: failover.onNetworkError,
+      on_error: failover.onError,
+    },
+  };
+}
+
+function transportFromMessage(transport: any): HttpTransport | undefined {
+  if (transport === undefined || transport === null) {
+    return undefined;
+  }
+
+  return {
+    proxyUrl: transport.proxy_url ?? undefined,
+    caPem: transport.ca_pem ?? undefined,
+    clientCertificate: transport.client_certificate ?? undefined,
+  };
+}
+
 type AppCore = {
   instance: WebAssembly.Instance;
   asyncify: Asyncify;
   setupFn: () => Promise<void>;
   teardownFn: () => Promise<void>;
   performFn: () => Promise<void>;
+  performBatchFn: () => Promise<void>;
+  describeFn: () => Promise<void>;
+  cancelFn: () => Promise<void>;
   getMetricsFn: () => Promise<number>;
   clearMetricsFn: () => Promise<void>;
   getDeveloperDumpFn: () => Promise<number>;
@@ -71,11 +155,28 @@ export class App extends AppContext {
         input: unknown;
         parameters: Record<string, string>;
         security: SecurityValuesMap;
+        failover?: PerformFailover;
+        dryRun?: boolean;
         result?: unknown;
         error?: PerformError;
         exception?: UnexpectedError;
       }
     | undefined = undefined;
+  private performBatchState:
+    | {
+        performs: PerformBatchEntry[];
+        outcomes?: PerformBatchOutcome[];
+        exception?: ValidationError | UnexpectedError;
+      }
+    | undefined = undefined;
+  private describeState:
+    | {
+        profileUrl?: string;
+        providerUrl?: string;
+        result?: unknown;
+        exception?: UnexpectedError;
+      }
+    | undefined = undefined;
 
   // TODO: should the timer be part of the host platform instead?
   private metricsState: {
@@ -84,6 +185,8 @@ export class App extends AppContext {
   };
 
   private readonly userAgent;
+  private readonly coreConfig: Record<string, unknown> | undefined;
+  private setupException: UnexpectedError | undefined = undefined;
 
   constructor(
     dependencies: {
@@ -93,7 +196,12 @@ export class App extends AppContext {
       timers: Timers;
       persistence: Persistence;
     },
-    options: { userAgent?: string; metricsTimeout?: number }
+    options: {
+      userAgent?: string;
+      metricsTimeout?: number;
+      /** Core configuration, overrides configuration from environment variables. */
+      coreConfig?: Record<string, unknown>;
+    }
   ) {
     super();
 
@@ -105,6 +213,7 @@ export class App extends AppContext {
     this.streams = new HandleMap();
     this.requests = new HandleMap();
     this.userAgent = options?.userAgent;
+    this.coreConfig = options?.coreConfig;
     this.metricsState = {
       timeout: options.metricsTimeout ?? 1000,
       handle: 0,
@@ -159,6 +268,20 @@ export class App extends AppContext {
             instance.exports["oneclient_core_perform"] as () => void
           )
         ),
+        performBatchFn: this.wrapExport(
+          asyncify.wrapExport(
+            instance.exports["oneclient_core_perform_batch"] as () => void
+          )
+        ),
+        describeFn: this.wrapExport(
+          asyncify.wrapExport(
+            instance.exports["oneclient_core_describe"] as () => void
+          )
+        ),
+        // cancel only sets a flag in the core, so it can be called while perform is suspended
+        cancelFn: this.wrapExport(
+          instance.exports["oneclient_core_cancel"] as () => void
+        ),
         // if we fail during getting metrics, we want to skip dumping metrics but still attempt to create developer dump
         getMetricsFn: this.wrapExport(
           instance.exports["oneclient_core_get_metrics"] as () => number
@@ -172,7 +295,18 @@ export class App extends AppContext {
         ),
       });
 
-      return this.core.withLock((core) => core.setupFn());
+      // a new core negotiates the encoding again
+      this.messageEncoding = "json";
+      await this.core.withLock((core) => core.setupFn());
+
+      // the core stays uninitialized when setup fails, so the next init instantiates it again
+      if (this.setupException !== undefined) {
+        const exception = this.setupException;
+        this.setupException = undefined;
+        this.core = undefined;
+
+        throw exception;
+      }
     }
   }
 
@@ -194,7 +328,9 @@ export class App extends AppContext {
     usecase: string,
     input: unknown,
     parameters: Record<string, string>,
-    security: SecurityValuesMap
+    security: SecurityValuesMap,
+    failover?: PerformFailover,
+    dryRun?: boolean
   ): Promise<unknown> {
     this.setSendMetricsTimeout();
 
@@ -207,6 +343,8 @@ export class App extends AppContext {
         input,
         parameters,
         security,
+        failover,
+        dryRun,
       };
       await core.performFn();
 
@@ -224,8 +362,101 @@ export class App extends AppContext {
     });
   }
 
+  /**
+   * Performs all entries in one core call, reusing documents between them.
+   *
+   * Outcomes are returned in the order of `performs`, failures of individual performs don't reject.
+   *
+   * @throws {ValidationError | UnexpectedError} when the batch itself failed
+   */
+  public async performBatch(
+    performs: PerformBatchEntry[]
+  ): Promise<PerformBatchOutcome[]> {
+    this.setSendMetricsTimeout();
+
+    return this.core!.withLock(async (core) => {
+      this.performBatchState = { performs };
+      await core.performBatchFn();
+
+      const state = this.performBatchState;
+      this.performBatchState = undefined;
+      if (state.exception !== undefined) {
+        throw state.exception;
+      }
+
+      return state.outcomes!;
+    });
+  }
+
+  /**
+   * Describes use cases of the profile and parameters, services and security schemes of the provider.
+   *
+   * @throws {UnexpectedError}
+   */
+  public async describe(options: {
+    profileUrl?: string;
+    providerUrl?: string;
+  }): Promise<unknown> {
+    return this.core!.withLock(async (core) => {
+      this.describeState = { ...options };
+      await core.describeFn();
+
+      const state = this.describeState;
+      this.describeState = undefined;
+      if (state.exception !== undefined) {
+        throw state.exception;
+      }
+
+      return state.result;
+    });
+  }
+
+  /**
+   * Requests cancellation of the in-flight perform, if any.
+   *
+   * The pending `perform` call rejects with an `UnexpectedError` named `Cancelled`.
+   */
+  public async cancel(): Promise<void> {
+    if (
+      this.core === undefined ||
+      (this.performState === undefined && this.performBatchState === undefined)
+    ) {
+      return;
+    }
+
+    // intentionally not taking the lock, it is held by the perform we are cancelling
+    return this.core.unsafeValue.cancelFn();
+  }
+
   public async handleMessage(message: any): Promise<any> {
     switch (message.kind) {
+      case "handshake":
+        if (message.abi_version !== ABI_VERSION) {
+          return {
+            kind: "err",
+            message: `Core ${message.core_version} implements ABI version ${message.abi_version}, host implements ABI version ${ABI_VERSION}`,
+          };
+        }
+
+        // the response is still JSON, following messages use the new encoding
+        this.messageEncoding = message.encodings?.includes("msgpack") ? "msgpack" : "json";
+        return {
+          kind: "ok",
+          abi_version: ABI_VERSION,
+          message_kinds: MESSAGE_KINDS,
+          encoding: this.messageEncoding,
+        };
+
+      case "core-config":
+        return { kind: "ok", config: this.coreConfig ?? {} };
+
+      case "setup-output-exception":
+        this.setupException = new UnexpectedError(
+          message.exception.error_code,
+          message.exception.message
+        );
+        return { kind: "ok" };
+
       case "perform-input":
         return {
           kind: "ok",
@@ -236,6 +467,8 @@ export class App extends AppContext {
           map_input: this.performState!.input,
           map_parameters: this.performState!.parameters,
           map_security: this.performState!.security,
+          ...failoverToMessage(this.performState!.failover),
+          dry_run: this.performState!.dryRun,
         };
 
       case "perform-output-result":
@@ -246,18 +479,68 @@ export class App extends AppContext {
         this.performState!.error = new PerformError(message.error);
         return { kind: "ok" };
 
-      case "perform-output-exception":
-        if (message.exception.error_code === "InputValidationError") {
-          this.performState!.exception = new ValidationError(
-            message.exception.message
-          );
+      case "perform-output-exception": {
+        // exception of a whole batch is sent the same way as of a single perform
+        const exception = this.exceptionFromMessage(message.exception);
+        if (this.performBatchState !== undefined) {
+          this.performBatchState.exception = exception;
         } else {
-          this.performState!.exception = new UnexpectedError(
-            message.exception.error_code,
-            message.exception.message
-          );
+          this.performState!.exception = exception;
         }
         return { kind: "ok" };
+      }
+
+      case "perform-batch-input":
+        return {
+          kind: "ok",
+          performs: this.performBatchState!.performs.map((perform) => ({
+            profile_url: perform.profileUrl,
+            provider_url: perform.providerUrl,
+            map_url: perform.mapUrl,
+            usecase: perform.usecase,
+            map_input: perform.input,
+            map_parameters: perform.parameters,
+            map_security: perform.security,
+            ...failoverToMessage(perform.failover),
+            dry_run: perform.dryRun,
+          })),
+        };
+
+      case "perform-batch-output":
+        this.performBatchState!.outcomes = message.outcomes.map(
+          (outcome: any): PerformBatchOutcome => {
+            switch (outcome.kind) {
+              case "result":
+                return { kind: "result", result: outcome.result };
+              case "error":
+                return { kind: "error", error: new PerformError(outcome.error) };
+              default:
+                return {
+                  kind: "exception",
+                  exception: this.exceptionFromMessage(outcome.exception),
+                };
+            }
+          }
+        );
+        return { kind: "ok" };
+
+      case "describe-input":
+        return {
+          kind: "ok",
+          profile_url: this.describeState!.profileUrl,
+          provider_url: this.describeState!.providerUrl,
+        };
+
+      case "describe-output-result":
+        this.describeState!.result = message.result;
+        return { kind: "ok" };
+
+      case "describe-output-exception":
+        this.describeState!.exception = new UnexpectedError(
+          message.exception.error_code,
+          message.exception.message
+        );
+        return { kind: "ok" };
 
       case "file-open": {
         try {
@@ -284,6 +567,8 @@ export class App extends AppContext {
         const requestInit: RequestInit = {
           method: message.method,
           headers: message.headers,
+          // fetch has its own limit on the number of followed redirects, so `max_hops` is not enforced here
+          redirect: message.redirect?.mode,
         };
 
         if (message.body !== undefined && message.body !== null) {
@@ -293,7 +578,8 @@ export class App extends AppContext {
         try {
           const request: Promise<Response> = this.network.fetch(
             message.url,
-            requestInit
+            requestInit,
+            transportFromMessage(message.transport)
           );
           return { kind: "ok", handle: this.requests.insert(request) };
         } catch (error: any) {
@@ -324,6 +610,12 @@ export class App extends AppContext {
         }
       }
 
+      case "sleep":
+        await new Promise<void>((resolve) =>
+          this.timers.setTimeout(resolve, message.duration_ms)
+        );
+        return { kind: "ok" };
+
       default:
         return { kind: "err", error: `Unknown message ${message["kind"]}` };
     }
@@ -356,6 +648,20 @@ export class App extends AppContext {
     await stream.close();
   }
 
+  private exceptionFromMessage(
+    exception: any
+  ): ValidationError | UnexpectedError {
+    if (exception.error_code === "InputValidationError") {
+      return new ValidationError(exception.message);
+    }
+
+    return new UnexpectedError(
+      exception.error_code,
+      exception.message,
+      exception.js_exception
+    );
+  }
+
   private importObject(
     wasi: WasiContext,
     asyncify: Asyncify
@@ -19,8 +19,22 @@ export class ValidationError extends BaseError {
   }
 }
 
+export type JsStackFrame = {
+  function: string | null;
+  file: string | null;
+  line: number | null;
+  column: number | null;
+};
+
+/** Uncaught exception thrown by the map, stack frames point into the original sources if the map was shipped with a source map. */
+export type JsException = {
+  name: string;
+  message: string;
+  stack: JsStackFrame[];
+};
+
 export class UnexpectedError extends BaseError {
-  constructor(name: string, message: string) {
+  constructor(name: string, message: string, public readonly jsException?: JsException) {
     super(name, message);
   }
 }
@@ -1,5 +1,5 @@
 export { App } from './app.js';
 export * from './error.js';
-export type { FileSystem, Network, Persistence, TextCoder, Timers, WasiContext } from './interfaces.js';
+export type { FileSystem, HttpTransport, Network, Persistence, TextCoder, Timers, WasiContext } from './interfaces.js';
 export type { SecurityValuesMap } from './security.js';
 export { HandleMap, AsyncMutex } from './lib/index.js';
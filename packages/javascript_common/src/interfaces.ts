@@ -9,10 +9,20 @@ export interface FileSystem {
   write(handle: number, data: Uint8Array): Promise<number>;
   close(handle: number): Promise<void>;
 }
+/** Proxy and TLS settings of a request, resolved by the core from provider transport and security values. */
+export type HttpTransport = {
+  /** URL of the proxy to send the request through. */
+  proxyUrl?: string;
+  /** PEM encoded CA certificates used instead of the default trust store. */
+  caPem?: string;
+  /** PEM encoded client certificate chain and private key for mutual TLS. */
+  clientCertificate?: { certificate: string, key: string };
+};
 export interface Network {
   fetch(
     input: RequestInfo,
-    init?: RequestInit
+    init?: RequestInit,
+    transport?: HttpTransport
   ): Promise<Response>
 }
 export interface Timers {
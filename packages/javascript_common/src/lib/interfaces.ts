@@ -1,4 +1,9 @@
+import type { MessageEncoding } from './msgpack.js';
+
 export abstract class AppBase {
+  /** Encoding of messages after the handshake, which itself is always JSON. */
+  messageEncoding: MessageEncoding = 'json';
+
   abstract get memory(): WebAssembly.Memory;
   get memoryBytes(): Uint8Array {
     return new Uint8Array(this.memory.buffer);
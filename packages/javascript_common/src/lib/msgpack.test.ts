@@ -0,0 +1,95 @@
+import { AppContextSync, TextCoder } from './interfaces.js';
+import { decode, encode } from './msgpack.js';
+import { linkSync } from './sf_host.js';
+
+class TestCoder implements TextCoder {
+  private encoder: TextEncoder = new TextEncoder();
+  private decoder: TextDecoder = new TextDecoder();
+
+  decodeUtf8(buffer: ArrayBufferLike): string {
+    return this.decoder.decode(buffer);
+  }
+
+  encodeUtf8(string: string): Uint8Array {
+    return this.encoder.encode(string);
+  }
+}
+
+/** Answers every message with `response` and records the messages. */
+class TestApp extends AppContextSync {
+  private readonly wasmMemory = new WebAssembly.Memory({ initial: 1 });
+  public readonly messages: any[] = [];
+
+  constructor(private readonly response: unknown) {
+    super();
+  }
+
+  get memory(): WebAssembly.Memory {
+    return this.wasmMemory;
+  }
+
+  handleMessage(message: any): any {
+    this.messages.push(message);
+    return this.response;
+  }
+  readStream(_handle: number, _out: Uint8Array): number {
+    throw new Error('Method not implemented.');
+  }
+  writeStream(_handle: number, _data: Uint8Array): number {
+    throw new Error('Method not implemented.');
+  }
+  closeStream(_handle: number): void {
+    throw new Error('Method not implemented.');
+  }
+}
+
+describe('msgpack', () => {
+  const textCoder = new TestCoder();
+
+  test('round trip', () => {
+    const value = {
+      nil: null,
+      bools: [true, false],
+      ints: [0, 127, 128, 255, 256, 65536, 2 ** 32, -1, -32, -33, -129, -32769, -(2 ** 31) - 1],
+      float: 1.5,
+      string: 'čau'.repeat(20),
+      bytes: new Uint8Array([0, 1, 255]),
+      nested: { array: Array.from({ length: 20 }, (_, i) => i) },
+    };
+
+    expect(decode(encode(value, textCoder), textCoder)).toEqual(value);
+  });
+
+  test('omits undefined fields like JSON', () => {
+    expect(decode(encode({ a: 1, b: undefined }, textCoder), textCoder)).toEqual({ a: 1 });
+  });
+
+  test('rejects truncated input', () => {
+    const bytes = encode({ a: 'string' }, textCoder);
+
+    expect(() => decode(bytes.subarray(0, bytes.byteLength - 1), textCoder)).toThrow();
+  });
+
+  test('http-call with binary body over message exchange', () => {
+    const app = new TestApp({ kind: 'ok', handle: 1 });
+    app.messageEncoding = 'msgpack';
+    const imports = linkSync(app, textCoder).sf_host_unstable as any;
+
+    const body = new Uint8Array([0, 1, 2, 128, 255]);
+    const message = encode({
+      kind: 'http-call',
+      method: 'POST',
+      url: 'https://example.com',
+      headers: { 'content-type': ['application/octet-stream'] },
+      body,
+    }, textCoder);
+    app.memoryBytes.set(message, 0);
+
+    const outPtr = 1024;
+    const responseLength = imports.message_exchange(0, message.byteLength, outPtr, 1024, 4096);
+
+    expect(app.messages[0].body).toEqual(body);
+    expect(app.memoryView.getInt32(4096, true)).toBe(0);
+    expect(decode(app.memoryBytes.slice(outPtr, outPtr + responseLength), textCoder)).toEqual({ kind: 'ok', handle: 1 });
+  });
+});
@@ -0,0 +1,264 @@
+import type { TextCoder } from './interfaces.js';
+
+/** Encoding of messages exchanged with the core, negotiated during handshake. */
+export type MessageEncoding = 'json' | 'msgpack';
+
+/**
+ * Encodes `value` as MessagePack.
+ *
+ * Values are encoded like `JSON.stringify` would, except that `Uint8Array`s and `ArrayBuffer`s are encoded as `bin`.
+ */
+export function encode(value: unknown, textCoder: TextCoder): Uint8Array {
+  const writer = new Writer();
+  encodeValue(writer, value, textCoder);
+
+  return writer.finish();
+}
+
+/** Decodes one MessagePack value, `bin` is decoded as `Uint8Array`. */
+export function decode(bytes: Uint8Array, textCoder: TextCoder): unknown {
+  const reader = new Reader(bytes);
+  const value = decodeValue(reader, textCoder);
+  if (reader.offset !== bytes.byteLength) {
+    throw new Error(`MessagePack: ${bytes.byteLength - reader.offset} trailing bytes`);
+  }
+
+  return value;
+}
+
+class Writer {
+  private buffer = new Uint8Array(256);
+  private view = new DataView(this.buffer.buffer);
+  private length = 0;
+
+  private reserve(additional: number): number {
+    const offset = this.length;
+    if (offset + additional > this.buffer.byteLength) {
+      const buffer = new Uint8Array(Math.max(this.buffer.byteLength * 2, offset + additional));
+      buffer.set(this.buffer.subarray(0, offset));
+      this.buffer = buffer;
+      this.view = new DataView(buffer.buffer);
+    }
+    this.length += additional;
+
+    return offset;
+  }
+
+  // `reserve` may replace the buffer, so it has to be called before the view is accessed
+  u8(value: number) { const offset = this.reserve(1); this.view.setUint8(offset, value); }
+  u16(value: number) { const offset = this.reserve(2); this.view.setUint16(offset, value); }
+  u32(value: number) { const offset = this.reserve(4); this.view.setUint32(offset, value); }
+  u64(value: number) { const offset = this.reserve(8); this.view.setBigUint64(offset, BigInt(value)); }
+  i8(value: number) { const offset = this.reserve(1); this.view.setInt8(offset, value); }
+  i16(value: number) { const offset = this.reserve(2); this.view.setInt16(offset, value); }
+  i32(value: number) { const offset = this.reserve(4); this.view.setInt32(offset, value); }
+  i64(value: number) { const offset = this.reserve(8); this.view.setBigInt64(offset, BigInt(value)); }
+  f64(value: number) { const offset = this.reserve(8); this.view.setFloat64(offset, value); }
+  bytes(value: Uint8Array) { const offset = this.reserve(value.byteLength); this.buffer.set(value, offset); }
+
+  finish(): Uint8Array {
+    return this.buffer.slice(0, this.length);
+  }
+}
+
+function encodeValue(writer: Writer, value: unknown, textCoder: TextCoder) {
+  if (value === null || value === undefined) {
+    writer.u8(0xc0);
+  } else if (typeof value === 'boolean') {
+    writer.u8(value ? 0xc3 : 0xc2);
+  } else if (typeof value === 'number') {
+    encodeNumber(writer, value);
+  } else if (typeof value === 'string') {
+    const bytes = textCoder.encodeUtf8(value);
+    encodeHeader(writer, bytes.byteLength, 0xa0, 32, 0xd9);
+    writer.bytes(bytes);
+  } else if (value instanceof Uint8Array || value instanceof ArrayBuffer) {
+    const bytes = new Uint8Array(value);
+    encodeHeader(writer, bytes.byteLength, undefined, 0, 0xc4);
+    writer.bytes(bytes);
+  } else if (Array.isArray(value)) {
+    encodeHeader(writer, value.length, 0x90, 16, 0xdc);
+    for (const item of value) {
+      encodeValue(writer, item, textCoder);
+    }
+  } else if (typeof value === 'object') {
+    // like JSON, fields with undefined values are omitted
+    const entries = Object.entries(value).filter(([_, v]) => v !== undefined);
+    encodeHeader(writer, entries.length, 0x80, 16, 0xde);
+    for (const [key, item] of entries) {
+      encodeValue(writer, key, textCoder);
+      encodeValue(writer, item, textCoder);
+    }
+  } else {
+    throw new Error(`MessagePack: cannot encode ${typeof value}`);
+  }
+}
+
+function encodeNumber(writer: Writer, value: number) {
+  if (!Number.isSafeInteger(value)) {
+    if (!Number.isFinite(value)) {
+      // like JSON
+      writer.u8(0xc0);
+    } else {
+      writer.u8(0xcb);
+      writer.f64(value);
+    }
+  } else if (value >= 0) {
+    if (value < 0x80) {
+      writer.u8(value);
+    } else if (value <= 0xff) {
+      writer.u8(0xcc);
+      writer.u8(value);
+    } else if (value <= 0xffff) {
+      writer.u8(0xcd);
+      writer.u16(value);
+    } else if (value <= 0xffffffff) {
+      writer.u8(0xce);
+      writer.u32(value);
+    } else {
+      writer.u8(0xcf);
+      writer.u64(value);
+    }
+  } else {
+    if (value >= -0x20) {
+      writer.i8(value);
+    } else if (value >= -0x80) {
+      writer.u8(0xd0);
+      writer.i8(value);
+    } else if (value >= -0x8000) {
+      writer.u8(0xd1);
+      writer.i16(value);
+    } else if (value >= -0x80000000) {
+      writer.u8(0xd2);
+      writer.i32(value);
+    } else {
+      writer.u8(0xd3);
+      writer.i64(value);
+    }
+  }
+}
+
+/**
+ * Writes the header of a string, binary, array or map of `length`.
+ *
+ * `fixType` is used below `fixLimit`, otherwise `type` followed by the next type for each longer length field.
+ * Strings and binaries start at 8-bit lengths, arrays and maps at 16-bit ones.
+ */
+function encodeHeader(writer: Writer, length: number, fixType: number | undefined, fixLimit: number, type: number) {
+  const hasU8 = type === 0xd9 || type === 0xc4;
+  if (fixType !== undefined && length < fixLimit) {
+    writer.u8(fixType | length);
+  } else if (hasU8 && length <= 0xff) {
+    writer.u8(type);
+    writer.u8(length);
+  } else if (length <= 0xffff) {
+    writer.u8(hasU8 ? type + 1 : type);
+    writer.u16(length);
+  } else {
+    writer.u8(hasU8 ? type + 2 : type + 1);
+    writer.u32(length);
+  }
+}
+
+class Reader {
+  private readonly view: DataView;
+  public offset = 0;
+
+  constructor(private readonly input: Uint8Array) {
+    this.view = new DataView(input.buffer, input.byteOffset, input.byteLength);
+  }
+
+  private advance(count: number): number {
+    const offset = this.offset;
+    if (offset + count > this.input.byteLength) {
+      throw new Error('MessagePack: unexpected end of input');
+    }
+    this.offset += count;
+
+    return offset;
+  }
+
+  u8(): number { return this.view.getUint8(this.advance(1)); }
+  u16(): number { return this.view.getUint16(this.advance(2)); }
+  u32(): number { return this.view.getUint32(this.advance(4)); }
+  u64(): number { return Number(this.view.getBigUint64(this.advance(8))); }
+  i8(): number { return this.view.getInt8(this.advance(1)); }
+  i16(): number { return this.view.getInt16(this.advance(2)); }
+  i32(): number { return this.view.getInt32(this.advance(4)); }
+  i64(): number { return Number(this.view.getBigInt64(this.advance(8))); }
+  f32(): number { return this.view.getFloat32(this.advance(4)); }
+  f64(): number { return this.view.getFloat64(this.advance(8)); }
+  bytes(length: number): Uint8Array {
+    const offset = this.advance(length);
+    return this.input.slice(offset, offset + length);
+  }
+}
+
+function decodeValue(reader: Reader, textCoder: TextCoder): unknown {
+  const type = reader.u8();
+
+  if (type < 0x80) {
+    return type;
+  } else if (type < 0x90) {
+    return decodeMap(reader, type & 0x0f, textCoder);
+  } else if (type < 0xa0) {
+    return decodeArray(reader, type & 0x0f, textCoder);
+  } else if (type < 0xc0) {
+    return decodeString(reader, type & 0x1f, textCoder);
+  } else if (type >= 0xe0) {
+    return type - 0x100;
+  }
+
+  switch (type) {
+    case 0xc0: return null;
+    case 0xc2: return false;
+    case 0xc3: return true;
+    case 0xc4: return reader.bytes(reader.u8());
+    case 0xc5: return reader.bytes(reader.u16());
+    case 0xc6: return reader.bytes(reader.u32());
+    case 0xca: return reader.f32();
+    case 0xcb: return reader.f64();
+    case 0xcc: return reader.u8();
+    case 0xcd: return reader.u16();
+    case 0xce: return reader.u32();
+    case 0xcf: return reader.u64();
+    case 0xd0: return reader.i8();
+    case 0xd1: return reader.i16();
+    case 0xd2: return reader.i32();
+    case 0xd3: return reader.i64();
+    case 0xd9: return decodeString(reader, reader.u8(), textCoder);
+    case 0xda: return decodeString(reader, reader.u16(), textCoder);
+    case 0xdb: return decodeString(reader, reader.u32(), textCoder);
+    case 0xdc: return decodeArray(reader, reader.u16(), textCoder);
+    case 0xdd: return decodeArray(reader, reader.u32(), textCoder);
+    case 0xde: return decodeMap(reader, reader.u16(), textCoder);
+    case 0xdf: return decodeMap(reader, reader.u32(), textCoder);
+    default:
+      // extension types are not used by the core
+      throw new Error(`MessagePack: unsupported type 0x${type.toString(16)}`);
+  }
+}
+
+function decodeString(reader: Reader, length: number, textCoder: TextCoder): string {
+  return textCoder.decodeUtf8(reader.bytes(length).buffer);
+}
+
+function decodeArray(reader: Reader, length: number, textCoder: TextCoder): unknown[] {
+  const result = [];
+  for (let i = 0; i < length; i += 1) {
+    result.push(decodeValue(reader, textCoder));
+  }
+
+  return result;
+}
+
+function decodeMap(reader: Reader, length: number, textCoder: TextCoder): Record<string, unknown> {
+  const result: Record<string, unknown> = {};
+  for (let i = 0; i < length; i += 1) {
+    const key = String(decodeValue(reader, textCoder));
+    // like JSON.parse, `__proto__` is an own property and doesn't replace the prototype
+    Object.defineProperty(result, key, { value: decodeValue(reader, textCoder), enumerable: true, writable: true, configurable: true });
+  }
+
+  return result;
+}
@@ -2,6 +2,8 @@ import type { TextCoder, AppContext, AppContextSync } from './interfaces.js';
 import { Asyncify, AsyncifyState } from './asyncify.js'
 import { HandleMap } from './handle_map.js'
 import { WasiErrno } from './error.js';
+import * as msgpack from './msgpack.js';
+import type { MessageEncoding } from './msgpack.js';
 
 type AbiResult = number;
 // @ts-ignore ignore the unused parameter
@@ -54,6 +56,21 @@ function abi_err(value: number): number {
   return join_abi_result(value, 1);
 }
 
+function decodeMessage(bytes: Uint8Array, encoding: MessageEncoding, textCoder: TextCoder): any {
+  if (encoding === 'msgpack') {
+    return msgpack.decode(bytes, textCoder);
+  }
+
+  return JSON.parse(textCoder.decodeUtf8(bytes));
+}
+function encodeMessage(message: unknown, encoding: MessageEncoding, textCoder: TextCoder): Uint8Array {
+  if (encoding === 'msgpack') {
+    return msgpack.encode(message, textCoder);
+  }
+
+  return textCoder.encodeUtf8(JSON.stringify(message));
+}
+
 function writeBytes(source: ArrayBuffer, dest: Uint8Array) {
   const src = new Uint8Array(source);
   for (let i = 0; i < src.byteLength; i += 1) {
@@ -65,13 +82,13 @@ export function link(app: AppContext, textCoder: TextCoder, asyncify: Asyncify):
   const messageStore = new HandleMap<ArrayBuffer>();
 
   async function __export_message_exchange(msg_ptr: Ptr<8>, msg_len: Size, out_ptr: Ptr<8>, out_len: Size, ret_handle: Ptr<32>): Promise<Size> {
-    const msg = JSON.parse(textCoder.decodeUtf8(
-      app.memoryBytes.subarray(msg_ptr, msg_ptr + msg_len)
-    ));
+    // the handshake may change the encoding, its response is still encoded like the request
+    const encoding = app.messageEncoding;
+    const msg = decodeMessage(app.memoryBytes.subarray(msg_ptr, msg_ptr + msg_len), encoding, textCoder);
     const response = await app.handleMessage(msg);
 
     let messageHandle = 0;
-    const responseBytes = textCoder.encodeUtf8(JSON.stringify(response));
+    const responseBytes = encodeMessage(response, encoding, textCoder);
     if (responseBytes.byteLength > out_len) {
       messageHandle = messageStore.insert(responseBytes);
     } else {
@@ -126,13 +143,13 @@ export function linkSync(app: AppContextSync, textCoder: TextCoder): WebAssembly
   const messageStore = new HandleMap<ArrayBuffer>();
 
   function __export_message_exchange(msg_ptr: Ptr<8>, msg_len: Size, out_ptr: Ptr<8>, out_len: Size, ret_handle: Ptr<32>): Size {
-    const msg = JSON.parse(textCoder.decodeUtf8(
-      app.memoryBytes.subarray(msg_ptr, msg_ptr + msg_len)
-    ));
+    // the handshake may change the encoding, its response is still encoded like the request
+    const encoding = app.messageEncoding;
+    const msg = decodeMessage(app.memoryBytes.subarray(msg_ptr, msg_ptr + msg_len), encoding, textCoder);
     const response = app.handleMessage(msg);
 
     let messageHandle = 0;
-    const responseBytes = textCoder.encodeUtf8(JSON.stringify(response));
+    const responseBytes = encodeMessage(response, encoding, textCoder);
     if (responseBytes.byteLength > out_len) {
       messageHandle = messageStore.insert(responseBytes);
     } else {
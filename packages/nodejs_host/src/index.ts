@@ -9,6 +9,8 @@ import {
   App,
   FileSystem,
   HandleMap,
+  HostError,
+  HttpTransport,
   Network,
   Persistence,
   SecurityValuesMap,
@@ -19,6 +21,7 @@ import {
   WasiError,
 } from "./common/index.js";
 import { fetchErrorToHostError, systemErrorToWasiError } from "./error.js";
+import { fetchWithTransport } from "./transport.js";
 import { fileURLToPath } from "node:url";
 
 function coreWasmPath(): string {
@@ -159,10 +162,17 @@ class NodeTimers implements Timers {
 
 class NodeNetwork implements Network {
   // TODO: import from undici explicitly
-  async fetch(input: RequestInfo, init?: RequestInit): Promise<Response> {
+  async fetch(input: RequestInfo, init?: RequestInit, transport?: HttpTransport): Promise<Response> {
     try {
+      if (transport !== undefined) {
+        return await fetchWithTransport(input, init, transport);
+      }
+
       return await fetch(input, init);
     } catch (err: unknown) {
+      if (err instanceof HostError) {
+        throw err;
+      }
       throw fetchErrorToHostError(err);
     }
   }
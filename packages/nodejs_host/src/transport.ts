@@ -0,0 +1,169 @@
+import http from 'node:http';
+import https from 'node:https';
+import type { Socket } from 'node:net';
+import { Readable, Transform } from 'node:stream';
+import tls from 'node:tls';
+import zlib from 'node:zlib';
+
+import { ErrorCode, HostError, HttpTransport } from './common/index.js';
+
+/** Statuses for which `Response` must be constructed without a body. */
+const NULL_BODY_STATUSES = [204, 205, 304];
+
+/**
+ * Sends the request through the proxy and with the TLS settings from `transport`, which global fetch does not support.
+ *
+ * Proxied https requests are tunneled through `CONNECT`, proxied http requests are sent to the proxy with an absolute url.
+ *
+ * Redirects are never followed, the core sends map requests with manual redirects and follows them itself.
+ *
+ * The body is decoded according to `content-encoding` like with fetch, headers are kept as received.
+ *
+ * Errors are thrown the same way as from fetch, with the underlying system error as `cause`.
+ */
+export async function fetchWithTransport(input: RequestInfo, init: RequestInit | undefined, transport: HttpTransport): Promise<Response> {
+  const url = new URL(typeof input === 'string' ? input : input.url);
+  const method = init?.method ?? 'GET';
+  const headers = Object.fromEntries(new Headers(init?.headers).entries());
+  const body = init?.body as Uint8Array | null | undefined;
+
+  const tlsOptions: tls.ConnectionOptions = {
+    ca: transport.caPem,
+    cert: transport.clientCertificate?.certificate,
+    key: transport.clientCertificate?.key,
+  };
+  const proxy = transport.proxyUrl !== undefined ? new URL(transport.proxyUrl) : undefined;
+
+  let request: http.ClientRequest;
+  try {
+    if (url.protocol === 'https:') {
+      let createConnection: (() => Socket) | undefined;
+      if (proxy !== undefined) {
+        const tunnel = await connectTunnel(proxy, url);
+        createConnection = () => tls.connect({ ...tlsOptions, socket: tunnel, servername: url.hostname });
+      }
+
+      request = https.request(url, { method, headers, ...tlsOptions, agent: createConnection === undefined ? false : undefined, createConnection });
+    } else if (proxy !== undefined) {
+      request = requestModule(proxy).request({
+        ...tlsOptions,
+        host: proxy.hostname,
+        port: proxy.port,
+        path: url.href,
+        method,
+        headers: { ...headers, host: url.host, ...proxyHeaders(proxy) },
+        agent: false,
+      });
+    } else {
+      request = http.request(url, { method, headers, agent: false });
+    }
+  } catch (err: unknown) {
+    if (err instanceof HostError) {
+      throw err;
+    }
+    throw new TypeError('fetch failed', { cause: err });
+  }
+
+  const response = new Promise<Response>((resolve, reject) => {
+    request.once('error', (err) => reject(new TypeError('fetch failed', { cause: err })));
+    request.once('response', (res) => {
+      const status = res.statusCode ?? 0;
+      if (init?.redirect === 'error' && status >= 300 && status < 400) {
+        res.destroy();
+        reject(new HostError(ErrorCode.NetworkError, `Unexpected redirect to ${res.headers.location}`));
+        return;
+      }
+
+      const responseHeaders = new Headers();
+      for (const [name, value] of Object.entries(res.headers)) {
+        for (const v of Array.isArray(value) ? value : [value ?? '']) {
+          responseHeaders.append(name, v);
+        }
+      }
+
+      const hasBody = method !== 'HEAD' && !NULL_BODY_STATUSES.includes(status);
+      resolve(new Response(
+        hasBody ? Readable.toWeb(decodeBody(res)) as ReadableStream : null,
+        { status, statusText: res.statusMessage, headers: responseHeaders }
+      ));
+    });
+  });
+
+  request.end(body ?? undefined);
+  return response;
+}
+
+/**
+ * Decodes the response body according to `content-encoding`, codings are undone in reverse order of application.
+ *
+ * Unknown codings are passed through undecoded, as with fetch.
+ */
+function decodeBody(res: http.IncomingMessage): Readable {
+  const codings = (res.headers['content-encoding'] ?? '')
+    .split(',')
+    .map(coding => coding.trim().toLowerCase())
+    .filter(coding => coding !== '' && coding !== 'identity')
+    .reverse();
+
+  const decoders: Transform[] = [];
+  for (const coding of codings) {
+    if (coding === 'gzip' || coding === 'x-gzip') {
+      // lenient flushing like fetch, servers commonly send truncated streams
+      decoders.push(zlib.createGunzip({ flush: zlib.constants.Z_SYNC_FLUSH, finishFlush: zlib.constants.Z_SYNC_FLUSH }));
+    } else if (coding === 'deflate') {
+      decoders.push(zlib.createInflate({ flush: zlib.constants.Z_SYNC_FLUSH, finishFlush: zlib.constants.Z_SYNC_FLUSH }));
+    } else if (coding === 'br') {
+      decoders.push(zlib.createBrotliDecompress({ flush: zlib.constants.BROTLI_OPERATION_FLUSH, finishFlush: zlib.constants.BROTLI_OPERATION_FLUSH }));
+    } else {
+      return res;
+    }
+  }
+
+  let body: Readable = res;
+  for (const decoder of decoders) {
+    // pipe doesn't forward errors, the core reads from the last stream
+    body.once('error', (err) => decoder.destroy(err));
+    body = body.pipe(decoder);
+  }
+
+  return body;
+}
+
+function requestModule(url: URL): typeof http | typeof https {
+  return url.protocol === 'https:' ? https : http;
+}
+
+function proxyHeaders(proxy: URL): Record<string, string> {
+  if (proxy.username === '' && proxy.password === '') {
+    return {};
+  }
+
+  const credentials = `${decodeURIComponent(proxy.username)}:${decodeURIComponent(proxy.password)}`;
+  return { 'proxy-authorization': `Basic ${Buffer.from(credentials).toString('base64')}` };
+}
+
+/** Opens a `CONNECT` tunnel to `target` through `proxy` and returns the tunneled socket. */
+function connectTunnel(proxy: URL, target: URL): Promise<Socket> {
+  const authority = `${target.hostname}:${target.port || 443}`;
+
+  return new Promise((resolve, reject) => {
+    const request = requestModule(proxy).request({
+      host: proxy.hostname,
+      port: proxy.port,
+      method: 'CONNECT',
+      path: authority,
+      headers: { host: authority, ...proxyHeaders(proxy) },
+      agent: false,
+    });
+    request.once('connect', (response, socket) => {
+      if (response.statusCode === 200) {
+        resolve(socket);
+      } else {
+        socket.destroy();
+        reject(new HostError(ErrorCode.NetworkError, `Proxy refused to connect to ${authority}: ${response.statusCode} ${response.statusMessage}`));
+      }
+    });
+    request.once('error', reject);
+    request.end();
+  });
+}
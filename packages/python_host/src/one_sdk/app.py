@@ -2,6 +2,7 @@ import importlib.metadata as metadata
 import os
 import platform
 import sys
+import time
 
 from typing import Any, BinaryIO, Callable, List, Mapping, Optional, cast, Union
 
@@ -22,6 +23,23 @@ from one_sdk.platform import PythonFilesystem, PythonNetwork, PythonPersistence,
 # TODO: TypeAlias - needs 3.10
 SecurityValuesMap = Mapping[str, Mapping[str, str]]
 
+# Version of the host <-> core ABI implemented by this host.
+ABI_VERSION = 1
+# Kinds of messages handled in `WasiApp.handle_message`, reported to the core during handshake.
+MESSAGE_KINDS = [
+	"handshake",
+	"core-config",
+	"setup-output-exception",
+	"perform-input",
+	"perform-output-result",
+	"perform-output-error",
+	"perform-output-exception",
+	"file-open",
+	"http-call",
+	"http-call-head",
+	"sleep"
+]
+
 class WasiMemory:
 	"""Pointer to Wasi Memory - do not store this between calls to WASM as it might get invalidated"""
 	def __init__(self, data_ptr):
@@ -75,8 +93,10 @@ class WasiApp:
 		self,
 		filesystem: PythonFilesystem,
 		network: PythonNetwork,
-		persistence: PythonPersistence
+		persistence: PythonPersistence,
+		core_config: Optional[Mapping[str, Any]] = None
 	):
+		"""`core_config` overrides core configuration from environment variables."""
 		self._engine = Engine()
 		self._linker = Linker(self._engine)
 		self._store = Store(self._engine)
@@ -98,11 +118,13 @@ class WasiApp:
 		self._filesystem = filesystem
 		self._network = network
 		self._persistence = persistence
+		self._core_config = core_config
 
 		# loaded when core is loaded
 		self._module: Optional[Module] = None
 		self._core: Optional[WasiApp._AppCore] = None
 		self._perform_state: Optional[WasiApp._PerformState] = None
+		self._setup_exception: Optional[UnexpectedError] = None
 
 	def _memory_from_core(self, core: "WasiApp._AppCore") -> WasiMemory:
 		memory = cast(Memory, core.instance.exports(self._store)["memory"])
@@ -118,6 +140,21 @@ class WasiApp:
 		return self._memory_from_core(self._core)
 
 	def handle_message(self, message: Any) -> Any:
+		# sent during setup, before any perform
+		if message["kind"] == "handshake":
+			if message["abi_version"] != ABI_VERSION:
+				return {
+					"kind": "err",
+					"message": f"Core {message['core_version']} implements ABI version {message['abi_version']}, host implements ABI version {ABI_VERSION}"
+				}
+
+			return { "kind": "ok", "abi_version": ABI_VERSION, "message_kinds": MESSAGE_KINDS }
+		elif message["kind"] == "core-config":
+			return { "kind": "ok", "config": self._core_config or {} }
+		elif message["kind"] == "setup-output-exception":
+			self._setup_exception = UnexpectedError(message["exception"]["error_code"], message["exception"]["message"])
+			return { "kind": "ok" }
+
 		if self._perform_state is None:
 			raise UnexpectedError("UnexpectedError", "Unexpected perform state")
 		
@@ -142,7 +179,7 @@ class WasiApp:
 			if message["exception"]["error_code"] == "InputValidationError":
 				self._perform_state.exception = ValidationError(message["exception"]["message"])
 			else:
-				self._perform_state.exception = UnexpectedError(message["exception"]["error_code"], message["exception"]["message"])
+				self._perform_state.exception = UnexpectedError(message["exception"]["error_code"], message["exception"]["message"], message["exception"].get("js_exception"))
 			return { "kind": "ok" }
 		elif message["kind"] == "file-open":
 			try:
@@ -176,7 +213,9 @@ class WasiApp:
 					message["url"],
 					message["method"],
 					message["headers"],
-					None if message["body"] is None else bytes(message["body"])
+					None if message["body"] is None else bytes(message["body"]),
+					message.get("redirect"),
+					message.get("transport")
 				)
 			except HostError as err:
 				return { "kind": "err", "error_code": err.code, "message": err.message }
@@ -199,6 +238,9 @@ class WasiApp:
 				"headers": response.headers(),
 				"body_stream": self._streams.insert(response.body())
 			}
+		elif message["kind"] == "sleep":
+			time.sleep(message["duration_ms"] / 1000)
+			return { "kind": "ok" }
 		else:
 			return { "kind": "err", "error": f"Unknown message {message['kind']}" }
 	
@@ -247,6 +289,14 @@ class WasiApp:
 		)
 
 		self._core.setup_fn()
+
+		# the core stays uninitialized when setup fails, so the next init instantiates it again
+		if self._setup_exception is not None:
+			exception = self._setup_exception
+			self._setup_exception = None
+			self._core = None
+
+			raise exception
 	
 	def destroy(self):
 		if self._core is not None:
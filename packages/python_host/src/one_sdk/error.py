@@ -20,8 +20,10 @@ class ValidationError(BaseError):
 		super().__init__("ValidationError", message)
 
 class UnexpectedError(BaseError):
-	def __init__(self, name: str, message: str):
+	def __init__(self, name: str, message: str, js_exception: Any = None):
 		super().__init__(name, message)
+		# uncaught exception thrown by the map: `name`, `message` and `stack` frames (`function`, `file`, `line`, `column`)
+		self.js_exception = js_exception
 
 class UninitializedError(BaseError):
 	def __init__(self):
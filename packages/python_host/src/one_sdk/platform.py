@@ -1,8 +1,11 @@
-from typing import BinaryIO, List, Mapping, Optional, Union, cast
+from typing import Any, BinaryIO, Dict, List, Mapping, Optional, Tuple, Union, cast
 
 import os.path
+import ssl
+import tempfile
 from datetime import datetime
 from collections import defaultdict
+from urllib.parse import unquote
 
 import urllib3
 from urllib3.exceptions import MaxRetryError, NewConnectionError
@@ -137,30 +140,44 @@ class PythonNetwork:
 	def __init__(self):
 		self._manager = urllib3.PoolManager(num_pools = 3)
 		self._retries = urllib3.Retry(connect = 2, read = 2, redirect = 3)
+		# managers for requests with transport settings, keyed by the settings so that connections are reused
+		self._transport_managers: Dict[Tuple[Optional[str], ...], urllib3.PoolManager] = {}
 
 	def fetch(
 		self,
 		url: str,
 		method: str,
 		headers: Mapping[str, List[str]],
-		body: Optional[bytes]
+		body: Optional[bytes],
+		redirect: Optional[Mapping[str, Any]] = None,
+		transport: Optional[Mapping[str, Any]] = None
 	) -> DeferredHttpResponse:
 		# TODO: catch InvalidUrl
+		manager = self._manager_for_transport(transport)
+
 		headers_dict = urllib3.HTTPHeaderDict()
 		for (key, values) in headers.items():
 			for value in values:
 				headers_dict.add(key, value)
 		
+		redirect_mode = "follow" if redirect is None else redirect["mode"]
+		max_hops = 20 if redirect is None else redirect.get("max_hops", 20)
+		retries = self._retries.new(
+			total = None,
+			redirect = max_hops if redirect_mode == "follow" else 0,
+			raise_on_redirect = redirect_mode != "manual"
+		)
+		
 		response = None
 		exception = None
 		try:
-			response = self._manager.urlopen(
+			response = manager.urlopen(
 				method,
 				url,
-				True,
+				redirect_mode != "manual",
 				body = body,
 				headers = headers_dict,
-				retries = None,
+				retries = retries,
 				preload_content = False,
 				decode_content = True,
 				release_conn = False,
@@ -180,6 +197,56 @@ class PythonNetwork:
 		
 		return DeferredHttpResponse(response, exception)
 
+	def _manager_for_transport(self, transport: Optional[Mapping[str, Any]]) -> urllib3.PoolManager:
+		"""Returns a pool manager which sends requests through the proxy and with the TLS settings of `transport`."""
+		if transport is None or all(value is None for value in transport.values()):
+			return self._manager
+
+		client_certificate = transport.get("client_certificate") or {}
+		key = (
+			transport.get("proxy_url"),
+			transport.get("ca_pem"),
+			client_certificate.get("certificate"),
+			client_certificate.get("key")
+		)
+		manager = self._transport_managers.get(key)
+		if manager is not None:
+			return manager
+
+		(proxy_url, ca_pem, certificate, private_key) = key
+		try:
+			ssl_context = ssl.create_default_context(cadata = ca_pem)
+			if certificate is not None and private_key is not None:
+				# ssl can only load the certificate chain from files, so the private key is written to disk until it is loaded
+				# the directory is only accessible by the current user and the key file is created readable by its owner only
+				with tempfile.TemporaryDirectory() as directory:
+					certificate_path = os.path.join(directory, "certificate.pem")
+					key_path = os.path.join(directory, "key.pem")
+					with open(certificate_path, "w") as file:
+						file.write(certificate)
+					with os.fdopen(os.open(key_path, os.O_WRONLY | os.O_CREAT | os.O_EXCL, 0o600), "w") as file:
+						file.write(private_key)
+					ssl_context.load_cert_chain(certificate_path, key_path)
+		except (ssl.SSLError, ValueError) as err:
+			raise HostError(ErrorCode.NetworkError, f"Invalid transport TLS settings: {err}") from err
+
+		if proxy_url is None:
+			manager = urllib3.PoolManager(num_pools = 3, ssl_context = ssl_context)
+		else:
+			proxy = urllib3.util.parse_url(proxy_url)
+			proxy_headers = None
+			if proxy.auth is not None:
+				proxy_headers = urllib3.make_headers(proxy_basic_auth = unquote(proxy.auth))
+			manager = urllib3.ProxyManager(
+				proxy_url,
+				num_pools = 3,
+				proxy_headers = proxy_headers,
+				ssl_context = ssl_context
+			)
+
+		self._transport_managers[key] = manager
+		return manager
+
 class PythonPersistence:
 	def __init__(self, token: Optional[str] = None, superface_api_url: Union[None, str, bool] = None, user_agent: Optional[str] = None):
 		self._token = token
@@ -1,7 +1,7 @@
 import os
 import unittest
 
-from one_sdk import ValidationError
+from one_sdk import UnexpectedError, ValidationError
 from one_sdk.app import WasiApp
 from one_sdk.platform import PythonFilesystem, PythonNetwork, PythonPersistence
 
@@ -34,5 +34,33 @@ class TestApp(unittest.TestCase):
 				security = {}
 			)
 
+class TestAppSetup(unittest.TestCase):
+	def _app(self, core_config):
+		return WasiApp(
+			filesystem = PythonFilesystem,
+			network = PythonNetwork,
+			persistence = PythonPersistence,
+			core_config = core_config
+		)
+
+	def test_handshake(self):
+		app = self._app(None)
+		response = app.handle_message({ "kind": "handshake", "core_version": "0.0.0", "abi_version": 1, "message_kinds": [], "encodings": ["json"] })
+		self.assertEqual(response["kind"], "ok")
+		self.assertIn("core-config", response["message_kinds"])
+
+		response = app.handle_message({ "kind": "handshake", "core_version": "0.0.0", "abi_version": 2, "message_kinds": [], "encodings": ["json"] })
+		self.assertEqual(response["kind"], "err")
+
+	def test_core_config(self):
+		app = self._app({ "user_log": True })
+		self.assertEqual(app.handle_message({ "kind": "core-config" }), { "kind": "ok", "config": { "user_log": True } })
+
+	def test_setup_output_exception(self):
+		app = self._app(None)
+		response = app.handle_message({ "kind": "setup-output-exception", "exception": { "error_code": "InvalidConfiguration", "message": "Invalid vcr_mode" } })
+		self.assertEqual(response, { "kind": "ok" })
+		self.assertIsInstance(app._setup_exception, UnexpectedError)
+
 if __name__ == '__main__':
 	unittest.main()